@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE matchmaking_tickets;
+
+DROP TYPE enum_matchmaking_statuses;
\ No newline at end of file
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TYPE enum_matchmaking_statuses AS ENUM ('queued', 'searching', 'succeeded', 'cancelled', 'timed_out', 'failed');
+
+CREATE TABLE matchmaking_tickets (
+  id SERIAL PRIMARY KEY,
+  ticket_id uuid UNIQUE NOT NULL,
+  configuration_name VARCHAR(100) NOT NULL,
+  status enum_matchmaking_statuses NOT NULL DEFAULT 'queued',
+  started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ended_at TIMESTAMP NULL
+);
+
+CREATE INDEX matchmaking_tickets_configuration_name_ended_at_idx
+  ON matchmaking_tickets (configuration_name, ended_at);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE flexmatch_events (
+  id SERIAL PRIMARY KEY,
+  event_id VARCHAR(100) UNIQUE NOT NULL,
+  event_type VARCHAR(50) NOT NULL,
+  processed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
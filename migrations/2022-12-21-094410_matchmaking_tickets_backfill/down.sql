@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE matchmaking_tickets
+    DROP COLUMN is_backfill,
+    DROP COLUMN game_session_arn;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE matchmaking_tickets
+    ADD is_backfill BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD game_session_arn VARCHAR(256) NULL;
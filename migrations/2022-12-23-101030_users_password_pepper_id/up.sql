@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE users ADD password_pepper_id VARCHAR(32) NOT NULL DEFAULT '0';
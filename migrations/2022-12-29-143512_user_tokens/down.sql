@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users 
+    ADD reset_password_hash VARCHAR(159) NULL,
+    ADD password_hash_expire_at TIMESTAMP NULL;
+
+DROP TABLE user_tokens;
+
+DROP TYPE enum_user_token_purposes;
@@ -0,0 +1,35 @@
+-- Your SQL goes here
+CREATE TYPE enum_user_token_purposes AS ENUM ('email_confirmation', 'password_reset');
+
+CREATE TABLE user_tokens (
+  id SERIAL PRIMARY KEY,
+  token_id uuid UNIQUE NOT NULL,
+  user_id INT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  purpose enum_user_token_purposes NOT NULL,
+  token_hash VARCHAR(64) NOT NULL,
+  expires_at TIMESTAMP NOT NULL,
+  used_at TIMESTAMP NULL,
+  request_ip VARCHAR(45) NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX user_tokens_user_id_purpose_idx ON user_tokens (user_id, purpose);
+
+-- Links sent before the tokens stay valid until they expire, the whole hash is their secret.
+-- Email confirmations and password resets shared the column, an unconfirmed account keeps both uses.
+INSERT INTO user_tokens (token_id, user_id, purpose, token_hash, expires_at)
+SELECT md5(random()::text || clock_timestamp()::text || users.id)::uuid, users.id, 'password_reset', 
+    encode(sha256(convert_to(users.reset_password_hash, 'UTF8')), 'base64'), users.password_hash_expire_at
+FROM users
+WHERE users.reset_password_hash IS NOT NULL AND users.password_hash_expire_at > CURRENT_TIMESTAMP;
+
+INSERT INTO user_tokens (token_id, user_id, purpose, token_hash, expires_at)
+SELECT md5(random()::text || clock_timestamp()::text || users.id)::uuid, users.id, 'email_confirmation', 
+    encode(sha256(convert_to(users.reset_password_hash, 'UTF8')), 'base64'), users.password_hash_expire_at
+FROM users
+WHERE users.reset_password_hash IS NOT NULL AND users.password_hash_expire_at > CURRENT_TIMESTAMP
+    AND users.email_confirmation_required;
+
+ALTER TABLE users 
+    DROP COLUMN reset_password_hash,
+    DROP COLUMN password_hash_expire_at;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE rate_limit_attempts (
+  id SERIAL PRIMARY KEY,
+  attempt_key VARCHAR(400) NOT NULL,
+  attempted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX rate_limit_attempts_attempt_key_attempted_at_idx
+  ON rate_limit_attempts (attempt_key, attempted_at);
+
+CREATE TABLE account_lockouts (
+  lockout_key VARCHAR(400) PRIMARY KEY,
+  failures INT NOT NULL DEFAULT 0,
+  locked_until TIMESTAMP NULL
+);
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE refresh_tokens (
+  id SERIAL PRIMARY KEY,
+  token_id uuid UNIQUE NOT NULL,
+  family_id uuid NOT NULL,
+  user_id INT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  token_hash VARCHAR(64) NOT NULL,
+  expires_at TIMESTAMP NOT NULL,
+  used_at TIMESTAMP NULL,
+  revoked_at TIMESTAMP NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX refresh_tokens_family_id_idx ON refresh_tokens (family_id);
+CREATE INDEX refresh_tokens_user_id_idx ON refresh_tokens (user_id);
+
+-- incremented to end the access tokens already issued
+ALTER TABLE users ADD token_version INT NOT NULL DEFAULT 0;
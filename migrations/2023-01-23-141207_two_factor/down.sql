@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM user_tokens WHERE purpose = 'login_challenge';
+ALTER TABLE user_tokens ALTER purpose TYPE TEXT;
+DROP TYPE enum_user_token_purposes;
+CREATE TYPE enum_user_token_purposes AS ENUM ('email_confirmation', 'password_reset');
+ALTER TABLE user_tokens ALTER purpose TYPE enum_user_token_purposes USING purpose::enum_user_token_purposes;
+
+DROP TABLE user_recovery_codes;
+
+DROP TABLE user_totp;
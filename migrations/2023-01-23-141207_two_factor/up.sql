@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TABLE user_totp (
+  id SERIAL PRIMARY KEY,
+  user_id INT UNIQUE NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  secret VARCHAR(128) NOT NULL,
+  enabled_at TIMESTAMP NULL,
+  last_used_step BIGINT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE user_recovery_codes (
+  id SERIAL PRIMARY KEY,
+  user_id INT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  code_hash VARCHAR(64) NOT NULL,
+  used_at TIMESTAMP NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX user_recovery_codes_user_id_idx ON user_recovery_codes (user_id);
+
+ALTER TABLE user_tokens ALTER purpose TYPE TEXT;
+DROP TYPE enum_user_token_purposes;
+CREATE TYPE enum_user_token_purposes AS ENUM ('email_confirmation', 'password_reset', 'login_challenge');
+ALTER TABLE user_tokens ALTER purpose TYPE enum_user_token_purposes USING purpose::enum_user_token_purposes;
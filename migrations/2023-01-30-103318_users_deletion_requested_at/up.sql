@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE users ADD deletion_requested_at TIMESTAMP NULL;
@@ -0,0 +1,43 @@
+-- Your SQL goes here
+-- case-only duplicate emails: the confirmed then oldest account keeps the address, the others
+-- get an undeliverable one made from it, to be sorted out by hand
+UPDATE users u SET
+  email = LEFT(REPLACE(u.email, '@', '='), 60) || '.' || u.id || '@duplicate.invalid',
+  email_confirmation_required = TRUE
+FROM (
+  SELECT id, ROW_NUMBER() OVER (PARTITION BY LOWER(email) ORDER BY email_confirmation_required, id) AS rank
+  FROM users
+) ranked
+WHERE ranked.id = u.id AND ranked.rank > 1;
+
+-- case-only duplicate nicknames: the newer accounts get a suffix, cut to fit the 32 characters
+-- of a nickname and numbered until it is free
+DO $$
+DECLARE
+  duplicate RECORD;
+  suffix TEXT;
+  candidate TEXT;
+  attempt INT;
+BEGIN
+  FOR duplicate IN
+    SELECT u.id, u.nickname FROM users u
+    WHERE EXISTS (
+      SELECT 1 FROM users other
+      WHERE LOWER(other.nickname) = LOWER(u.nickname) AND other.id < u.id
+    )
+    ORDER BY u.id
+  LOOP
+    attempt := 0;
+    LOOP
+      suffix := '_' || duplicate.id || CASE WHEN attempt > 0 THEN '_' || attempt ELSE '' END;
+      candidate := LEFT(duplicate.nickname, 32 - LENGTH(suffix)) || suffix;
+      EXIT WHEN NOT EXISTS (SELECT 1 FROM users WHERE LOWER(nickname) = LOWER(candidate));
+      attempt := attempt + 1;
+    END LOOP;
+    UPDATE users SET nickname = candidate WHERE id = duplicate.id;
+  END LOOP;
+END $$;
+
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+CREATE UNIQUE INDEX users_email_lower_key ON users (LOWER(email));
+CREATE UNIQUE INDEX users_nickname_lower_key ON users (LOWER(nickname));
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+UPDATE users SET steam_id = 'unlinked-' || id WHERE steam_id IS NULL;
+ALTER TABLE users ALTER steam_id SET NOT NULL;
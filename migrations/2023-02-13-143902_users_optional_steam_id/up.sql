@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE users ALTER steam_id DROP NOT NULL;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE steam_ban_records;
+
+DROP TABLE bans;
+
+DROP TYPE enum_ban_scopes;
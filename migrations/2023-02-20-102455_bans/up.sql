@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TYPE enum_ban_scopes AS ENUM ('login', 'matchmaking', 'chat');
+
+CREATE TABLE bans (
+  id SERIAL PRIMARY KEY,
+  user_id INT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  scope enum_ban_scopes NOT NULL,
+  reason TEXT NOT NULL,
+  expires_at TIMESTAMP NULL,
+  issued_by INT NULL REFERENCES users(id) ON DELETE SET NULL,
+  revoked_at TIMESTAMP NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX bans_user_id_idx ON bans (user_id);
+
+CREATE TABLE steam_ban_records (
+  id SERIAL PRIMARY KEY,
+  user_id INT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  steam_id TEXT NOT NULL,
+  vac_banned BOOLEAN NOT NULL,
+  publisher_banned BOOLEAN NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX steam_ban_records_user_id_idx ON steam_ban_records (user_id);
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+CREATE TYPE enum_user_roles AS ENUM ('player', 'moderator', 'admin');
+
+ALTER TABLE users ADD COLUMN role enum_user_roles NOT NULL DEFAULT 'player';
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE audit_events;
+
+DROP TYPE enum_audit_outcomes;
+
+DROP TYPE enum_audit_event_types;
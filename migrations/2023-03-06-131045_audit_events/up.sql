@@ -0,0 +1,35 @@
+-- Your SQL goes here
+CREATE TYPE enum_audit_event_types AS ENUM (
+  'account_creation',
+  'login',
+  'two_factor_login',
+  'steam_login',
+  'password_reset_request',
+  'password_reset',
+  'password_change',
+  'email_change',
+  'email_confirmation',
+  'steam_link',
+  'steam_unlink',
+  'two_factor_enable',
+  'two_factor_disable',
+  'account_deletion',
+  'account_restore'
+);
+
+CREATE TYPE enum_audit_outcomes AS ENUM ('success', 'failure');
+
+CREATE TABLE audit_events (
+  id SERIAL PRIMARY KEY,
+  -- the events of a purged account are kept anonymised
+  user_id INT NULL REFERENCES users(id) ON DELETE SET NULL,
+  event_type enum_audit_event_types NOT NULL,
+  outcome enum_audit_outcomes NOT NULL,
+  ip VARCHAR(64) NULL,
+  user_agent VARCHAR(512) NULL,
+  details TEXT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX audit_events_user_id_created_at_idx ON audit_events (user_id, created_at);
+CREATE INDEX audit_events_created_at_idx ON audit_events (created_at);
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+CREATE TYPE enum_languages AS ENUM ('en', 'fr');
+
+ALTER TABLE users ADD COLUMN language enum_languages NOT NULL DEFAULT 'en';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE email_outbox;
+
+DROP TYPE enum_email_statuses;
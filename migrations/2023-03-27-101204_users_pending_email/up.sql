@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- new address of an email change, applied once confirmed
+ALTER TABLE users ADD COLUMN pending_email VARCHAR(100) NULL;
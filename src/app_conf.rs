@@ -1,23 +1,47 @@
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
-use actix_web::{cookie::Key, middleware};
+use actix_web::{cookie::Key, middleware, web};
 use super::{Pool};
-use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+use actix_session::{config::BrowserSession, config::TtlExtensionPolicy, SessionMiddleware};
+use crate::services::session::store::PgSessionStore;
+#[cfg(debug_assertions)]
+use crate::services::steam;
+use std::net::IpAddr;
 
 pub mod static_routes;
 pub mod open_routes;
+pub mod admin_routes;
 pub mod api_routes;
 pub mod ws_routes;
 pub mod aws_routes;
+pub mod game_server_routes;
+pub mod bearer_auth;
+pub mod deletion_guard;
 
 lazy_static::lazy_static! {
     pub static ref SECRET_KEY: String = std::env::var("SECRET_KEY").unwrap_or_else(|_| "0123".repeat(16));
+    /// `TRUSTED_PROXIES`, comma separated addresses of the reverse proxies allowed to set X-Forwarded-For
+    pub static ref TRUSTED_PROXIES: Vec<IpAddr> = std::env::var("TRUSTED_PROXIES").unwrap_or_default()
+        .split(',')
+        .filter(|address| !address.trim().is_empty())
+        .map(|address| address.trim().parse::<IpAddr>()
+            .unwrap_or_else(|_| panic!("Unknown TRUSTED_PROXIES address: {}", address)))
+        .collect();
 }
 
-pub fn middleware_cookie_session() -> SessionMiddleware<CookieSessionStore> {
-    SessionMiddleware::new(
-        CookieSessionStore::default(), 
+/// Sessions stored in Postgres, they expire after `SESSION_TTL_SECS` (default 7 days) without request.
+pub fn middleware_session(pool: Pool) -> SessionMiddleware<PgSessionStore> {
+    let ttl = std::env::var("SESSION_TTL_SECS").ok()
+        .map(|secs| secs.parse::<i64>().expect("SESSION_TTL_SECS must be a number of seconds"))
+        .unwrap_or(7 * 24 * 3600);
+
+    SessionMiddleware::builder(
+        PgSessionStore::new(pool), 
         Key::from(SECRET_KEY.as_bytes()))
+        .session_lifecycle(BrowserSession::default()
+            .state_ttl(actix_web::cookie::time::Duration::seconds(ttl))
+            .state_ttl_extension_policy(TtlExtensionPolicy::OnEveryRequest))
+        .build()
 }
 
 #[cfg(debug_assertions)]
@@ -25,9 +49,11 @@ pub fn nb_worker() -> Option<u32> {
     None
 }
 
-#[cfg(debug_assertions)]
+/// Default format without the query string, it carries the access token of the websocket
 pub fn middleware_logger() -> middleware::Logger {
-    middleware::Logger::default()
+    middleware::Logger::new(r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#)
+        .custom_request_replace("request_line", |req| 
+            format!("{} {} {:?}", req.method(), req.path(), req.version()))
 }
 
 #[cfg(debug_assertions)]
@@ -40,6 +66,12 @@ pub fn set_env() {
     env_logger::init();
 }
 
+/// Local stand-ins of the external services, see `steam::stub`
+#[cfg(debug_assertions)]
+pub fn dev_routes(cfg: &mut web::ServiceConfig) {
+    steam::stub::configure(cfg);
+}
+
 #[cfg(debug_assertions)]
 pub fn connect_database() -> Pool {
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
@@ -63,6 +95,28 @@ pub fn get_base_url() -> String {
     format!("http://{}", get_listen_address())
 }
 
+/// Interval of the DescribeMatchmaking polling, disabled when `MATCHMAKING_POLLING_INTERVAL_SECS` is not set.
+pub fn matchmaking_polling_interval() -> Option<std::time::Duration> {
+    std::env::var("MATCHMAKING_POLLING_INTERVAL_SECS").ok()
+        .map(|secs| secs.parse::<u64>().expect("MATCHMAKING_POLLING_INTERVAL_SECS must be a number of seconds"))
+        .filter(|secs| *secs > 0)
+        .map(std::time::Duration::from_secs)
+}
+
+/// Interval of the deletion of the accounts past their grace period, `ACCOUNT_PURGE_INTERVAL_SECS` (1 hour by default).
+pub fn account_purge_interval() -> std::time::Duration {
+    std::time::Duration::from_secs(std::env::var("ACCOUNT_PURGE_INTERVAL_SECS").ok()
+        .map(|secs| secs.parse::<u64>().expect("ACCOUNT_PURGE_INTERVAL_SECS must be a number of seconds"))
+        .unwrap_or(3600))
+}
+
+/// Interval at which the outbox is checked for emails to send, `EMAIL_OUTBOX_INTERVAL_SECS` (5 seconds by default).
+pub fn email_outbox_interval() -> std::time::Duration {
+    std::time::Duration::from_secs(std::env::var("EMAIL_OUTBOX_INTERVAL_SECS").ok()
+        .map(|secs| secs.parse::<u64>().expect("EMAIL_OUTBOX_INTERVAL_SECS must be a number of seconds"))
+        .unwrap_or(5))
+}
+
 fn get_domain() -> String {
     std::env::var("DOMAIN").unwrap_or_else(|_| "localhost".to_string())
 }
@@ -76,29 +130,43 @@ pub fn nb_worker() -> Option<u32> {
     Some(max_nb_workers)
 }
 
-#[cfg(not(debug_assertions))]
-pub fn middleware_logger() -> middleware::Logger {
-    middleware::Logger::default()
-}
-
 #[cfg(not(debug_assertions))]
 pub fn set_env() {
     //check postgre URI
     std::env::var("POSTGRESQL_ADDON_URI").expect("Missing POSTGRESQL_ADDON_URI env variable.");
     std::env::var("DOMAIN").expect("Missing DOMAIN env variable.");
-    std::env::var("EMAIL_DOMAIN").expect("Missing EMAIL_DOMAIN env variable.");
-    std::env::var("EMAIL_KEY").expect("Missing EMAIL_KEY env variable.");
+    match std::env::var("EMAIL_TRANSPORT").unwrap_or_default().as_str() {
+        "smtp" => {
+            std::env::var("SMTP_HOST").expect("Missing SMTP_HOST env variable.");
+        },
+        "file" => (),
+        _ => {
+            std::env::var("EMAIL_DOMAIN").expect("Missing EMAIL_DOMAIN env variable.");
+            std::env::var("EMAIL_KEY").expect("Missing EMAIL_KEY env variable.");
+        }
+    }
     std::env::var("EMAIL_DEFAULT_ADDRESS").expect("Missing EMAIL_DEFAULT_ADDRESS env variable.");
     std::env::var("MAX_NB_WORKERS").expect("Missing MAX_NB_WORKERS env variable.");
     std::env::var("MAX_DB_CONNS_WORKER").expect("Missing MAX_DB_CONNS_WORKER env variable.");
-    std::env::var("AWS_ACCESS_KEY_ID").expect("Missing AWS_ACCESS_KEY_ID env variable.");
-    std::env::var("AWS_SECRET_ACCESS_KEY").expect("Missing AWS_SECRET_ACCESS_KEY env variable.");
+    if std::env::var("GAMELIFT_BACKEND").unwrap_or_default() != "simulator" {
+        std::env::var("AWS_ACCESS_KEY_ID").expect("Missing AWS_ACCESS_KEY_ID env variable.");
+        std::env::var("AWS_SECRET_ACCESS_KEY").expect("Missing AWS_SECRET_ACCESS_KEY env variable.");
+    }
+    std::env::var("AWS_SNS_TOPIC_ARNS").expect("Missing AWS_SNS_TOPIC_ARNS env variable.");
+    std::env::var("GAME_SERVER_KEY").expect("Missing GAME_SERVER_KEY env variable.");
     std::env::var("SECRET_KEY").expect("Missing SECRET_KEY env variable.");
     std::env::var("STEAM_SECRET_ACCESS_KEY").expect("Missing STEAM_SECRET_ACCESS_KEY env variable");
+    if std::env::var("STEAM_STUB_STEAM_ID").is_ok() {
+        panic!("STEAM_STUB_STEAM_ID must not be set in release builds, the Steam stub is for development only.");
+    }
 
     env_logger::init();
 }
 
+/// The stubs are never mounted in release builds
+#[cfg(not(debug_assertions))]
+pub fn dev_routes(_cfg: &mut web::ServiceConfig) {}
+
 #[cfg(not(debug_assertions))]
 pub fn connect_database() -> Pool {
     let database_url = std::env::var("POSTGRESQL_ADDON_URI").expect("POSTGRESQL_ADDON_URI must be set");
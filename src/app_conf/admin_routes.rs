@@ -0,0 +1,37 @@
+use actix_web::{web, Scope};
+use crate::handlers::admin;
+
+/// Registered before the `/api` scope which would shadow it
+pub fn get_all() -> Scope {
+    web::scope("/api/admin")
+        .service(
+            web::resource("/users")
+                .route(web::get().to(admin::search_users)))
+        .service(
+            web::resource("/users/{id}")
+                .route(web::get().to(admin::get_user)))
+        .service(
+            web::resource("/users/{id}/role")
+                .route(web::put().to(admin::set_role)))
+        .service(
+            web::resource("/users/{id}/bans")
+                .route(web::post().to(admin::issue_ban)))
+        .service(
+            web::resource("/bans/{id}")
+                .route(web::delete().to(admin::revoke_ban)))
+        .service(
+            web::resource("/custom-rooms/{id}")
+                .route(web::delete().to(admin::delete_custom_room)))
+        .service(
+            web::resource("/matchmaking-tickets/{ticket_id}")
+                .route(web::delete().to(admin::stop_matchmaking_ticket)))
+        .service(
+            web::resource("/live-sessions")
+                .route(web::get().to(admin::get_live_sessions)))
+        .service(
+            web::resource("/audit-events")
+                .route(web::get().to(admin::get_audit_events)))
+        .service(
+            web::resource("/failed-emails")
+                .route(web::get().to(admin::get_failed_emails)))
+}
@@ -1,5 +1,5 @@
 use actix_web::{web, Scope};
-use crate::handlers::{custom_room, auth};
+use crate::handlers::{custom_room, auth, session, two_factor, user};
 
 pub fn get_all() -> Scope {
     web::scope("/api")
@@ -7,8 +7,44 @@ pub fn get_all() -> Scope {
             web::resource("/logout")
                 .route(web::post().to(auth::logout)))
         .service(
-            web::resource("/refresh-cookie")
-                .route(web::get().to(auth::refresh_cookie)))
+            web::resource("/users/me")
+                .route(web::get().to(user::get_me))
+                .route(web::put().to(user::update_me))
+                .route(web::delete().to(user::delete_me)))
+        .service(
+            web::resource("/users/me/steam")
+                .route(web::put().to(user::link_steam))
+                .route(web::delete().to(user::unlink_steam)))
+        .service(
+            web::resource("/users/me/restore")
+                .route(web::post().to(user::restore_me)))
+        .service(
+            web::resource("/users/me/export")
+                .route(web::get().to(user::export_me)))
+        .service(
+            web::resource("/users/me/password")
+                .route(web::put().to(user::change_password)))
+        .service(
+            web::resource("/users/me/email")
+                .route(web::put().to(user::change_email)))
+        .service(
+            web::resource("/users/me/activity")
+                .route(web::get().to(user::get_activity)))
+        .service(
+            web::resource("/sessions")
+                .route(web::get().to(session::get_all)))
+        .service(
+            web::resource("/sessions/{id}")
+                .route(web::delete().to(session::delete)))
+        .service(
+            web::resource("/2fa")
+                .route(web::delete().to(two_factor::disable)))
+        .service(
+            web::resource("/2fa/setup")
+                .route(web::post().to(two_factor::setup)))
+        .service(
+            web::resource("/2fa/enable")
+                .route(web::post().to(two_factor::enable)))
         .service(
             web::resource("/matchmaking/custom-room")
                 .route(web::get().to(custom_room::get_all))
@@ -36,4 +72,7 @@ pub fn get_all() -> Scope {
         .service(
             web::resource("/matchmaking/custom-room/{id}/stop-matchmaking")
                 .route(web::put().to(custom_room::stop_matchmaking)))
+        .service(
+            web::resource("/matchmaking/custom-room/{id}/matchmaking-status")
+                .route(web::get().to(custom_room::matchmaking_status)))
 }
\ No newline at end of file
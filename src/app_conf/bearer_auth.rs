@@ -0,0 +1,101 @@
+use actix_session::SessionExt;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error, HttpRequest};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use std::rc::Rc;
+use crate::services::token;
+use crate::Pool;
+
+// Session keys read by `actix_identity::Identity`
+const IDENTITY_KEY: &str = "actix_identity.user_id";
+const WEBSOCKET_PATH: &str = "/ws";
+
+/// Authenticates requests carrying an access token in an `Authorization: Bearer` header, or in
+/// the `access_token` query parameter for the websocket upgrade, so that `Identity` works as with
+/// a session cookie. Tokens revoked since they were issued are ignored. Must be wrapped inside
+/// the session middleware.
+pub struct BearerAuth;
+
+impl<S, B> Transform<S, ServiceRequest> for BearerAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = BearerAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(BearerAuthMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct BearerAuthMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for BearerAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let access_token = get_access_token(req.request());
+        let pool = req.app_data::<web::Data<Pool>>().cloned();
+
+        Box::pin(async move {
+            let session = req.get_session();
+            // a session cookie takes precedence over the token
+            let mut injected = false;
+            if let (Some(access_token), Some(pool), Ok(None)) = (access_token, pool, session.get::<String>(IDENTITY_KEY)) {
+                let user_id = web::block(move || token::verify_access_token(&access_token, &pool.get().unwrap()))
+                    .await
+                    .ok()
+                    .flatten();
+                if let Some(user_id) = user_id {
+                    injected = session.insert(IDENTITY_KEY, user_id.to_string()).is_ok();
+                }
+            }
+
+            let res = service.call(req).await;
+            // the identity only lasts for the request, nothing is stored
+            if injected {
+                session.remove(IDENTITY_KEY);
+            }
+            res
+        })
+    }
+}
+
+/// Whether the request is authenticated by an access token rather than a session cookie
+pub fn has_access_token(req: &HttpRequest) -> bool {
+    get_access_token(req).is_some()
+}
+
+fn get_access_token(req: &HttpRequest) -> Option<String> {
+    if let Some(header) = req.headers().get("authorization") {
+        return header.to_str().ok()?
+            .strip_prefix("Bearer ")
+            .map(|access_token| access_token.trim().to_string())
+    }
+
+    if req.path() == WEBSOCKET_PATH {
+        return req.query_string().split('&')
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| *name == "access_token")
+            .map(|(_, access_token)| access_token.to_string())
+    }
+
+    None
+}
@@ -0,0 +1,79 @@
+use actix_identity::IdentityExt;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::{web, Error};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use std::rc::Rc;
+use crate::errors::AppError;
+use crate::services::user as user_service;
+use crate::Pool;
+
+/// Routes of the signed in users, including the websocket
+const GUARDED_PREFIXES: [&str; 2] = ["/api/", "/ws"];
+
+/// What an account whose deletion is pending can still do: see and export its data, restore it
+/// and sign out
+const ALLOWED_ROUTES: [(Method, &str); 4] = [
+    (Method::GET, "/api/users/me"),
+    (Method::GET, "/api/users/me/export"),
+    (Method::POST, "/api/users/me/restore"),
+    (Method::POST, "/api/logout"),
+];
+
+/// Refuses the requests of the accounts whose deletion is pending, until they are restored or
+/// purged. Must be wrapped inside the bearer token and identity middlewares.
+pub struct DeletionGuard;
+
+impl<S, B> Transform<S, ServiceRequest> for DeletionGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = DeletionGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeletionGuardMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct DeletionGuardMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for DeletionGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let allowed = !GUARDED_PREFIXES.iter().any(|prefix| req.path().starts_with(prefix))
+            || ALLOWED_ROUTES.iter().any(|(method, path)| req.method() == method && req.path() == *path);
+        let user_id = req.get_identity().ok()
+            .and_then(|id| id.id().ok())
+            .and_then(|user_id| user_id.parse::<i32>().ok());
+        let pool = req.app_data::<web::Data<Pool>>().cloned();
+
+        Box::pin(async move {
+            if let (false, Some(user_id), Some(pool)) = (allowed, user_id, pool) {
+                web::block(move || user_service::check_not_deleting(user_id, &pool.get().unwrap()))
+                    .await
+                    .map_err(AppError::from)??;
+            }
+
+            service.call(req).await
+        })
+    }
+}
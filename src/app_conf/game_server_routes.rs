@@ -0,0 +1,12 @@
+use actix_web::{web, Scope};
+use crate::handlers::game_server;
+
+pub fn get_all() -> Scope {
+    web::scope("/game-server")
+        .service(
+            web::resource("/backfill")
+                .route(web::post().to(game_server::backfill)))
+        .service(
+            web::resource("/players/{user_id}/bans")
+                .route(web::get().to(game_server::get_player_bans)))
+}
@@ -10,12 +10,27 @@ pub fn get_all() -> Scope {
         .service(
             web::resource("/login")
                 .route(web::post().to(auth::login)))
+        .service(
+            web::resource("/login/2fa")
+                .route(web::post().to(auth::login_two_factor)))
         .service(
             web::resource("/login-steam")
                 .route(web::post().to(auth::login_steam)))
+        .service(
+            web::resource("/steam/openid/login")
+                .route(web::get().to(auth::steam_openid_login)))
+        .service(
+            web::resource("/steam/openid/callback")
+                .route(web::get().to(auth::steam_openid_callback)))
+        .service(
+            web::resource("/token/refresh")
+                .route(web::post().to(auth::refresh_token)))
         .service(
             web::resource("/user/create")
                 .route(web::post().to(user::create)))
+        .service(
+            web::resource("/user/nickname-availability")
+                .route(web::get().to(user::nickname_availability)))
         .service(
             web::resource("/email-confirmation")
                 .route(web::post().to(auth::email_confirmation))
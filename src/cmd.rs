@@ -64,24 +64,25 @@ mod db {
         use crate::models::user;
         use crate::models::forms::user::UserForm;
         use crate::chrono::NaiveDateTime;
+        use crate::enums::Languages;
 
         let nb_users = 10;
         let pass_hash = auth::hash_password("spike").unwrap();
         let conn = &app_conf::connect_database().get().unwrap();
-        let email_confirmation_hash = "toto";
-
         for i in 0..nb_users {
-            user::create(UserForm {
+            let user = user::create(UserForm {
                 email: format!("{}@spikegames.eu", i).as_str(),
                 nickname: format!("Spike{}", i).as_str(),
-                steam_id: format!("{}", i).as_str(),
+                steam_id: Some(format!("{}", i).as_str()),
                 first_name: "Spike",
                 last_name: format!("{}", i).as_str(),
                 hash: &pass_hash,
+                password_pepper_id: auth::current_pepper_id(),
                 birth_date: NaiveDateTime::default(),
-            }, email_confirmation_hash, conn).unwrap();
+                language: Languages::En,
+            }, conn).unwrap();
             
-            user::confirm_email(email_confirmation_hash, conn).unwrap();
+            user::confirm_email(&user.id, conn).unwrap();
         }        
     }
 }
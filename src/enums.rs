@@ -79,4 +79,221 @@ impl Display for Maps {
     fn fmt(&self, f: &mut Formatter) -> Result {
         write!(f, "{:?}", self)
     }
-}
\ No newline at end of file
+}
+
+#[derive(Eq, Hash, Deserialize, PartialEq, Serialize, Debug, DbEnum, Clone, Copy)]
+#[PgType = "enum_matchmaking_statuses"]
+#[DieselType = "Enum_matchmaking_statuses"]
+pub enum MatchmakingStatuses {
+    #[db_rename = "queued"]
+    Queued,
+    #[db_rename = "searching"]
+    Searching,
+    #[db_rename = "succeeded"]
+    Succeeded,
+    #[db_rename = "cancelled"]
+    Cancelled,
+    #[db_rename = "timed_out"]
+    TimedOut,
+    #[db_rename = "failed"]
+    Failed,
+}
+
+impl MatchmakingStatuses {
+    pub fn from_aws_status(value: &str) -> Option<MatchmakingStatuses> {
+        match value {
+            "QUEUED" => Some(MatchmakingStatuses::Queued),
+            "SEARCHING" | "REQUIRES_ACCEPTANCE" | "PLACING" => Some(MatchmakingStatuses::Searching),
+            "COMPLETED" => Some(MatchmakingStatuses::Succeeded),
+            "CANCELLED" => Some(MatchmakingStatuses::Cancelled),
+            "TIMED_OUT" => Some(MatchmakingStatuses::TimedOut),
+            "FAILED" => Some(MatchmakingStatuses::Failed),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, MatchmakingStatuses::Queued | MatchmakingStatuses::Searching)
+    }
+
+    /// Position in the ticket lifecycle, a ticket never goes back to a lower rank.
+    pub fn rank(&self) -> u32 {
+        match self {
+            MatchmakingStatuses::Queued => 0,
+            MatchmakingStatuses::Searching => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl Display for MatchmakingStatuses {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Eq, Hash, Deserialize, PartialEq, Serialize, Debug, DbEnum, Clone, Copy)]
+#[PgType = "enum_user_token_purposes"]
+#[DieselType = "Enum_user_token_purposes"]
+pub enum UserTokenPurposes {
+    #[db_rename = "email_confirmation"]
+    EmailConfirmation,
+    #[db_rename = "password_reset"]
+    PasswordReset,
+    #[db_rename = "login_challenge"]
+    LoginChallenge,
+}
+
+impl Display for UserTokenPurposes {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Eq, Hash, Deserialize, PartialEq, Serialize, Debug, DbEnum, Clone, Copy)]
+#[PgType = "enum_ban_scopes"]
+#[DieselType = "Enum_ban_scopes"]
+pub enum BanScopes {
+    #[db_rename = "login"]
+    Login,
+    #[db_rename = "matchmaking"]
+    Matchmaking,
+    #[db_rename = "chat"]
+    Chat,
+}
+
+impl Display for BanScopes {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Declared from the least to the most privileged, a role has the rights of the lower ones.
+#[derive(Eq, Hash, Deserialize, PartialEq, PartialOrd, Ord, Serialize, Debug, DbEnum, Clone, Copy)]
+#[PgType = "enum_user_roles"]
+#[DieselType = "Enum_user_roles"]
+pub enum UserRoles {
+    #[db_rename = "player"]
+    Player,
+    #[db_rename = "moderator"]
+    Moderator,
+    #[db_rename = "admin"]
+    Admin,
+}
+
+impl Display for UserRoles {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Languages of the emails sent to a user
+#[derive(Eq, Hash, Deserialize, PartialEq, Serialize, Debug, DbEnum, Clone, Copy, Default)]
+#[PgType = "enum_languages"]
+#[DieselType = "Enum_languages"]
+pub enum Languages {
+    #[default]
+    #[db_rename = "en"]
+    En,
+    #[db_rename = "fr"]
+    Fr,
+}
+
+impl Languages {
+    pub fn from_code(code: &str) -> Option<Languages> {
+        match code.split(['-', '_']).next()?.to_lowercase().as_str() {
+            "en" => Some(Languages::En),
+            "fr" => Some(Languages::Fr),
+            _ => None
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Languages::En => "en",
+            Languages::Fr => "fr",
+        }
+    }
+}
+
+impl Display for Languages {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Eq, Hash, Deserialize, PartialEq, Serialize, Debug, DbEnum, Clone, Copy)]
+#[PgType = "enum_audit_event_types"]
+#[DieselType = "Enum_audit_event_types"]
+pub enum AuditEventTypes {
+    #[db_rename = "account_creation"]
+    AccountCreation,
+    #[db_rename = "login"]
+    Login,
+    #[db_rename = "two_factor_login"]
+    TwoFactorLogin,
+    #[db_rename = "steam_login"]
+    SteamLogin,
+    #[db_rename = "password_reset_request"]
+    PasswordResetRequest,
+    #[db_rename = "password_reset"]
+    PasswordReset,
+    #[db_rename = "password_change"]
+    PasswordChange,
+    #[db_rename = "email_change"]
+    EmailChange,
+    #[db_rename = "email_confirmation"]
+    EmailConfirmation,
+    #[db_rename = "steam_link"]
+    SteamLink,
+    #[db_rename = "steam_unlink"]
+    SteamUnlink,
+    #[db_rename = "two_factor_enable"]
+    TwoFactorEnable,
+    #[db_rename = "two_factor_disable"]
+    TwoFactorDisable,
+    #[db_rename = "account_deletion"]
+    AccountDeletion,
+    #[db_rename = "account_restore"]
+    AccountRestore,
+}
+
+impl Display for AuditEventTypes {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Eq, Hash, Deserialize, PartialEq, Serialize, Debug, DbEnum, Clone, Copy)]
+#[PgType = "enum_audit_outcomes"]
+#[DieselType = "Enum_audit_outcomes"]
+pub enum AuditOutcomes {
+    #[db_rename = "success"]
+    Success,
+    #[db_rename = "failure"]
+    Failure,
+}
+
+impl Display for AuditOutcomes {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Emails of the outbox, a sent email is removed from it
+#[derive(Eq, Hash, Deserialize, PartialEq, Serialize, Debug, DbEnum, Clone, Copy)]
+#[PgType = "enum_email_statuses"]
+#[DieselType = "Enum_email_statuses"]
+pub enum EmailStatuses {
+    #[db_rename = "pending"]
+    Pending,
+    /// Given up after too many attempts
+    #[db_rename = "failed"]
+    Failed,
+}
+
+impl Display for EmailStatuses {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
@@ -1,10 +1,13 @@
 use actix_web::{error::{BlockingError, ResponseError, PayloadError}, HttpResponse};
 use derive_more::Display;
-use diesel::result::{Error as DBError};
+use diesel::result::{DatabaseErrorKind, Error as DBError};
 use std::convert::From;
 use serde_json;
 use serde::{Serialize};
 use awc::error::{SendRequestError, HttpError};
+use chrono::NaiveDateTime;
+use crate::services::ban::BanDto;
+use crate::validation::ValidationErrors;
 
 pub type AppResult<R> = Result<R, AppError>;
 
@@ -24,6 +27,24 @@ pub enum AppError {
 
     #[display(fmt = "Forbidden")]
     Forbidden,
+
+    #[display(fmt = "Too Many Requests: {}", _0)]
+    TooManyRequests(String),
+
+    #[display(fmt = "Validation: {:?}", _0)]
+    Validation(ValidationErrors),
+
+    /// A unique value is already used, holds the offending field
+    #[display(fmt = "Conflict: {}", _0)]
+    Conflict(String),
+
+    /// The user is banned from the action
+    #[display(fmt = "Banned: {:?}", _0)]
+    Banned(BanDto),
+
+    /// The account is to be deleted at the given date, it must be restored first
+    #[display(fmt = "Deletion Pending: {}", _0)]
+    DeletionPending(NaiveDateTime),
 }
 
 // impl ResponseError trait allows to convert our errors into http responses with appropriate data
@@ -45,6 +66,21 @@ impl ResponseError for AppError {
             AppError::Forbidden => {
                 HttpResponse::Forbidden().json("Forbidden")
             }
+            AppError::TooManyRequests(ref message) => {
+                HttpResponse::TooManyRequests().json(message)
+            }
+            AppError::Validation(ref errors) => {
+                HttpResponse::BadRequest().json(serde_json::json!({ "errors": errors }))
+            }
+            AppError::Conflict(ref field) => {
+                HttpResponse::Conflict().json(serde_json::json!({ "errors": { field: ["Already taken."] } }))
+            }
+            AppError::Banned(ref ban) => {
+                HttpResponse::Forbidden().json(serde_json::json!({ "ban": ban }))
+            }
+            AppError::DeletionPending(ref deletion_scheduled_at) => {
+                HttpResponse::Forbidden().json(serde_json::json!({ "deletion_scheduled_at": deletion_scheduled_at }))
+            }
         }
     }
 }
@@ -52,6 +88,9 @@ impl ResponseError for AppError {
 impl From<DBError> for AppError {
     fn from(error: DBError) -> AppError {
         match error {
+            DBError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                AppError::Conflict(get_conflict_field(info.constraint_name()).to_string())
+            },
             DBError::DatabaseError(_kind, info) => {
                 return AppError::BadRequest(info.details().unwrap_or_else(|| info.message()).to_string());
             },
@@ -63,6 +102,18 @@ impl From<DBError> for AppError {
     }
 }
 
+/// Field of the payloads matching a unique constraint
+fn get_conflict_field(constraint_name: Option<&str>) -> &'static str {
+    match constraint_name {
+        Some("users_email_lower_key") => "email",
+        Some("users_nickname_lower_key") => "nickname",
+        Some("users_steam_id_key") => "steam_id",
+        Some("custom_rooms_user_id_key") => "custom_room",
+        Some("custom_room_slots_user_id_key") => "custom_room_slot",
+        _ => "id"
+    }
+}
+
 impl From<serde_json::Error> for AppError {
     fn from(error: serde_json::Error) -> AppError {
         AppError::InternalServerError(format!("Error while parsing json. {}", error.to_string()))
@@ -1,25 +1,83 @@
 use actix_identity::Identity;
-use actix_web::{web::Data, web::Payload, HttpResponse, HttpRequest};
+use actix_web::{web, web::Data, web::Payload, HttpResponse, HttpRequest, http::header::HeaderMap};
+use crate::app_conf::TRUSTED_PROXIES;
+use crate::enums::Languages;
 use crate::errors::*;
 use crate::services::websocket::{new_connection, WebsocketLobby};
+use crate::services::ban as ban_service;
+use crate::Pool;
 use actix::Addr;
+use std::net::IpAddr;
 
+pub mod admin;
 pub mod auth;
 pub mod custom_room;
 pub mod aws;
+pub mod game_server;
+pub mod session;
+pub mod two_factor;
 pub mod user;
 
+/// Client address. X-Forwarded-For is only followed from the `TRUSTED_PROXIES`, its addresses
+/// are read from the last one since a client can put anything before those of the proxies.
+pub fn get_request_ip(request: &HttpRequest) -> Option<String> {
+    let peer_ip = request.peer_addr()?.ip();
+    Some(get_client_ip(peer_ip, request.headers(), &TRUSTED_PROXIES).to_string())
+}
+
+fn get_client_ip(peer_ip: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client_ip = peer_ip;
+    let forwarded_ips = headers.get_all("x-forwarded-for")
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(','))
+        .collect::<Vec<_>>();
+
+    for forwarded_ip in forwarded_ips.into_iter().rev() {
+        if !trusted_proxies.contains(&client_ip) {
+            break;
+        }
+        match forwarded_ip.trim().parse::<IpAddr>() {
+            Ok(ip) => client_ip = ip,
+            Err(_) => break
+        }
+    }
+
+    client_ip
+}
+
+/// First supported language of the `Accept-Language` header
+pub fn get_request_language(request: &HttpRequest) -> Option<Languages> {
+    request.headers()
+        .get("accept-language")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|languages| languages.split(',')
+            .find_map(|language| Languages::from_code(language.split(';').next().unwrap_or("").trim())))
+}
+
+/// Client software, cut to what is stored
+pub fn get_request_user_agent(request: &HttpRequest) -> Option<String> {
+    request.headers()
+        .get("user-agent")
+        .and_then(|header| header.to_str().ok())
+        .map(|user_agent| user_agent.chars().take(512).collect::<String>())
+}
+
 pub async fn new_websocket(
     req: HttpRequest,
     stream: Payload,
     id: Identity,
-    srv: Data<Addr<WebsocketLobby>>
+    srv: Data<Addr<WebsocketLobby>>,
+    pool: Data<Pool>
 ) -> AppResult<HttpResponse> {    
     if let Ok(user_id) = id.id() {
+        let user_id = user_id.parse::<i32>().unwrap();
+        web::block(move || 
+            ban_service::check_websocket(user_id, &pool.get().unwrap())).await??;
+
         match new_connection(
             req, 
             stream, 
-            user_id.parse::<i32>().unwrap(), 
+            user_id, 
             srv) {
             Ok(resp) => {
                 return Ok(resp);
@@ -31,4 +89,43 @@ pub async fn new_websocket(
     }
 
     Err(AppError::Unauthorized)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HeaderValue;
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            actix_web::http::header::HeaderName::from_static("x-forwarded-for"), 
+            HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn forwarded_for_is_only_read_from_trusted_proxies() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let headers = forwarded_for("198.51.100.1, 203.0.113.7");
+
+        assert_eq!(get_client_ip(client, &headers, &[proxy]), client);
+        assert_eq!(get_client_ip(proxy, &headers, &[]), proxy);
+        // the spoofed first address is never reached
+        assert_eq!(get_client_ip(proxy, &headers, &[proxy]), client);
+        assert_eq!(get_client_ip(proxy, &HeaderMap::new(), &[proxy]), proxy);
+    }
+
+    #[test]
+    fn forwarded_for_is_followed_through_chained_proxies() {
+        let first_proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let second_proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let headers = forwarded_for("198.51.100.1, 203.0.113.7, 10.0.0.2");
+
+        assert_eq!(
+            get_client_ip(first_proxy, &headers, &[first_proxy, second_proxy]), 
+            "203.0.113.7".parse::<IpAddr>().unwrap());
+        assert_eq!(get_client_ip(first_proxy, &headers, &[first_proxy]), second_proxy);
+        assert_eq!(get_client_ip(first_proxy, &forwarded_for("not an ip"), &[first_proxy]), first_proxy);
+    }
+}
@@ -0,0 +1,285 @@
+use actix::Addr;
+use actix_identity::Identity;
+use actix_web::{HttpResponse, web, web::Path};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::enums::{AuditEventTypes, AuditOutcomes, BanScopes, UserRoles};
+use crate::errors::AppResult;
+use crate::services::aws::GameLiftBackend;
+use crate::services::email::outbox;
+use crate::services::websocket::WebsocketLobby;
+use crate::services::{admin as service, custom_room as custom_room_service};
+use crate::Pool;
+use crate::validation::{self, Validate, ValidationErrors};
+
+const SEARCH_MAX_LIMIT: i32 = 100;
+const BAN_REASON_MAX_LENGTH: usize = 500;
+/// Ten years, longer bans are permanent ones
+const BAN_MAX_DURATION_HOURS: i32 = 87600;
+
+#[derive(Deserialize)]
+pub struct SearchUsersQuery {
+    pub q: Option<String>,
+    pub role: Option<UserRoles>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+impl Validate for SearchUsersQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(limit) = self.limit {
+            validation::range(errors, "limit", limit, 1, SEARCH_MAX_LIMIT);
+        }
+        if let Some(offset) = self.offset {
+            validation::range(errors, "offset", offset, 0, i32::MAX);
+        }
+    }
+}
+
+/// Without a duration the ban is permanent
+#[derive(Deserialize)]
+pub struct IssueBanData {
+    pub scope: BanScopes,
+    pub reason: String,
+    pub duration_hours: Option<i32>,
+}
+
+impl Validate for IssueBanData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::length(errors, "reason", &self.reason, 1, BAN_REASON_MAX_LENGTH);
+        if let Some(duration_hours) = self.duration_hours {
+            validation::range(errors, "duration_hours", duration_hours, 1, BAN_MAX_DURATION_HOURS);
+        }
+    }
+}
+
+/// Filters of the audit log, all optional
+#[derive(Deserialize)]
+pub struct AuditEventsQuery {
+    pub user_id: Option<i32>,
+    pub event_type: Option<AuditEventTypes>,
+    pub outcome: Option<AuditOutcomes>,
+    pub ip: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+impl Validate for AuditEventsQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(limit) = self.limit {
+            validation::range(errors, "limit", limit, 1, SEARCH_MAX_LIMIT);
+        }
+        if let Some(offset) = self.offset {
+            validation::range(errors, "offset", offset, 0, i32::MAX);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FailedEmailsQuery {
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+impl Validate for FailedEmailsQuery {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(limit) = self.limit {
+            validation::range(errors, "limit", limit, 1, SEARCH_MAX_LIMIT);
+        }
+        if let Some(offset) = self.offset {
+            validation::range(errors, "offset", offset, 0, i32::MAX);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetRoleData {
+    pub role: UserRoles,
+}
+
+fn get_user_id(id: &Identity) -> i32 {
+    id.id().unwrap().parse::<i32>().unwrap()
+}
+
+pub async fn search_users(
+    query: web::Query<SearchUsersQuery>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+    let user_id = get_user_id(&id);
+
+    let users = web::block(move || {
+        let conn = &pool.get().unwrap();
+        service::check_role(user_id, UserRoles::Moderator, conn)?;
+        service::search_users(&query, conn)
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+pub async fn get_user(
+    target_id: Path<i32>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&id);
+
+    let user = web::block(move || {
+        let conn = &pool.get().unwrap();
+        service::check_role(user_id, UserRoles::Moderator, conn)?;
+        service::get_user(target_id.into_inner(), conn)
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+pub async fn set_role(
+    target_id: Path<i32>,
+    data: web::Json<SetRoleData>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&id);
+
+    let user = web::block(move || {
+        let conn = &pool.get().unwrap();
+        let admin = service::check_role(user_id, UserRoles::Admin, conn)?;
+        service::set_role(&admin, target_id.into_inner(), data.role, conn)
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+pub async fn issue_ban(
+    target_id: Path<i32>,
+    data: web::Json<IssueBanData>,
+    id: Identity,
+    ws: web::Data<Addr<WebsocketLobby>>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = get_user_id(&id);
+
+    let ban = web::block(move || {
+        let conn = &pool.get().unwrap();
+        let moderator = service::check_role(user_id, UserRoles::Moderator, conn)?;
+        service::issue_ban(&moderator, target_id.into_inner(), &data, ws.get_ref().to_owned(), conn)
+    }).await??;
+
+    Ok(HttpResponse::Created().json(ban))
+}
+
+pub async fn revoke_ban(
+    ban_id: Path<i32>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&id);
+
+    let ban = web::block(move || {
+        let conn = &pool.get().unwrap();
+        let moderator = service::check_role(user_id, UserRoles::Moderator, conn)?;
+        service::revoke_ban(&moderator, ban_id.into_inner(), conn)
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(ban))
+}
+
+pub async fn delete_custom_room(
+    custom_room_id: Path<i32>,
+    id: Identity,
+    ws: web::Data<Addr<WebsocketLobby>>,
+    gamelift: web::Data<dyn GameLiftBackend>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&id);
+    let c_pool = pool.clone();
+    web::block(move ||
+        service::check_role(user_id, UserRoles::Moderator, &c_pool.get().unwrap())).await??;
+
+    custom_room_service::force_delete(
+        custom_room_id.into_inner(),
+        user_id,
+        ws.get_ref().to_owned(),
+        gamelift.get_ref(),
+        &pool.get().unwrap()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn stop_matchmaking_ticket(
+    ticket_id: Path<Uuid>,
+    id: Identity,
+    ws: web::Data<Addr<WebsocketLobby>>,
+    gamelift: web::Data<dyn GameLiftBackend>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&id);
+    let c_pool = pool.clone();
+    web::block(move ||
+        service::check_role(user_id, UserRoles::Admin, &c_pool.get().unwrap())).await??;
+
+    custom_room_service::force_stop_matchmaking(
+        ticket_id.into_inner(),
+        user_id,
+        ws.get_ref().to_owned(),
+        gamelift.get_ref(),
+        &pool.get().unwrap()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn get_live_sessions(
+    id: Identity,
+    ws: web::Data<Addr<WebsocketLobby>>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = get_user_id(&id);
+    web::block(move ||
+        service::check_role(user_id, UserRoles::Moderator, &pool.get().unwrap())).await??;
+
+    let live_sessions = service::get_live_sessions(ws.get_ref().to_owned()).await?;
+
+    Ok(HttpResponse::Ok().json(live_sessions))
+}
+
+pub async fn get_audit_events(
+    query: web::Query<AuditEventsQuery>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+    let user_id = get_user_id(&id);
+
+    let events = web::block(move || {
+        let conn = &pool.get().unwrap();
+        service::check_role(user_id, UserRoles::Admin, conn)?;
+        service::search_audit_events(&query, conn)
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(events))
+}
+
+pub async fn get_failed_emails(
+    query: web::Query<FailedEmailsQuery>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    query.validate()?;
+    let user_id = get_user_id(&id);
+
+    let emails = web::block(move || {
+        let conn = &pool.get().unwrap();
+        service::check_role(user_id, UserRoles::Admin, conn)?;
+        outbox::get_failed(
+            query.limit.unwrap_or(service::DEFAULT_SEARCH_LIMIT) as i64,
+            query.offset.unwrap_or(0) as i64,
+            conn)
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(emails))
+}
@@ -1,14 +1,26 @@
 use actix_identity::Identity;
+use actix_session::SessionExt;
 use actix_web::{web, HttpResponse, HttpRequest, HttpMessage};
 use actix_web::http::StatusCode;
-use serde::Deserialize;
+use actix_web::cookie::{time::Duration as CookieDuration, Cookie, SameSite};
+use crate::app_conf::get_base_url;
+use serde::{Deserialize, Serialize};
 use crate::errors::{AppResult, AppError};
 use crate::models::user::{self};
 use crate::Pool;
-use crate::services::{email::EmailService, steam::SteamAuthData};
-use chrono::NaiveDateTime;
-use crate::app_conf::get_base_url;
-use crate::services::{steam, auth as auth_service};
+use crate::services::steam::SteamAuthData;
+use rand::Rng;
+use std::collections::HashMap;
+use diesel::{Connection, PgConnection};
+use diesel::result::Error as DBError;
+use crate::enums::{AuditEventTypes, BanScopes, UserTokenPurposes};
+use crate::handlers::get_request_ip;
+use crate::app_conf::bearer_auth::has_access_token;
+use crate::services::rate_limit::RateLimiter;
+use crate::services::audit::{self as audit_service, AuditContext};
+use crate::services::{steam, auth as auth_service, ban as ban_service, session as session_service, token as token_service, totp as totp_service};
+use crate::services::token::TokensDto;
+use crate::validation::{self, Validate, ValidationErrors};
 
 #[derive(Debug, Deserialize)]
 pub struct AuthData {
@@ -16,42 +28,233 @@ pub struct AuthData {
     pub password: String
 }
 
+impl Validate for AuthData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "email", &self.email);
+        validation::required(errors, "password", &self.password);
+    }
+}
+
+/// Logged in user with the tokens of clients not using the session cookie
+#[derive(Serialize)]
+pub struct LoginDto {
+    #[serde(flatten)]
+    pub user: user::User,
+    #[serde(flatten)]
+    pub tokens: TokensDto,
+}
+
+/// Returned by `login` instead of the user when the account has 2FA enabled,
+/// the challenge is sent back to `login/2fa` with a code.
+#[derive(Serialize)]
+pub struct TwoFactorChallengeDto {
+    pub two_factor_required: bool,
+    pub challenge: String,
+}
+
+enum LoginStep {
+    Done(Box<LoginDto>),
+    TwoFactor(TwoFactorChallengeDto),
+}
+
 pub async fn login(
     request: HttpRequest,
     auth_data: web::Json<AuthData>,
+    rate_limiter: web::Data<RateLimiter>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {    
-    let user = web::block(move || t_login(auth_data, pool)).await??;
+    auth_data.validate()?;
+    let request_ip = get_request_ip(&request);
+    let audit = AuditContext::from_request(&request);
+    let email = auth_data.email.clone();
+    let c_pool = pool.clone();
+    let step = web::block(move || {
+        let user = t_login(request_ip.clone(), auth_data, rate_limiter, c_pool.clone())?;
+        get_login_step(user, request_ip.as_deref(), &c_pool.get().unwrap())
+    }).await?;
+    audit_service::record_by_email(
+        &pool, &audit, &email, AuditEventTypes::Login, audit_service::failure_of(&step)).await;
 
-    if let Err(err) = Identity::login(&request.extensions(), user.id.to_string()) {
+    complete_login_step(&request, step?)
+}
+
+/// Logs in an authenticated user, or hands back a challenge when the account has 2FA enabled.
+/// Every login method goes through it.
+fn get_login_step(user: user::User, request_ip: Option<&str>, conn: &PgConnection) -> AppResult<LoginStep> {
+    if let Some(challenge) = new_login_challenge(user.id, request_ip, conn)? {
+        return Ok(LoginStep::TwoFactor(TwoFactorChallengeDto { two_factor_required: true, challenge }));
+    }
+    let tokens = token_service::issue_tokens(user.id, conn)?;
+
+    Ok(LoginStep::Done(Box::new(LoginDto { user, tokens })))
+}
+
+fn new_login_challenge(user_id: i32, request_ip: Option<&str>, conn: &PgConnection) -> AppResult<Option<String>> {
+    if !totp_service::is_enabled(user_id, conn)? {
+        return Ok(None);
+    }
+    let (challenge, _expire_timestamp) = auth_service::new_user_token(
+        user_id, 
+        UserTokenPurposes::LoginChallenge, 
+        request_ip, 
+        conn)?;
+
+    Ok(Some(challenge))
+}
+
+fn complete_login_step(request: &HttpRequest, step: LoginStep) -> AppResult<HttpResponse> {
+    match step {
+        LoginStep::Done(login) => complete_login(request, *login),
+        LoginStep::TwoFactor(challenge) => Ok(HttpResponse::Ok().json(challenge))
+    }
+}
+
+/// `challenge` can be left out after the Steam sign-in of the web pages, it is kept in the session.
+#[derive(Deserialize)]
+pub struct TwoFactorLoginData {
+    pub challenge: Option<String>,
+    pub code: String
+}
+
+impl Validate for TwoFactorLoginData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(challenge) = &self.challenge {
+            validation::required(errors, "challenge", challenge);
+        }
+        validation::required(errors, "code", &self.code);
+    }
+}
+
+/// Second step of a login with 2FA, the code is either from the authenticator app or a recovery code.
+/// A challenge is single-use, a wrong code requires to log in again.
+pub async fn login_two_factor(
+    request: HttpRequest,
+    data: web::Json<TwoFactorLoginData>,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let request_ip = get_request_ip(&request);
+    let audit = AuditContext::from_request(&request);
+    let challenge = data.challenge.clone().or_else(|| request.get_session()
+        .remove_as::<String>(STEAM_OPENID_CHALLENGE_KEY)
+        .and_then(Result::ok));
+    let c_pool = pool.clone();
+    let (user_id, result) = web::block(move || {
+        let mut user_id = None;
+        let result = t_login_two_factor(
+            challenge.as_deref().unwrap_or_default(), 
+            &data.code, 
+            request_ip.as_deref(), 
+            &rate_limiter, 
+            &mut user_id, 
+            &c_pool.get().unwrap());
+
+        (user_id, result)
+    }).await?;
+    audit_service::record(
+        &pool, &audit, user_id, AuditEventTypes::TwoFactorLogin, audit_service::failure_of(&result)).await;
+
+    let (user, tokens) = result?;
+    complete_login(&request, LoginDto { user, tokens })
+}
+
+/// Sets the id of the user once the challenge is known, so that the failed codes can be recorded.
+fn t_login_two_factor(
+    challenge: &str,
+    code: &str,
+    request_ip: Option<&str>,
+    rate_limiter: &RateLimiter,
+    user_id: &mut Option<i32>,
+    conn: &PgConnection
+) -> AppResult<(user::User, TokensDto)> {
+    rate_limiter.check("login-2fa", request_ip, None)?;
+
+    let challenge_user_id = match auth_service::use_user_token(challenge, UserTokenPurposes::LoginChallenge, conn) {
+        Ok(user_id) => user_id,
+        Err(AppError::BadRequest(_)) => return Err(AppError::BadRequest(
+            String::from("The login challenge is invalid or has expired. Log in again."))),
+        Err(err) => return Err(err)
+    };
+    *user_id = Some(challenge_user_id);
+    let user = user::get(&challenge_user_id, conn)?;
+    // the codes are also limited by account, whatever the IPs trying them
+    rate_limiter.check("login-2fa", None, Some(&user.email))?;
+
+    if !totp_service::verify_code(user.id, code, conn)? {
+        rate_limiter.record_failure(&user.email)?;
+        return Err(AppError::BadRequest(String::from("Invalid code.")));
+    }
+    rate_limiter.record_success(&user.email)?;
+    ban_service::check(user.id, BanScopes::Login, conn)?;
+    let tokens = token_service::issue_tokens(user.id, conn)?;
+
+    Ok((user, tokens))
+}
+
+/// Attaches the user to the session and answers with the bearer tokens
+fn complete_login(request: &HttpRequest, login: LoginDto) -> AppResult<HttpResponse> {
+    if let Err(err) = Identity::login(&request.extensions(), login.user.id.to_string()) {
         return Err(AppError::InternalServerError(err.to_string()))
     }
+    session_service::insert_metadata(request)?;
 
-    Ok(HttpResponse::Ok().json(user))
+    Ok(HttpResponse::Ok().json(login))
 }
 
 fn t_login(
+    request_ip: Option<String>,
     auth_data: web::Json<AuthData>,
+    rate_limiter: web::Data<RateLimiter>,
     pool: web::Data<Pool>
 ) -> AppResult<user::User> {
     let datas = auth_data.into_inner();
     let email = datas.email.clone();
-    match user::get_by_email(&email, &pool.get().unwrap()) {
-        Ok(user) => {
-            if !user.can_login() {
-                return Err(AppError::Forbidden);
-            }
-            
-            if user.is_password_ok(&datas.password)? {
-                return Ok(user);
-            }
-        }
+    let incorrect_error = Err(AppError::BadRequest(String::from("Incorrect email or password.")));
+
+    rate_limiter.check("login", request_ip.as_deref(), Some(&email))?;
+
+    let conn = &pool.get().unwrap();
+    let user = match user::get_by_email(&email, conn) {
+        Ok(user) => user,
         Err(_err) => {
-            return Err(AppError::BadRequest(String::from("Unknown email.")));
+            // hashes anyway so that unknown emails answer as slowly as wrong passwords
+            let _ = auth_service::hash_password(&datas.password);
+            rate_limiter.record_failure(&email)?;
+            return incorrect_error;
         }
+    };
+
+    if !matches!(auth_service::verify_password(&user.hash, &user.password_pepper_id, &datas.password), Ok(true)) {
+        rate_limiter.record_failure(&email)?;
+        return incorrect_error;
     }
+    rate_limiter.record_success(&email)?;
 
-    Err(AppError::BadRequest(String::from("Incorrect password.")))
+    if !user.can_login() {
+        return Err(AppError::Forbidden);
+    }
+    ban_service::check(user.id, BanScopes::Login, conn)?;
+    if auth_service::needs_rehash(&user.hash, &user.password_pepper_id) {
+        rehash_password(&user, &datas.password, conn);
+    }
+
+    Ok(user)
+}
+
+/// Upgrades the stored hash to the current parameters, the login goes on if it fails.
+fn rehash_password(user: &user::User, password: &str, conn: &PgConnection) {
+    let result = auth_service::hash_password(password)
+        .and_then(|new_hash| user::update_hash(
+            &user.id, 
+            &new_hash, 
+            auth_service::current_pepper_id(), 
+            conn)
+            .map_err(|err| AppError::InternalServerError(err.to_string())));
+
+    if let Err(err) = result {
+        println!("Password rehash of user {} failed: {:?}", user.id, err);
+    }
 }
 
 pub async fn login_steam(
@@ -59,25 +262,190 @@ pub async fn login_steam(
     auth_data: web::Json<SteamAuthData>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
-    let steam_id = steam::authenticate_user_ticket(&auth_data).await?;
+    let audit = AuditContext::from_request(&request);
+    let mut user_id = None;
+    let result = t_login_steam(&request, auth_data, pool.clone(), &mut user_id).await;
+    let failure = match &result {
+        Ok(response) if !response.status().is_success() => Some(String::from("forbidden")),
+        _ => audit_service::failure_of(&result)
+    };
+    audit_service::record(&pool, &audit, user_id, AuditEventTypes::SteamLogin, failure).await;
+
+    result
+}
 
+/// Sets the id of the user once the Steam account is known, so that the failures can be recorded.
+async fn t_login_steam(
+    request: &HttpRequest,
+    auth_data: web::Json<SteamAuthData>,
+    pool: web::Data<Pool>,
+    user_id: &mut Option<i32>
+) -> AppResult<HttpResponse> {
+    let ticket = steam::authenticate_user_ticket(&auth_data).await?;
+    let steam_id = ticket.steam_id;
+
+    let c_pool = pool.clone();
     let user = web::block(move || 
-        user::get_by_steam_id(&steam_id.to_string(), &pool.get().unwrap())).await??;
+        user::get_by_steam_id(&steam_id.to_string(), &c_pool.get().unwrap())).await??;
+    *user_id = Some(user.id);
+
+    let c_pool = pool.clone();
+    let c_user_id = user.id;
+    web::block(move || {
+        let conn = &c_pool.get().unwrap();
+        ban_service::record_steam_flags(c_user_id, &ticket, conn)?;
+        ban_service::check_steam(&ticket)?;
+        ban_service::check(c_user_id, BanScopes::Login, conn)
+    }).await??;
     
     steam::check_app_ownership(&auth_data.app_id, &steam_id).await?;
     if user.can_login() {
-        if let Err(err) = Identity::login(&request.extensions(), user.id.to_string()) {
-            return Err(AppError::InternalServerError(err.to_string()))
-        }
-        return Ok(HttpResponse::Ok().json(user));
+        let request_ip = get_request_ip(request);
+        let step = web::block(move || 
+            get_login_step(user, request_ip.as_deref(), &pool.get().unwrap())).await??;
+
+        return complete_login_step(request, step);
     }
     
     Ok(HttpResponse::Forbidden().json(user))
 }
 
+/// Cookie of the state of a Steam sign-in, kept out of the session so that no session is stored for
+/// a sign-in which is not completed
+const STEAM_OPENID_STATE_COOKIE: &str = "steam_openid_state";
+const STEAM_OPENID_STATE_MAX_AGE_MINUTES: i64 = 10;
+/// Login challenge of an account with 2FA signed in through Steam, for `login/2fa`
+const STEAM_OPENID_CHALLENGE_KEY: &str = "steam_openid_challenge";
+
+/// The state cookie is only sent back to the callback
+fn steam_openid_state_cookie(state: &str, max_age: CookieDuration) -> Cookie<'static> {
+    Cookie::build(STEAM_OPENID_STATE_COOKIE, state.to_string())
+        .path(steam::openid::CALLBACK_PATH)
+        .http_only(true)
+        .secure(get_base_url().starts_with("https://"))
+        .same_site(SameSite::Lax)
+        .max_age(max_age)
+        .finish()
+}
+
+fn login_page_redirect(result: &str) -> HttpResponse {
+    HttpResponse::TemporaryRedirect()
+        .status(StatusCode::SEE_OTHER)
+        .insert_header((
+            "Location", 
+            format!("/static/login.html?steam_login={}", result)))
+        .cookie(steam_openid_state_cookie("", CookieDuration::ZERO))
+        .finish()
+}
+
+/// Starts the Steam sign-in of the web pages
+pub async fn steam_openid_login() -> AppResult<HttpResponse> {
+    let state = base64::encode_config(rand::thread_rng().gen::<[u8; 32]>(), base64::URL_SAFE_NO_PAD);
+
+    Ok(HttpResponse::TemporaryRedirect()
+        .status(StatusCode::SEE_OTHER)
+        .insert_header((
+            "Location", 
+            steam::openid::get_login_url(&state)))
+        .cookie(steam_openid_state_cookie(&state, CookieDuration::minutes(STEAM_OPENID_STATE_MAX_AGE_MINUTES)))
+        .finish())
+}
+
+/// Steam sends the browser back here, the user is logged in and redirected to the login page with the result.
+pub async fn steam_openid_callback(
+    request: HttpRequest,
+    params: web::Query<HashMap<String, String>>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let audit = AuditContext::from_request(&request);
+    let mut user_id = None;
+    let result = t_steam_openid_callback(&request, params, pool.clone(), &mut user_id).await?;
+    let failure = match result {
+        "ok" | "two_factor" => None,
+        _ => Some(format!("steam_openid_{}", result))
+    };
+    audit_service::record(&pool, &audit, user_id, AuditEventTypes::SteamLogin, failure).await;
+
+    Ok(login_page_redirect(result))
+}
+
+/// Returns the result shown by the login page.
+/// Sets the id of the user once the Steam account is known, so that the failures can be recorded.
+async fn t_steam_openid_callback(
+    request: &HttpRequest,
+    params: web::Query<HashMap<String, String>>,
+    pool: web::Data<Pool>,
+    user_id: &mut Option<i32>
+) -> AppResult<&'static str> {
+    let expected_state = request.cookie(STEAM_OPENID_STATE_COOKIE).map(|cookie| cookie.value().to_string());
+    let state_is_valid = matches!((&expected_state, params.get("state")), 
+        (Some(expected_state), Some(state)) if expected_state.len() == state.len() 
+            && openssl::memcmp::eq(expected_state.as_bytes(), state.as_bytes()));
+    if !state_is_valid {
+        return Ok("failed");
+    }
+
+    let steam_id = match steam::openid::verify(&params, expected_state.as_deref().unwrap_or_default()).await {
+        Ok(steam_id) => steam_id,
+        Err(_) => return Ok("failed")
+    };
+    let c_pool = pool.clone();
+    let user = match web::block(move || 
+        user::get_by_steam_id(&steam_id.to_string(), &c_pool.get().unwrap())).await? {
+        Ok(user) => user,
+        Err(DBError::NotFound) => return Ok("unknown_account"),
+        Err(err) => return Err(AppError::from(err))
+    };
+    *user_id = Some(user.id);
+
+    let mut steam_bans = match steam::get_player_bans(steam_id).await {
+        Ok(steam_bans) => steam_bans,
+        Err(_) => return Ok("failed")
+    };
+    let c_pool = pool.clone();
+    let c_user_id = user.id;
+    match web::block(move || {
+        let conn = &c_pool.get().unwrap();
+        ban_service::record_steam_player_flags(c_user_id, &mut steam_bans, conn)?;
+        ban_service::check_steam(&steam_bans)?;
+        ban_service::check(c_user_id, BanScopes::Login, conn)
+    }).await? {
+        Ok(()) => (),
+        Err(AppError::Banned(_)) => return Ok("forbidden"),
+        Err(err) => return Err(err)
+    }
+    if !user.can_login() {
+        return Ok("forbidden");
+    }
+
+    let request_ip = get_request_ip(request);
+    let c_user_id = user.id;
+    let challenge = web::block(move || 
+        new_login_challenge(c_user_id, request_ip.as_deref(), &pool.get().unwrap())).await??;
+    if let Some(challenge) = challenge {
+        request.get_session().insert(STEAM_OPENID_CHALLENGE_KEY, challenge)
+            .map_err(|err| AppError::InternalServerError(err.to_string()))?;
+        return Ok("two_factor");
+    }
+
+    if let Err(err) = Identity::login(&request.extensions(), user.id.to_string()) {
+        return Err(AppError::InternalServerError(err.to_string()))
+    }
+    session_service::insert_metadata(request)?;
+
+    Ok("ok")
+}
+
+/// Logging out with an access token ends every token of the account, the game client logs in again.
 pub async fn logout(
+    request: HttpRequest,
     id: Identity,
+    pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {    
+    if has_access_token(&request) {
+        let user_id = id.id().unwrap().parse::<i32>().unwrap();
+        web::block(move || token_service::revoke_all(user_id, &pool.get().unwrap())).await??;
+    }
     id.logout();
     Ok(HttpResponse::Ok().finish())
 }
@@ -87,53 +455,57 @@ pub struct AskPassData {
     pub email: String
 }
 
+impl Validate for AskPassData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::email(errors, "email", &self.email);
+    }
+}
+
 pub async fn ask_password_reset(
+    request: HttpRequest,
     data: web::Json<AskPassData>,
+    rate_limiter: web::Data<RateLimiter>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
-    match t_ask_password_reset(data, pool).await {
+    data.validate()?;
+    let audit = AuditContext::from_request(&request);
+    let email = data.email.clone();
+    let result = t_ask_password_reset(get_request_ip(&request), data, rate_limiter, pool.clone()).await;
+    audit_service::record_by_email(
+        &pool, &audit, &email, AuditEventTypes::PasswordResetRequest, audit_service::failure_of(&result)).await;
+
+    match result {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(err) => Err(err)
     }    
 }
 
 async fn t_ask_password_reset(
+    request_ip: Option<String>,
     data: web::Json<AskPassData>,
+    rate_limiter: web::Data<RateLimiter>,
     pool: web::Data<Pool>
 ) -> AppResult<()> {
-    let hash = auth_service::new_reset_password_hash()?;
-    let result  = user::update_reset_password_hash(
-        &data.email, 
-        &hash,
-        &pool.get().unwrap()
-    );
+    let email = data.email.clone();
+    web::block(move || {
+        rate_limiter.check("password-reset", request_ip.as_deref(), Some(&email))?;
 
-    match result {
-        Ok(expire_time) => {
-            let url = format!("{}/static/reset_password.html?id={}", get_base_url(), hash);
-            let expire_time = NaiveDateTime::from_timestamp_opt(expire_time, 0).unwrap()
-                .format("%c");
-            let link = format!("<h1>Hello !</h1><br/><p>Here's your link: {}.</p><p>Your link we'll expire at {} (UTC time)</p>", url, expire_time);
-    
-            let email_service = EmailService::new(
-                &data.email,
-                String::from("Rigidity password reset"),
-                link
-            );
-            
-            match email_service.send().await {
-                Ok(_response) => {
-                    Ok(())
-                }
-                Err(err) => {
-                    Err(AppError::ServiceUnavailable(format!("Email service unavailable. Message: {}", err)))
-                }
-            }
-        }
-        Err(err) => {
-            return Err(AppError::InternalServerError(format!("Database error: {}", err.to_string())));
+        let conn = &pool.get().unwrap();
+        match user::get_by_email(&email, conn) {
+            Ok(user) => conn.transaction(|| {
+                let (token, expire_timestamp) = auth_service::new_user_token(
+                    user.id, 
+                    UserTokenPurposes::PasswordReset, 
+                    request_ip.as_deref(), 
+                    conn)?;
+
+                auth_service::queue_password_reset_email(&user, &token, expire_timestamp, conn)
+            }),
+            // no email is sent for an unknown address
+            Err(DBError::NotFound) => Ok(()),
+            Err(err) => Err(AppError::from(err))
         }
-    } 
+    }).await?
 }
 
 #[derive(Debug, Deserialize)]
@@ -142,11 +514,29 @@ pub struct ResetPassData {
     pub new_password: String
 }
 
+impl Validate for ResetPassData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "hash", &self.hash);
+        validation::password(errors, "new_password", &self.new_password);
+    }
+}
+
 pub async fn reset_password(
+    request: HttpRequest,
     data: web::Json<ResetPassData>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
-    web::block(move || t_reset_password(data, pool)).await??;
+    data.validate()?;
+    let audit = AuditContext::from_request(&request);
+    let c_pool = pool.clone();
+    let result = web::block(move || t_reset_password(data, c_pool)).await?;
+    audit_service::record(
+        &pool, 
+        &audit, 
+        result.as_ref().ok().copied(), 
+        AuditEventTypes::PasswordReset, 
+        audit_service::failure_of(&result)).await;
+    result?;
 
     Ok(HttpResponse::TemporaryRedirect()
         .status(StatusCode::SEE_OTHER)
@@ -156,33 +546,50 @@ pub async fn reset_password(
         .finish())
 }
 
+/// Returns the id of the user whose password was reset.
 fn t_reset_password(
     data: web::Json<ResetPassData>,
     pool: web::Data<Pool>
-) -> AppResult<()> {
+) -> AppResult<i32> {
     let conn = &pool.get().unwrap();
 
-    auth_service::check_reset_password_hash(&data.hash, conn)?;
-    match auth_service::hash_password(&data.new_password) {
-        Ok(new_hash) => user::update_password(&data, &new_hash, conn)?,
-        Err(err) => return Err(AppError::InternalServerError(err.to_string()))
-    }
+    conn.transaction(|| {
+        let user_id = auth_service::use_user_token(&data.hash, UserTokenPurposes::PasswordReset, conn)?;
+        match auth_service::hash_password(&data.new_password) {
+            Ok(new_hash) => user::update_hash(
+                &user_id, 
+                &new_hash, 
+                auth_service::current_pepper_id(), 
+                conn)?,
+            Err(err) => return Err(AppError::InternalServerError(err.to_string()))
+        }
+        session_service::delete_all(user_id, conn)?;
+        token_service::revoke_all(user_id, conn)?;
 
-    Ok(())
+        Ok(user_id)
+    })
 }
 
-pub async fn refresh_cookie(
-    request: HttpRequest,
-    id: Identity,
-) -> AppResult<HttpResponse> {
-    let user_id = id.id().unwrap();
-    id.logout();
+#[derive(Deserialize)]
+pub struct RefreshTokenData {
+    pub refresh_token: String
+}
 
-    if let Err(err) = Identity::login(&request.extensions(), user_id) {
-        return Err(AppError::InternalServerError(err.to_string()))
+impl Validate for RefreshTokenData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "refresh_token", &self.refresh_token);
     }
+}
 
-    Ok(HttpResponse::Ok().finish())
+pub async fn refresh_token(
+    data: web::Json<RefreshTokenData>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let tokens = web::block(move || 
+        token_service::refresh_tokens(&data.refresh_token, &pool.get().unwrap())).await??;
+
+    Ok(HttpResponse::Ok().json(tokens))
 }
 
 #[derive(Deserialize)]
@@ -190,12 +597,29 @@ pub struct EmailConfirmationData {
     pub hash: String
 }
 
+impl Validate for EmailConfirmationData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "hash", &self.hash);
+    }
+}
+
 pub async fn email_confirmation(
+    request: HttpRequest,
     data: web::Json<EmailConfirmationData>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
-    web::block(move ||
-        auth_service::email_confirmation(&data.hash, &pool.get().unwrap())).await??; 
+    data.validate()?;
+    let audit = AuditContext::from_request(&request);
+    let c_pool = pool.clone();
+    let result = web::block(move ||
+        auth_service::email_confirmation(&data.hash, &c_pool.get().unwrap())).await?; 
+    audit_service::record(
+        &pool, 
+        &audit, 
+        result.as_ref().ok().copied(), 
+        AuditEventTypes::EmailConfirmation, 
+        audit_service::failure_of(&result)).await;
+    result?;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -206,14 +630,22 @@ pub struct UpdateEmailConfirmationData {
     pub auth: steam::SteamAuthData
 }
 
+impl Validate for UpdateEmailConfirmationData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::email(errors, "email", &self.email);
+    }
+}
+
 pub async fn update_email_confirmation(
+    request: HttpRequest,
     data: web::Json<UpdateEmailConfirmationData>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
+    data.validate()?;
     let steam_id = auth_service::steam_authenticate_and_ownership_check(&data.auth).await?;
 
     auth_service::update_email_confirmation(
-        data.email.clone(), steam_id, pool).await?;
+        data.email.clone(), steam_id, get_request_ip(&request), pool).await?;
     
     Ok(HttpResponse::Ok().finish())
 }
\ No newline at end of file
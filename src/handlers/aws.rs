@@ -3,17 +3,16 @@ use awc::Client;
 use futures_util::StreamExt;
 use crate::errors::{AppError, AppResult};
 use serde_json::{from_slice};
-use serde::Deserialize;
-use crate::services::aws::*;
-use crate::services::custom_room;
 use actix::{Addr};
 use crate::Pool;
-use crate::services::{as_json_string, websocket::WebsocketLobby};
+use crate::services::{matchmaking, websocket::WebsocketLobby};
+use crate::services::sns::{is_sns_url, SnsMessage, SnsVerifier};
 
 pub async fn sns(
     req: HttpRequest,
     mut stream: Payload,
     ws: web::Data<Addr<WebsocketLobby>>,
+    verifier: web::Data<SnsVerifier>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
     let error = Err(AppError::BadRequest(String::from("x-amz-sns-message-type header is unknown or missing.")));
@@ -29,12 +28,21 @@ pub async fn sns(
                     Err(_) => return Err(AppError::BadRequest(String::from("Corrupted body.")))
                 }
             }
+            let message = match from_slice::<SnsMessage>(&body) {
+                Ok(message) => message,
+                Err(_) => return Err(AppError::BadRequest(String::from("Json body has wrong format.")))
+            };
+            if message.m_type != message_type {
+                return error
+            }
+            verifier.verify(&message).await?;
+
             match message_type {
                 "SubscriptionConfirmation" => {
-                    return handle_sns_subscription(body).await
+                    return handle_sns_subscription(message).await
                 },
                 "Notification" => {
-                    return handle_sns_notification(body,ws,pool).await
+                    return handle_sns_notification(message, ws, pool).await
                 },
                 _ => {
                     return error
@@ -47,18 +55,15 @@ pub async fn sns(
 }
 
 async fn handle_sns_subscription(
-    body: web::BytesMut
+    message: SnsMessage
 ) -> AppResult<HttpResponse> {
-    #[derive(Deserialize)]
-    struct SnsData {
-        #[serde(rename = "SubscribeURL")]
-        pub subscribe_url: String
-    }
-    if let Ok(obj) = from_slice::<SnsData>(&body) {
-        let obj: SnsData = obj;
+    if let Some(subscribe_url) = message.subscribe_url {
+        if !is_sns_url(&subscribe_url) {
+            return Err(AppError::BadRequest(format!("Untrusted SubscribeURL: {}", subscribe_url)))
+        }
 
         let client = Client::default();
-        match client.get(obj.subscribe_url).send().await {
+        match client.get(subscribe_url).send().await {
             Ok(_) => return Ok(HttpResponse::Ok().finish()),
             Err(err) => {
                 return Err(AppError::BadRequest(err.to_string()))
@@ -66,56 +71,18 @@ async fn handle_sns_subscription(
         }
     }
 
-    Err(AppError::BadRequest(String::from("Json body has wrong format.")))    
+    Err(AppError::BadRequest(String::from("SubscribeURL is missing.")))    
 }
 
 async fn handle_sns_notification(
-    body: web::BytesMut,
+    message: SnsMessage,
     ws: web::Data<Addr<WebsocketLobby>>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
-    #[derive(Deserialize)]
-    struct SnsData {
-        #[serde(rename = "Message", with = "as_json_string")]
-        pub message: FlexMatchData<FlexMatchDetail>,
-    }
+    matchmaking::handle_flexmatch_event(
+        &message.message, 
+        ws.get_ref().to_owned(), 
+        &pool.get().unwrap()).await?;
 
-    if let Ok(obj) = from_slice::<SnsData>(&body) {
-        match obj.message.detail.e_type {
-            FlexMatchEvents::MatchmakingSucceeded => {
-                #[derive(Deserialize)]
-                struct SnsDataSucceeded {
-                    #[serde(rename = "Message", with = "as_json_string")]
-                    pub message: FlexMatchData<FlexMatchSucceededDetail>,
-                }
-                let data = from_slice::<SnsDataSucceeded>(&body).unwrap();
-                if let Err(err) = custom_room::matchmaking_succeeded(
-                    data.message,
-                    ws.get_ref().to_owned(),
-                    &pool.get().unwrap()
-                ) {
-                    return Err(err)
-                }
-            },
-            FlexMatchEvents::MatchmakingTimedOut |
-            FlexMatchEvents::MatchmakingCancelled |
-            FlexMatchEvents::MatchmakingFailed => {
-                let ticket_id = &obj.message.detail.tickets[0].ticket_id;
-                if let Err(err) = custom_room::matchmaking_failed(
-                    obj.message.detail.e_type, 
-                    ticket_id,
-                    ws.get_ref().to_owned(), 
-                    &pool.get().unwrap()
-                ).await {
-                    return Err(err)
-                }
-            }
-           _ => {
-                
-           }
-        }
-        return Ok(HttpResponse::Ok().finish())
-    }
-
-    Err(AppError::BadRequest(String::from("Json body has wrong format.")))   
-}
\ No newline at end of file
+    Ok(HttpResponse::Ok().finish())
+}
@@ -6,10 +6,15 @@ use crate::Pool;
 use actix_identity::Identity;
 use crate::services::{custom_room as service, websocket::WebsocketLobby};
 use actix::{Addr};
-use rusoto_gamelift::GameLiftClient;
+use crate::services::aws::GameLiftBackend;
+use crate::validation::{self, Validate, ValidationErrors};
 
 pub mod dtos;
 
+const LABEL_MAX_LENGTH: usize = 100;
+const MAX_TEAMS: i32 = 8;
+const MAX_PLAYERS_PER_TEAM: i32 = 8;
+
 pub async fn get_all(
     _: Identity,
     pool: web::Data<Pool>
@@ -29,12 +34,21 @@ pub struct CustomRoomData {
     pub map: Option<Maps>
 }
 
+impl Validate for CustomRoomData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::length(errors, "label", &self.label, 1, LABEL_MAX_LENGTH);
+        validation::range(errors, "nb_teams", self.nb_teams, 1, MAX_TEAMS);
+        validation::range(errors, "max_players_per_team", self.max_players_per_team, 1, MAX_PLAYERS_PER_TEAM);
+    }
+}
+
 pub async fn create(
     create_data: web::Json<CustomRoomData>,
     id: Identity,
     ws: web::Data<Addr<WebsocketLobby>>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
+    create_data.validate()?;
     let user_id = id.id().unwrap();
     let custom_room = web::block(move || 
         service::create(
@@ -52,6 +66,7 @@ pub async fn update(
     ws: web::Data<Addr<WebsocketLobby>>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
+    update_data.validate()?;
     let user_id = id.id().unwrap();
     let custom_room = web::block(move || 
         service::update(
@@ -118,6 +133,13 @@ pub struct SwitchSlotData {
     pub team_position: i32,
 }
 
+impl Validate for SwitchSlotData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::range(errors, "team", self.team, 0, MAX_TEAMS - 1);
+        validation::range(errors, "team_position", self.team_position, 0, MAX_PLAYERS_PER_TEAM - 1);
+    }
+}
+
 pub async fn switch_slot(
     custom_room_id: Path<i32>,
     id: Identity,
@@ -125,6 +147,7 @@ pub async fn switch_slot(
     ws: web::Data<Addr<WebsocketLobby>>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
+    position.validate()?;
     let user_id = id.id().unwrap();
     let custom_room = web::block(move || 
         service::switch_slot(
@@ -185,7 +208,7 @@ pub async fn start_matchmaking(
     custom_room_id: Path<i32>,
     id: Identity,
     ws: web::Data<Addr<WebsocketLobby>>,
-    gamelift: web::Data<GameLiftClient>,
+    gamelift: web::Data<dyn GameLiftBackend>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
     let user_id = id.id().unwrap();
@@ -208,7 +231,7 @@ pub async fn stop_matchmaking(
     custom_room_id: Path<i32>,
     id: Identity,
     ws: web::Data<Addr<WebsocketLobby>>,
-    gamelift: web::Data<GameLiftClient>,
+    gamelift: web::Data<dyn GameLiftBackend>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {
     let user_id = id.id().unwrap();
@@ -225,4 +248,20 @@ pub async fn stop_matchmaking(
             Err(err)
         }
     }
-}
\ No newline at end of file
+}
+
+pub async fn matchmaking_status(
+    custom_room_id: Path<i32>,
+    id: Identity,
+    gamelift: web::Data<dyn GameLiftBackend>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap();
+    let status = service::get_matchmaking_status(
+        custom_room_id.into_inner(),
+        user_id.parse::<i32>().unwrap(),
+        gamelift.get_ref(),
+        &pool.get().unwrap()).await?;
+
+    Ok(HttpResponse::Ok().json(status))
+}
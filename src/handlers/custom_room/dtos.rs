@@ -1,6 +1,6 @@
 use serde::{Serialize};
 use crate::models::custom_room::{CustomRoomSlot, CustomRoom};
-use crate::enums::{GameModes, Maps};
+use crate::enums::{GameModes, Maps, MatchmakingStatuses};
 use crate::models::user;
 use diesel::{PgConnection};
 use crate::models::ORMResult;
@@ -101,4 +101,15 @@ impl CustomRoomSlotDto {
             archetype: slot.current_archetype.to_u32(),
         })
     }
-}
\ No newline at end of file
+}
+
+#[derive(Serialize)]
+pub struct MatchmakingStatusDto {
+    pub ticket_id: Uuid,
+    pub status: MatchmakingStatuses,
+    pub status_reason: Option<String>,
+    pub status_message: Option<String>,
+    pub elapsed_seconds: i64,
+    /// Average search duration of the last matches found with the same configuration
+    pub estimated_wait_seconds: Option<i64>,
+}
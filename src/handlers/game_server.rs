@@ -0,0 +1,83 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use crate::enums::{Archetypes, BanScopes, Maps};
+use crate::errors::{AppError, AppResult};
+use crate::services::{matchmaking as service, ban as ban_service};
+use crate::services::aws::GameLiftBackend;
+use crate::Pool;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackfillPlayerData {
+    pub user_id: i32,
+    pub team: i32,
+    pub team_position: i32,
+    pub archetype: Archetypes
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackfillData {
+    pub game_session_arn: String,
+    pub map: Maps,
+    /// Players currently in the game session
+    pub players: Vec<BackfillPlayerData>
+}
+
+#[derive(Serialize)]
+struct BackfillDto {
+    ticket_id: Uuid
+}
+
+/// Called by a game server with an open slot to find a player for it.
+pub async fn backfill(
+    req: HttpRequest,
+    data: web::Json<BackfillData>,
+    gamelift: web::Data<dyn GameLiftBackend>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    check_game_server_key(&req)?;
+
+    let ticket_id = service::start_backfill(
+        data.into_inner(), 
+        gamelift.get_ref(), 
+        &pool.get().unwrap()).await?;
+
+    Ok(HttpResponse::Ok().json(BackfillDto { ticket_id }))
+}
+
+#[derive(Serialize)]
+struct PlayerBansDto {
+    user_id: i32,
+    scopes: Vec<BanScopes>
+}
+
+/// Called by a game server when a player joins, it mutes the players banned from chat.
+pub async fn get_player_bans(
+    req: HttpRequest,
+    user_id: web::Path<i32>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    check_game_server_key(&req)?;
+
+    let user_id = user_id.into_inner();
+    let scopes = web::block(move || 
+        ban_service::get_active_scopes(user_id, &pool.get().unwrap())).await??;
+
+    Ok(HttpResponse::Ok().json(PlayerBansDto { user_id, scopes }))
+}
+
+fn check_game_server_key(req: &HttpRequest) -> AppResult<()> {
+    let expected = std::env::var("GAME_SERVER_KEY").unwrap_or_default();
+    let key = req.headers()
+        .get("x-game-server-key")
+        .and_then(|header| header.to_str().ok())
+        .unwrap_or_default();
+
+    if expected.is_empty() 
+        || key.len() != expected.len() 
+        || !openssl::memcmp::eq(key.as_bytes(), expected.as_bytes()) {
+        return Err(AppError::Unauthorized)
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use actix_identity::Identity;
+use actix_web::{HttpRequest, HttpResponse, web, web::Path};
+use serde::Serialize;
+use uuid::Uuid;
+use crate::errors::AppResult;
+use crate::models::session::Session;
+use crate::services::{session as service};
+use crate::Pool;
+
+#[derive(Serialize)]
+pub struct SessionDto {
+    #[serde(flatten)]
+    pub session: Session,
+    pub current: bool,
+}
+
+pub async fn get_all(
+    request: HttpRequest,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let current_session_id = service::get_current_session_id(&request);
+
+    let sessions = web::block(move || 
+        service::get_all(user_id, &pool.get().unwrap())).await??;
+    let dtos: Vec<SessionDto> = sessions.into_iter()
+        .map(|session| SessionDto {
+            current: Some(session.session_id) == current_session_id,
+            session,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(dtos))
+}
+
+pub async fn delete(
+    session_id: Path<Uuid>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+
+    web::block(move || 
+        service::delete(user_id, &session_id, &pool.get().unwrap())).await??;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,75 @@
+use actix_identity::Identity;
+use actix_web::{HttpRequest, HttpResponse, web};
+use serde::Deserialize;
+use crate::enums::AuditEventTypes;
+use crate::errors::AppResult;
+use crate::models::user;
+use crate::services::audit::{self as audit_service, AuditContext};
+use crate::services::totp::{self as service, RecoveryCodesDto};
+use crate::Pool;
+use crate::validation::{self, Validate, ValidationErrors};
+
+#[derive(Deserialize)]
+pub struct TwoFactorCodeData {
+    pub code: String
+}
+
+impl Validate for TwoFactorCodeData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "code", &self.code);
+    }
+}
+
+pub async fn setup(
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+
+    let setup = web::block(move || {
+        let conn = &pool.get().unwrap();
+        let user = user::get(&user_id, conn)?;
+        service::setup(&user, conn)
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(setup))
+}
+
+pub async fn enable(
+    request: HttpRequest,
+    data: web::Json<TwoFactorCodeData>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let audit = AuditContext::from_request(&request);
+
+    let c_pool = pool.clone();
+    let result = web::block(move || 
+        service::enable(user_id, &data.code, &c_pool.get().unwrap())).await?;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::TwoFactorEnable, audit_service::failure_of(&result)).await;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesDto { recovery_codes: result? }))
+}
+
+pub async fn disable(
+    request: HttpRequest,
+    data: web::Json<TwoFactorCodeData>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let audit = AuditContext::from_request(&request);
+
+    let c_pool = pool.clone();
+    let result = web::block(move || 
+        service::disable(user_id, &data.code, &c_pool.get().unwrap())).await?;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::TwoFactorDisable, audit_service::failure_of(&result)).await;
+    result?;
+
+    Ok(HttpResponse::Ok().finish())
+}
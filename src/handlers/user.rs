@@ -1,11 +1,20 @@
-use serde::{Deserialize};
+use serde::{Deserialize, Serialize};
+use actix_identity::Identity;
 use crate::chrono::{DateTime, Utc};
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, web};
+use diesel::Connection;
+use crate::enums::{AuditEventTypes, Languages, UserTokenPurposes};
+use crate::handlers::{get_request_ip, get_request_language};
+use crate::services::rate_limit::RateLimiter;
+use crate::services::websocket::WebsocketLobby;
+use actix::Addr;
 use crate::Pool;
 use crate::{errors::{AppResult, AppError}};
-use crate::models::user::{create as create_user};
+use crate::models::user::{create as create_user, User};
 use crate::models::forms::user::UserForm;
-use crate::services::{steam, auth as auth_service};
+use crate::services::audit::{self as audit_service, AuditContext};
+use crate::services::{steam, auth as auth_service, ban as ban_service, session as session_service, user as user_service};
+use crate::validation::{self, Validate, ValidationErrors};
 
 #[derive(Deserialize)]
 pub struct CreateUserData {
@@ -15,27 +24,406 @@ pub struct CreateUserData {
     pub last_name: String,
     pub birth_date: DateTime<Utc>,
     pub auth: steam::SteamAuthData,
+    /// Taken from `Accept-Language` when not given
+    pub language: Option<Languages>,
+}
+
+impl Validate for CreateUserData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::email(errors, "email", &self.email);
+        validation::nickname(errors, "nickname", &self.nickname);
+        validation::name(errors, "first_name", &self.first_name);
+        validation::name(errors, "last_name", &self.last_name);
+        validation::birth_date(errors, "birth_date", &self.birth_date);
+    }
 }
 
 pub async fn create(
+    request: HttpRequest,
     create_data: web::Json<CreateUserData>,
+    rate_limiter: web::Data<RateLimiter>,
     pool: web::Data<Pool>
 ) -> AppResult<HttpResponse> {    
+    create_data.validate()?;
+    let audit = AuditContext::from_request(&request);
+    let language = create_data.language
+        .or_else(|| get_request_language(&request))
+        .unwrap_or_default();
+    let result = t_create(get_request_ip(&request), language, create_data, rate_limiter, pool.clone()).await;
+    audit_service::record(
+        &pool, 
+        &audit, 
+        result.as_ref().ok().map(|user| user.id), 
+        AuditEventTypes::AccountCreation, 
+        audit_service::failure_of(&result)).await;
+
+    Ok(HttpResponse::Ok().json(result?))
+}
+
+async fn t_create(
+    request_ip: Option<String>,
+    language: Languages,
+    create_data: web::Json<CreateUserData>,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<User> {
+    let (c_request_ip, email) = (request_ip.clone(), create_data.email.clone());
+    web::block(move || 
+        rate_limiter.check("user-create", c_request_ip.as_deref(), Some(&email))).await??;
+
     let steam_id = auth_service::steam_authenticate_and_ownership_check(&create_data.auth).await?;
     let data = create_data.into_inner();
-    let email_confirmation_hash = auth_service::new_reset_password_hash()?;
 
-    let (user, expire_timestamp) = web::block(move || 
-        create_user(
-            UserForm::new_from_data(&data, &steam_id.to_string()), 
-            &email_confirmation_hash,&pool.get().unwrap())).await??; 
+    web::block(move || {
+        let conn = &pool.get().unwrap();
+        conn.transaction(|| {
+            let user = create_user(
+                UserForm::new_from_data(&data, &steam_id.to_string(), language), 
+                conn)?;
+            let (token, expire_timestamp) = auth_service::new_user_token(
+                user.id, 
+                UserTokenPurposes::EmailConfirmation, 
+                request_ip.as_deref(), 
+                conn)?;
+            auth_service::queue_confirmation_email(&user, &token, expire_timestamp, conn)?;
+
+            Ok::<_, AppError>(user)
+        })
+    }).await?
+}
+
+#[derive(Deserialize)]
+pub struct NicknameData {
+    pub nickname: String,
+}
+
+impl Validate for NicknameData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::nickname(errors, "nickname", &self.nickname);
+    }
+}
+
+#[derive(Serialize)]
+pub struct NicknameAvailabilityDto {
+    pub nickname: String,
+    pub available: bool,
+}
+
+/// Lets the registration form tell whether a nickname is free before submitting it
+pub async fn nickname_availability(
+    request: HttpRequest,
+    data: web::Query<NicknameData>,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let request_ip = get_request_ip(&request);
+    let nickname = data.into_inner().nickname;
+
+    let c_nickname = nickname.clone();
+    let available = web::block(move || {
+        rate_limiter.check("nickname-availability", request_ip.as_deref(), None)?;
+        user_service::is_nickname_available(&c_nickname, &pool.get().unwrap())
+    }).await??;
+
+    Ok(HttpResponse::Ok().json(NicknameAvailabilityDto { nickname, available }))
+}
+
+pub async fn get_me(
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+
+    let profile = web::block(move || 
+        user_service::get_profile(user_id, &pool.get().unwrap())).await??;
 
-    match &user.reset_password_hash {
-        Some(hash) => {
-            let _r = auth_service::send_confirmation_email(&user.email, expire_timestamp, &hash).await;
-            Ok(HttpResponse::Ok().json(user))
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+#[derive(Deserialize)]
+pub struct UpdateProfileData {
+    pub nickname: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub language: Option<Languages>,
+}
+
+impl Validate for UpdateProfileData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        if let Some(nickname) = &self.nickname {
+            validation::nickname(errors, "nickname", nickname);
+        }
+        if let Some(first_name) = &self.first_name {
+            validation::name(errors, "first_name", first_name);
+        }
+        if let Some(last_name) = &self.last_name {
+            validation::name(errors, "last_name", last_name);
         }
-        None => return Err(AppError::InternalServerError(
-            format!("Reset password hash was not set up properly.")))
     }
 }
+
+pub async fn update_me(
+    data: web::Json<UpdateProfileData>,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+
+    let profile = web::block(move || 
+        user_service::update_profile(user_id, &data, &pool.get().unwrap())).await??;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordData {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+impl Validate for ChangePasswordData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "current_password", &self.current_password);
+        validation::password(errors, "new_password", &self.new_password);
+    }
+}
+
+pub async fn change_password(
+    request: HttpRequest,
+    data: web::Json<ChangePasswordData>,
+    id: Identity,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let request_ip = get_request_ip(&request);
+    let audit = AuditContext::from_request(&request);
+    let current_session_id = session_service::get_current_session_id(&request);
+
+    let c_pool = pool.clone();
+    let result = web::block(move || 
+        user_service::change_password(
+            user_id, 
+            &data, 
+            current_session_id, 
+            request_ip.as_deref(), 
+            &rate_limiter, 
+            &c_pool.get().unwrap())).await?;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::PasswordChange, audit_service::failure_of(&result)).await;
+
+    Ok(HttpResponse::Ok().json(result?))
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailData {
+    pub email: String,
+    pub password: String,
+}
+
+impl Validate for ChangeEmailData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::email(errors, "email", &self.email);
+        validation::required(errors, "password", &self.password);
+    }
+}
+
+pub async fn change_email(
+    request: HttpRequest,
+    data: web::Json<ChangeEmailData>,
+    id: Identity,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let request_ip = get_request_ip(&request);
+    let audit = AuditContext::from_request(&request);
+
+    let c_pool = pool.clone();
+    let result = web::block(move || 
+        user_service::change_email(
+            user_id, 
+            &data, 
+            request_ip.as_deref(), 
+            &rate_limiter, 
+            &c_pool.get().unwrap())).await?;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::EmailChange, audit_service::failure_of(&result)).await;
+
+    result?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Ticket of the Steam account to link, confirmed by the password or a 2FA code
+#[derive(Deserialize)]
+pub struct LinkSteamData {
+    #[serde(flatten)]
+    pub auth: steam::SteamAuthData,
+    pub password: Option<String>,
+    pub code: Option<String>,
+}
+
+impl Validate for LinkSteamData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "auth_ticket", &self.auth.auth_ticket);
+        match (&self.password, &self.code) {
+            (Some(password), _) => validation::required(errors, "password", password),
+            (None, Some(code)) => validation::required(errors, "code", code),
+            (None, None) => validation::required(errors, "password", "")
+        }
+    }
+}
+
+pub async fn link_steam(
+    request: HttpRequest,
+    data: web::Json<LinkSteamData>,
+    id: Identity,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let request_ip = get_request_ip(&request);
+    let audit = AuditContext::from_request(&request);
+    let result = t_link_steam(user_id, data.into_inner(), request_ip, rate_limiter, pool.clone()).await;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::SteamLink, audit_service::failure_of(&result)).await;
+
+    Ok(HttpResponse::Ok().json(result?))
+}
+
+async fn t_link_steam(
+    user_id: i32,
+    data: LinkSteamData,
+    request_ip: Option<String>,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<user_service::ProfileDto> {
+    let c_pool = pool.clone();
+    let (password, code) = (data.password, data.code);
+    web::block(move ||
+        user_service::check_password_or_code(
+            user_id,
+            password.as_deref(),
+            code.as_deref(),
+            "steam-link",
+            request_ip.as_deref(),
+            &rate_limiter,
+            &c_pool.get().unwrap())).await??;
+
+    let ticket = steam::authenticate_user_ticket(&data.auth).await?;
+    ban_service::check_steam(&ticket)?;
+
+    web::block(move || 
+        user_service::link_steam(user_id, ticket.steam_id, &pool.get().unwrap())).await?
+}
+
+pub async fn unlink_steam(
+    request: HttpRequest,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let audit = AuditContext::from_request(&request);
+
+    let c_pool = pool.clone();
+    let result = web::block(move || 
+        user_service::unlink_steam(user_id, &c_pool.get().unwrap())).await?;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::SteamUnlink, audit_service::failure_of(&result)).await;
+
+    Ok(HttpResponse::Ok().json(result?))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountData {
+    pub password: String,
+}
+
+impl Validate for DeleteAccountData {
+    fn check(&self, errors: &mut ValidationErrors) {
+        validation::required(errors, "password", &self.password);
+    }
+}
+
+/// Schedules the deletion of the account, logging in and calling `restore_me` during the grace period cancels it.
+pub async fn delete_me(
+    request: HttpRequest,
+    data: web::Json<DeleteAccountData>,
+    id: Identity,
+    ws: web::Data<Addr<WebsocketLobby>>,
+    rate_limiter: web::Data<RateLimiter>,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    data.validate()?;
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let request_ip = get_request_ip(&request);
+    let audit = AuditContext::from_request(&request);
+
+    let c_pool = pool.clone();
+    let result = web::block(move || 
+        user_service::request_deletion(
+            user_id, 
+            &data, 
+            request_ip.as_deref(), 
+            &rate_limiter, 
+            ws.get_ref().to_owned(),
+            &c_pool.get().unwrap())).await?;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::AccountDeletion, audit_service::failure_of(&result)).await;
+    let deletion = result?;
+    id.logout();
+
+    Ok(HttpResponse::Ok().json(deletion))
+}
+
+pub async fn restore_me(
+    request: HttpRequest,
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+    let audit = AuditContext::from_request(&request);
+
+    let c_pool = pool.clone();
+    let result = web::block(move || 
+        user_service::cancel_deletion(user_id, &c_pool.get().unwrap())).await?;
+    audit_service::record(
+        &pool, &audit, Some(user_id), AuditEventTypes::AccountRestore, audit_service::failure_of(&result)).await;
+
+    Ok(HttpResponse::Ok().json(result?))
+}
+
+/// Latest security events of the account, so that the user can spot what they did not do
+pub async fn get_activity(
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+
+    let activity = web::block(move || 
+        audit_service::get_recent_activity(user_id, &pool.get().unwrap())).await??;
+
+    Ok(HttpResponse::Ok().json(activity))
+}
+
+pub async fn export_me(
+    id: Identity,
+    pool: web::Data<Pool>
+) -> AppResult<HttpResponse> {
+    let user_id = id.id().unwrap().parse::<i32>().unwrap();
+
+    let export = web::block(move || 
+        user_service::export(user_id, &pool.get().unwrap())).await??;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            "Content-Disposition", 
+            format!("attachment; filename=\"rigidity-account-{}.json\"", user_id)))
+        .json(export))
+}
@@ -16,7 +16,32 @@ mod handlers;
 mod models;
 mod errors;
 mod schema;
+mod validation;
 
 pub fn new_websocket_lobby(pool: Pool) -> Addr<services::websocket::WebsocketLobby> {
     services::websocket::WebsocketLobby::new(pool).start()
-}
\ No newline at end of file
+}
+
+pub fn new_matchmaking_poller(
+    interval: std::time::Duration,
+    gamelift: std::sync::Arc<dyn services::aws::GameLiftBackend>,
+    ws: Addr<services::websocket::WebsocketLobby>,
+    pool: Pool
+) -> Addr<services::matchmaking::poller::MatchmakingPoller> {
+    services::matchmaking::poller::MatchmakingPoller::new(interval, gamelift, ws, pool).start()
+}
+
+pub fn new_account_purger(
+    interval: std::time::Duration,
+    pool: Pool
+) -> Addr<services::user::purger::AccountPurger> {
+    services::user::purger::AccountPurger::new(interval, pool).start()
+}
+
+pub fn new_email_sender(
+    interval: std::time::Duration,
+    transport: std::sync::Arc<dyn services::email::EmailTransport>,
+    pool: Pool
+) -> Addr<services::email::sender::EmailSender> {
+    services::email::sender::EmailSender::new(interval, transport, pool).start()
+}
@@ -1,9 +1,15 @@
 use actix_web::{web, HttpResponse, web::Data, App, HttpServer};
 use rigidity_application::{
     cmd::interpret_args,
-    services::aws::get_gamelift_client, 
+    services::aws::get_gamelift_backend, 
+    services::email::get_email_transport,
+    services::sns::SnsVerifier,
+    services::rate_limit::RateLimiter,
     app_conf, 
-    new_websocket_lobby};
+    new_websocket_lobby,
+    new_matchmaking_poller,
+    new_account_purger,
+    new_email_sender};
 use actix_identity::IdentityMiddleware;
 use std::env;
 
@@ -25,21 +31,36 @@ async fn main() -> std::io::Result<()> {
 async fn start_server() -> std::io::Result<()> {
     let conn = app_conf::connect_database();
     let ws_srv = new_websocket_lobby(conn.clone()); //important if clone in closure ref not properly tracked
-    let gamelift = get_gamelift_client().await;
+    let gamelift = get_gamelift_backend(ws_srv.clone(), conn.clone()).await;
+    let sns_verifier = Data::new(SnsVerifier::from_env());
+    let rate_limiter = Data::new(RateLimiter::from_env(conn.clone()));
+
+    // kept until the server stops so that the poller is not dropped
+    let _matchmaking_poller = app_conf::matchmaking_polling_interval()
+        .map(|interval| new_matchmaking_poller(interval, gamelift.clone(), ws_srv.clone(), conn.clone()));
+    let _account_purger = new_account_purger(app_conf::account_purge_interval(), conn.clone());
+    let _email_sender = new_email_sender(app_conf::email_outbox_interval(), get_email_transport(), conn.clone());
 
     let http_server = HttpServer::new(move || {
         App::new()
-            .app_data(Data::new(gamelift.to_owned()))
+            .app_data(Data::from(gamelift.clone()))
             .app_data(Data::new(conn.to_owned()))
             .app_data(Data::new(ws_srv.clone()))
+            .app_data(sns_verifier.clone())
+            .app_data(rate_limiter.clone())
+            .wrap(app_conf::deletion_guard::DeletionGuard)
+            .wrap(app_conf::bearer_auth::BearerAuth)
             .wrap(IdentityMiddleware::default())
-            .wrap(app_conf::middleware_cookie_session())
+            .wrap(app_conf::middleware_session(conn.clone()))
             .wrap(app_conf::middleware_logger())
             .route("/ws", app_conf::ws_routes::get())
             .service(app_conf::open_routes::get_all())
+            .service(app_conf::admin_routes::get_all())
             .service(app_conf::api_routes::get_all())
             .service(app_conf::aws_routes::get_all())
+            .service(app_conf::game_server_routes::get_all())
             .service(app_conf::static_routes::get_all())
+            .configure(app_conf::dev_routes)
             .default_service(web::to(|| HttpResponse::NotFound())) // 404
     });
 
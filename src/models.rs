@@ -1,5 +1,19 @@
 pub mod user;
+pub mod audit_event;
+pub mod ban;
 pub mod custom_room;
+pub mod email_outbox;
+pub mod flexmatch_event;
+pub mod matchmaking_ticket;
+pub mod rate_limit;
+pub mod recovery_code;
+pub mod refresh_token;
+pub mod session;
+pub mod steam_ban_record;
+pub mod user_token;
+pub mod user_totp;
 pub mod forms;
 
-pub type ORMResult<R> = Result<R, diesel::result::Error>;
\ No newline at end of file
+pub type ORMResult<R> = Result<R, diesel::result::Error>;
+
+sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
\ No newline at end of file
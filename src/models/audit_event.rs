@@ -0,0 +1,114 @@
+use crate::schema::audit_events::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::NaiveDateTime;
+use crate::enums::{AuditEventTypes, AuditOutcomes};
+use crate::models::{forms::audit_event::AuditEventForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+
+#[derive(Serialize, Queryable)]
+pub struct AuditEvent {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub event_type: AuditEventTypes,
+    pub outcome: AuditOutcomes,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Criteria of an events search, every one is optional
+#[derive(Default)]
+pub struct AuditEventFilter<'a> {
+    pub user_id: Option<i32>,
+    pub event_type: Option<AuditEventTypes>,
+    pub outcome: Option<AuditOutcomes>,
+    pub ip: Option<&'a str>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+pub fn create(
+    form: AuditEventForm,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::insert_into(audit_events)
+        .values(form)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Latest events of a user
+pub fn get_recent_by_user_id(
+    u_id: &i32,
+    limit: i64,
+    conn: &PgConnection
+) -> ORMResult<Vec<AuditEvent>> {
+    audit_events.filter(user_id.eq(u_id))
+        .order(created_at.desc())
+        .limit(limit)
+        .load::<AuditEvent>(conn)
+}
+
+pub fn get_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<AuditEvent>> {
+    audit_events.filter(user_id.eq(u_id))
+        .order(created_at.desc())
+        .load::<AuditEvent>(conn)
+}
+
+/// Removes what identifies the user from its events, they are unlinked when the account is deleted
+pub fn anonymize_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    diesel::update(audit_events.filter(user_id.eq(u_id)))
+        .set((ip.eq(None::<String>), user_agent.eq(None::<String>)))
+        .execute(conn)
+}
+
+/// Deletes the events without user created before the date
+pub fn delete_unattributed_before(
+    created_before: NaiveDateTime,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    diesel::delete(audit_events.filter(user_id.is_null()).filter(created_at.lt(created_before)))
+        .execute(conn)
+}
+
+/// Events matching the filter, the newest first.
+pub fn search(
+    filter: AuditEventFilter,
+    limit: i64,
+    offset: i64,
+    conn: &PgConnection
+) -> ORMResult<Vec<AuditEvent>> {
+    let mut request = audit_events.into_boxed();
+    if let Some(u_id) = filter.user_id {
+        request = request.filter(user_id.eq(u_id));
+    }
+    if let Some(with_event_type) = filter.event_type {
+        request = request.filter(event_type.eq(with_event_type));
+    }
+    if let Some(with_outcome) = filter.outcome {
+        request = request.filter(outcome.eq(with_outcome));
+    }
+    if let Some(with_ip) = filter.ip {
+        request = request.filter(ip.eq(with_ip));
+    }
+    if let Some(from) = filter.from {
+        request = request.filter(created_at.ge(from));
+    }
+    if let Some(to) = filter.to {
+        request = request.filter(created_at.lt(to));
+    }
+
+    request.order(created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<AuditEvent>(conn)
+}
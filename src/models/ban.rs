@@ -0,0 +1,88 @@
+use crate::schema::bans::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::{NaiveDateTime, Utc};
+use crate::enums::BanScopes;
+use crate::models::{forms::ban::BanForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct Ban {
+    pub id: i32,
+    pub user_id: i32,
+    pub scope: BanScopes,
+    pub reason: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub issued_by: Option<i32>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+pub fn create(
+    form: BanForm,
+    conn: &PgConnection
+) -> ORMResult<Ban> {
+    diesel::insert_into(bans)
+        .values(form)
+        .get_result::<Ban>(conn)
+}
+
+pub fn get(
+    i_d: &i32,
+    conn: &PgConnection
+) -> ORMResult<Ban> {
+    bans.filter(id.eq(i_d))
+        .get_result::<Ban>(conn)
+}
+
+pub fn get_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<Ban>> {
+    bans.filter(user_id.eq(u_id))
+        .order(created_at.desc())
+        .load::<Ban>(conn)
+}
+
+/// Returns the ban of a scope in force for the user, the permanent or latest ending one first.
+pub fn get_active(
+    u_id: &i32,
+    ban_scope: BanScopes,
+    conn: &PgConnection
+) -> ORMResult<Option<Ban>> {
+    bans.filter(user_id.eq(u_id))
+        .filter(scope.eq(ban_scope))
+        .filter(revoked_at.is_null())
+        .filter(expires_at.is_null().or(expires_at.gt(Utc::now().naive_utc())))
+        .order(expires_at.desc().nulls_first())
+        .first::<Ban>(conn)
+        .optional()
+}
+
+/// Returns the scopes in which the user is banned.
+pub fn get_active_scopes(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<BanScopes>> {
+    bans.select(scope)
+        .filter(user_id.eq(u_id))
+        .filter(revoked_at.is_null())
+        .filter(expires_at.is_null().or(expires_at.gt(Utc::now().naive_utc())))
+        .distinct()
+        .load::<BanScopes>(conn)
+}
+
+/// Lifts a ban.
+/// Returns false when it was already revoked.
+pub fn revoke(
+    i_d: &i32,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_updated = diesel::update(bans
+        .filter(id.eq(i_d))
+        .filter(revoked_at.is_null()))
+        .set(revoked_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(nb_updated == 1)
+}
@@ -71,36 +71,46 @@ pub struct CustomRoomSlot {
 
 impl CustomRoomSlot {
     pub fn get_gamelift_attributes(&self, nickname: &str) -> HashMap<String, AttributeValue> {
-        let mut attributes = HashMap::new();
-        attributes.insert(String::from("team"), AttributeValue { 
-            s: None,
-            n: Some(self.team as f64),
-            sdm: None,
-            sl: None
-        });
-        attributes.insert(String::from("team_position"), AttributeValue { 
-            s: None,
-            n: Some(self.team_position as f64),
-            sdm: None,
-            sl: None
-        });
-        attributes.insert(String::from("archetype"), AttributeValue { 
-            s: None,
-            n: Some(self.current_archetype.to_u32() as f64),
-            sdm: None,
-            sl: None
-        });
-        attributes.insert(String::from("nickname"), AttributeValue { 
-            s: Some(nickname.to_owned()),
-            n: None,
-            sdm: None,
-            sl: None
-        });
-
-        attributes
+        get_gamelift_attributes(self.team, self.team_position, &self.current_archetype, nickname)
     }
 }
 
+/// Player attributes expected by the matchmaking rule sets
+pub fn get_gamelift_attributes(
+    team: i32, 
+    team_position: i32, 
+    archetype: &Archetypes, 
+    nickname: &str
+) -> HashMap<String, AttributeValue> {
+    let mut attributes = HashMap::new();
+    attributes.insert(String::from("team"), AttributeValue { 
+        s: None,
+        n: Some(team as f64),
+        sdm: None,
+        sl: None
+    });
+    attributes.insert(String::from("team_position"), AttributeValue { 
+        s: None,
+        n: Some(team_position as f64),
+        sdm: None,
+        sl: None
+    });
+    attributes.insert(String::from("archetype"), AttributeValue { 
+        s: None,
+        n: Some(archetype.to_u32() as f64),
+        sdm: None,
+        sl: None
+    });
+    attributes.insert(String::from("nickname"), AttributeValue { 
+        s: Some(nickname.to_owned()),
+        n: None,
+        sdm: None,
+        sl: None
+    });
+
+    attributes
+}
+
 pub fn get(id: &i32, conn: &PgConnection)
 -> ORMResult<(CustomRoom, Vec<CustomRoomSlot>)> {
     let custom_room = get_without_associations(id, conn)?;
@@ -159,6 +169,18 @@ pub fn get_by_ticket_id(ticket_id: Uuid, conn: &PgConnection)
     Ok((custom_room, slots))
 }
 
+pub fn get_all_matchmaking_tickets(conn: &PgConnection)
+-> ORMResult<Vec<Uuid>> {
+    use crate::schema::custom_rooms::dsl::{matchmaking_ticket, custom_rooms};
+
+    let tickets = custom_rooms
+        .select(matchmaking_ticket)
+        .filter(matchmaking_ticket.is_not_null())
+        .load::<Option<Uuid>>(conn)?;
+
+    Ok(tickets.into_iter().flatten().collect())
+}
+
 pub fn get_all(conn: &PgConnection) 
 -> ORMResult<HashMap<CustomRoom, Vec<CustomRoomSlot>>> {
     use crate::schema::custom_rooms::dsl::*;
@@ -234,16 +256,12 @@ pub fn create(
     use crate::schema::custom_room_slots::dsl::{custom_room_slots};
 
     conn.transaction::<(CustomRoom, Vec<CustomRoomSlot>), Error, _>(move || {
-        diesel::insert_into(custom_rooms)
+        let custom_room_id = diesel::insert_into(custom_rooms)
             .values(CustomRoomForm::new_from_data(
                 &data, 
                 user_id))
-            .execute(conn)?;
-
-        let custom_room_id = custom_rooms
-            .select(id)
-            .order(id.desc())
-            .first(conn)?;
+            .returning(id)
+            .get_result::<i32>(conn)?;
 
         diesel::insert_into(custom_room_slots)
             .values(CustomRoomSlotForm::new_from_custom_room_creation(
@@ -0,0 +1,111 @@
+use crate::schema::email_outbox;
+use crate::schema::email_outbox::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::NaiveDateTime;
+use crate::enums::EmailStatuses;
+use crate::models::{forms::email_outbox::OutboxEmailForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+
+#[derive(Serialize, Queryable)]
+pub struct OutboxEmail {
+    pub id: i32,
+    pub user_id: i32,
+    pub recipient: String,
+    pub subject: String,
+    // encrypted, the links of the bodies are still valid tokens. Cleared once the email is given up.
+    #[serde(skip_serializing)]
+    pub text_body: String,
+    #[serde(skip_serializing)]
+    pub html_body: String,
+    pub status: EmailStatuses,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+pub fn create(
+    form: OutboxEmailForm,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::insert_into(email_outbox::table)
+        .values(&form)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Takes the pending emails due at `now` and pushes back their next attempt to `lease_until`,
+/// so that a sender stopped while sending them retries later and other instances skip them.
+/// The lease must outlast the sending of all of them.
+pub fn claim_due(
+    now: NaiveDateTime,
+    lease_until: NaiveDateTime,
+    limit: i64,
+    conn: &PgConnection
+) -> ORMResult<Vec<OutboxEmail>> {
+    conn.transaction(|| {
+        let due_ids = email_outbox
+            .select(id)
+            .filter(status.eq(EmailStatuses::Pending))
+            .filter(next_attempt_at.le(now))
+            .order(next_attempt_at.asc())
+            .limit(limit)
+            .for_update()
+            .skip_locked()
+            .load::<i32>(conn)?;
+
+        diesel::update(email_outbox.filter(id.eq_any(due_ids)))
+            .set(next_attempt_at.eq(lease_until))
+            .get_results::<OutboxEmail>(conn)
+    })
+}
+
+pub fn delete(
+    i_d: &i32,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::delete(email_outbox.filter(id.eq(i_d)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Records a failed attempt, the email is retried at `retry_at` or given up without it and its
+/// bodies are cleared.
+pub fn record_failure(
+    i_d: &i32,
+    error: &str,
+    retry_at: Option<NaiveDateTime>,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    let update = diesel::update(email_outbox.filter(id.eq(i_d)));
+    match retry_at {
+        Some(retry_at) => update
+            .set((attempts.eq(attempts + 1), last_error.eq(error), next_attempt_at.eq(retry_at)))
+            .execute(conn)?,
+        None => update
+            .set((
+                attempts.eq(attempts + 1),
+                last_error.eq(error),
+                status.eq(EmailStatuses::Failed),
+                text_body.eq(""),
+                html_body.eq("")))
+            .execute(conn)?,
+    };
+
+    Ok(())
+}
+
+pub fn get_all_failed(
+    limit: i64,
+    offset: i64,
+    conn: &PgConnection
+) -> ORMResult<Vec<OutboxEmail>> {
+    email_outbox.filter(status.eq(EmailStatuses::Failed))
+        .order(created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<OutboxEmail>(conn)
+}
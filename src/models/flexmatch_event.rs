@@ -0,0 +1,34 @@
+use crate::schema::flexmatch_events::dsl::*;
+use crate::diesel::prelude::*;
+use crate::models::ORMResult;
+use diesel::{PgConnection};
+
+/// Marks an event as being processed.
+/// Returns false when the event was already claimed by a previous delivery.
+pub fn claim(
+    e_id: &str,
+    e_type: &str,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_inserted = diesel::insert_into(flexmatch_events)
+        .values((
+            event_id.eq(e_id),
+            event_type.eq(e_type)
+        ))
+        .on_conflict(event_id)
+        .do_nothing()
+        .execute(conn)?;
+
+    Ok(nb_inserted == 1)
+}
+
+/// Forgets an event so that a new delivery of it will be processed.
+pub fn release(
+    e_id: &str,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::delete(flexmatch_events.filter(event_id.eq(e_id)))
+        .execute(conn)?;
+
+    Ok(())
+}
@@ -1,2 +1,12 @@
+pub mod audit_event;
+pub mod ban;
 pub mod custom_room;
+pub mod email_outbox;
+pub mod matchmaking_ticket;
+pub mod recovery_code;
+pub mod refresh_token;
+pub mod session;
+pub mod steam_ban_record;
+pub mod user_token;
+pub mod user_totp;
 pub mod user;
\ No newline at end of file
@@ -0,0 +1,13 @@
+use crate::schema::audit_events;
+use crate::enums::{AuditEventTypes, AuditOutcomes};
+
+#[derive(Insertable)]
+#[table_name = "audit_events"]
+pub struct AuditEventForm<'a> {
+    pub user_id: Option<i32>,
+    pub event_type: AuditEventTypes,
+    pub outcome: AuditOutcomes,
+    pub ip: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub details: Option<&'a str>,
+}
@@ -0,0 +1,13 @@
+use crate::schema::bans;
+use crate::chrono::NaiveDateTime;
+use crate::enums::BanScopes;
+
+#[derive(Insertable)]
+#[table_name = "bans"]
+pub struct BanForm<'a> {
+    pub user_id: i32,
+    pub scope: BanScopes,
+    pub reason: &'a str,
+    pub expires_at: Option<NaiveDateTime>,
+    pub issued_by: Option<i32>,
+}
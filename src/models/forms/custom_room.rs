@@ -20,7 +20,7 @@ pub struct CustomRoomForm<'a> {
 impl<'a> CustomRoomForm<'a> {
     pub fn new_from_data(create_data: &'a CustomRoomData, user_id: &'a i32) -> Self {
         CustomRoomForm {
-            label: &create_data.label,
+            label: create_data.label.trim(),
             user_id: user_id,
             nb_teams: &create_data.nb_teams,
             max_player_per_team: &create_data.max_players_per_team,
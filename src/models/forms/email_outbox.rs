@@ -0,0 +1,13 @@
+use crate::chrono::NaiveDateTime;
+use crate::schema::email_outbox;
+
+#[derive(Insertable)]
+#[table_name = "email_outbox"]
+pub struct OutboxEmailForm<'a> {
+    pub user_id: i32,
+    pub recipient: &'a str,
+    pub subject: &'a str,
+    pub text_body: &'a str,
+    pub html_body: &'a str,
+    pub next_attempt_at: NaiveDateTime,
+}
@@ -0,0 +1,11 @@
+use crate::schema::matchmaking_tickets;
+use uuid::Uuid;
+
+#[derive(Insertable)]
+#[table_name = "matchmaking_tickets"]
+pub struct MatchmakingTicketForm<'a> {
+    pub ticket_id: &'a Uuid,
+    pub configuration_name: &'a str,
+    pub is_backfill: bool,
+    pub game_session_arn: Option<&'a str>,
+}
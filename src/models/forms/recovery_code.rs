@@ -0,0 +1,8 @@
+use crate::schema::user_recovery_codes;
+
+#[derive(Insertable)]
+#[table_name = "user_recovery_codes"]
+pub struct RecoveryCodeForm {
+    pub user_id: i32,
+    pub code_hash: String,
+}
@@ -0,0 +1,13 @@
+use crate::chrono::NaiveDateTime;
+use crate::schema::refresh_tokens;
+use uuid::Uuid;
+
+#[derive(Insertable)]
+#[table_name = "refresh_tokens"]
+pub struct RefreshTokenForm<'a> {
+    pub token_id: &'a Uuid,
+    pub family_id: &'a Uuid,
+    pub user_id: i32,
+    pub token_hash: &'a str,
+    pub expires_at: NaiveDateTime,
+}
@@ -0,0 +1,17 @@
+use crate::chrono::NaiveDateTime;
+use crate::schema::sessions;
+use uuid::Uuid;
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "sessions"]
+#[changeset_options(treat_none_as_null="true")]
+pub struct SessionForm<'a> {
+    pub session_id: &'a Uuid,
+    pub session_key: &'a str,
+    pub user_id: Option<i32>,
+    pub state: &'a str,
+    pub ip: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
@@ -0,0 +1,10 @@
+use crate::schema::steam_ban_records;
+
+#[derive(Insertable)]
+#[table_name = "steam_ban_records"]
+pub struct SteamBanRecordForm<'a> {
+    pub user_id: i32,
+    pub steam_id: &'a str,
+    pub vac_banned: bool,
+    pub publisher_banned: bool,
+}
@@ -1,29 +1,46 @@
 use crate::chrono::NaiveDateTime;
+use crate::enums::Languages;
 use crate::schema::users;
 use crate::handlers::user::CreateUserData;
+use crate::models::user;
+use crate::services::auth;
 
 #[derive(Insertable, AsChangeset)]
 #[table_name = "users"]
 pub struct UserForm<'a> {
     pub email: &'a str,
     pub nickname: &'a str,
-    pub steam_id: &'a str,
+    pub steam_id: Option<&'a str>,
     pub first_name: &'a str,
     pub last_name: &'a str,
     pub hash: &'a str,
+    pub password_pepper_id: &'a str,
     pub birth_date: NaiveDateTime,
+    pub language: Languages,
 }
 
 impl<'a> UserForm<'a> {
-    pub fn new_from_data(create_data: &'a CreateUserData, steam_id: &'a str) -> Self {
+    pub fn new_from_data(create_data: &'a CreateUserData, steam_id: &'a str, language: Languages) -> Self {
         UserForm {
             email: &create_data.email,
             nickname: &create_data.nickname,
-            steam_id: steam_id,
-            first_name: &create_data.first_name,
-            last_name: &create_data.last_name,
-            hash: "Waiting for init",
+            steam_id: Some(steam_id),
+            first_name: create_data.first_name.trim(),
+            last_name: create_data.last_name.trim(),
+            hash: user::NO_PASSWORD_HASH,
+            password_pepper_id: auth::current_pepper_id(),
             birth_date: create_data.birth_date.naive_utc(),
+            language,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Profile fields a user can change, `None` fields are left unchanged
+#[derive(AsChangeset)]
+#[table_name = "users"]
+pub struct UserProfileForm<'a> {
+    pub nickname: Option<&'a str>,
+    pub first_name: Option<&'a str>,
+    pub last_name: Option<&'a str>,
+    pub language: Option<Languages>,
+}
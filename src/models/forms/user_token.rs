@@ -0,0 +1,15 @@
+use crate::chrono::NaiveDateTime;
+use crate::enums::UserTokenPurposes;
+use crate::schema::user_tokens;
+use uuid::Uuid;
+
+#[derive(Insertable)]
+#[table_name = "user_tokens"]
+pub struct UserTokenForm<'a> {
+    pub token_id: &'a Uuid,
+    pub user_id: i32,
+    pub purpose: UserTokenPurposes,
+    pub token_hash: &'a str,
+    pub expires_at: NaiveDateTime,
+    pub request_ip: Option<&'a str>,
+}
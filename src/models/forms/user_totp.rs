@@ -0,0 +1,8 @@
+use crate::schema::user_totp;
+
+#[derive(Insertable)]
+#[table_name = "user_totp"]
+pub struct UserTotpForm<'a> {
+    pub user_id: i32,
+    pub secret: &'a str,
+}
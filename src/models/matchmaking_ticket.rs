@@ -0,0 +1,82 @@
+use crate::schema::matchmaking_tickets::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::{NaiveDateTime, Utc};
+use crate::enums::MatchmakingStatuses;
+use crate::models::{forms::matchmaking_ticket::MatchmakingTicketForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Queryable)]
+pub struct MatchmakingTicket {
+    pub id: i32,
+    pub ticket_id: Uuid,
+    pub configuration_name: String,
+    pub status: MatchmakingStatuses,
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>,
+    pub is_backfill: bool,
+    pub game_session_arn: Option<String>,
+}
+
+impl MatchmakingTicket {
+    pub fn get_elapsed_seconds(&self) -> i64 {
+        let end = self.ended_at.unwrap_or_else(|| Utc::now().naive_utc());
+        (end - self.started_at).num_seconds().max(0)
+    }
+}
+
+pub fn create(
+    form: MatchmakingTicketForm,
+    conn: &PgConnection
+) -> ORMResult<MatchmakingTicket> {
+    diesel::insert_into(matchmaking_tickets)
+        .values(form)
+        .get_result::<MatchmakingTicket>(conn)
+}
+
+pub fn get_by_ticket_id(
+    t_id: &Uuid,
+    conn: &PgConnection
+) -> ORMResult<MatchmakingTicket> {
+    matchmaking_tickets.filter(ticket_id.eq(t_id))
+        .get_result::<MatchmakingTicket>(conn)
+}
+
+pub fn update_status(
+    t_id: &Uuid,
+    new_status: &MatchmakingStatuses,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    let new_ended_at = if new_status.is_terminal() {
+        Some(Utc::now().naive_utc())
+    } else {
+        None
+    };
+
+    diesel::update(matchmaking_tickets.filter(ticket_id.eq(t_id)))
+        .set((
+            status.eq(new_status),
+            ended_at.eq(new_ended_at)
+        )).execute(conn)?;
+
+    Ok(())
+}
+
+/// Durations in seconds of the last `limit` tickets of a configuration which found a match.
+pub fn get_recent_durations(
+    config_name: &str,
+    limit: i64,
+    conn: &PgConnection
+) -> ORMResult<Vec<i64>> {
+    let tickets = matchmaking_tickets
+        .filter(configuration_name.eq(config_name))
+        .filter(status.eq(MatchmakingStatuses::Succeeded))
+        .filter(is_backfill.eq(false))
+        .filter(ended_at.is_not_null())
+        .order(ended_at.desc())
+        .limit(limit)
+        .load::<MatchmakingTicket>(conn)?;
+
+    Ok(tickets.iter().map(|ticket| ticket.get_elapsed_seconds()).collect())
+}
@@ -0,0 +1,117 @@
+use crate::diesel::prelude::*;
+use crate::chrono::NaiveDateTime;
+use crate::models::ORMResult;
+use diesel::{PgConnection};
+
+/// Records an attempt and returns the number of attempts made since `window_start`.
+pub fn record_attempt(
+    key: &str,
+    window_start: NaiveDateTime,
+    conn: &PgConnection
+) -> ORMResult<i64> {
+    use crate::schema::rate_limit_attempts::dsl::*;
+
+    conn.transaction(|| {
+        diesel::delete(rate_limit_attempts
+            .filter(attempt_key.eq(key))
+            .filter(attempted_at.lt(window_start)))
+            .execute(conn)?;
+
+        diesel::insert_into(rate_limit_attempts)
+            .values(attempt_key.eq(key))
+            .execute(conn)?;
+
+        rate_limit_attempts
+            .filter(attempt_key.eq(key))
+            .count()
+            .get_result::<i64>(conn)
+    })
+}
+
+/// Deletes the attempts of every key made before `window_start`, they are no longer counted.
+pub fn delete_attempts_before(
+    window_start: NaiveDateTime,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    use crate::schema::rate_limit_attempts::dsl::*;
+
+    diesel::delete(rate_limit_attempts.filter(attempted_at.lt(window_start)))
+        .execute(conn)
+}
+
+pub fn get_locked_until(
+    key: &str,
+    conn: &PgConnection
+) -> ORMResult<Option<NaiveDateTime>> {
+    use crate::schema::account_lockouts::dsl::*;
+
+    let lockout = account_lockouts.filter(lockout_key.eq(key))
+        .select(locked_until)
+        .get_result::<Option<NaiveDateTime>>(conn)
+        .optional()?;
+
+    Ok(lockout.flatten())
+}
+
+/// Counts a failure and returns the number of consecutive failures.
+pub fn record_failure(
+    key: &str,
+    conn: &PgConnection
+) -> ORMResult<i32> {
+    use crate::schema::account_lockouts::dsl::*;
+
+    diesel::insert_into(account_lockouts)
+        .values((lockout_key.eq(key), failures.eq(1)))
+        .on_conflict(lockout_key)
+        .do_update()
+        .set(failures.eq(failures + 1))
+        .returning(failures)
+        .get_result::<i32>(conn)
+}
+
+pub fn lock(
+    key: &str,
+    until: NaiveDateTime,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    use crate::schema::account_lockouts::dsl::*;
+
+    diesel::update(account_lockouts.filter(lockout_key.eq(key)))
+        .set(locked_until.eq(until))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn reset(
+    key: &str,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    use crate::schema::account_lockouts::dsl::*;
+
+    diesel::delete(account_lockouts.filter(lockout_key.eq(key)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+
+/// Deletes the lockout and the attempts of an account.
+pub fn delete_by_account(
+    key: &str,
+    attempt_key_pattern: &str,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    conn.transaction(|| {
+        {
+            use crate::schema::account_lockouts::dsl::*;
+            diesel::delete(account_lockouts.filter(lockout_key.eq(key)))
+                .execute(conn)?;
+        }
+        use crate::schema::rate_limit_attempts::dsl::*;
+        diesel::delete(rate_limit_attempts.filter(attempt_key.like(attempt_key_pattern)))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
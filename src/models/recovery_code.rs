@@ -0,0 +1,64 @@
+use crate::schema::user_recovery_codes::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::{NaiveDateTime, Utc};
+use crate::models::{forms::recovery_code::RecoveryCodeForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+
+#[derive(Serialize, Queryable)]
+pub struct RecoveryCode {
+    pub id: i32,
+    pub user_id: i32,
+    #[serde(skip_serializing)]
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+pub fn get_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<RecoveryCode>> {
+    user_recovery_codes.filter(user_id.eq(u_id))
+        .load::<RecoveryCode>(conn)
+}
+
+/// Replaces the recovery codes of a user.
+pub fn replace_all(
+    u_id: &i32,
+    forms: Vec<RecoveryCodeForm>,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    conn.transaction(|| {
+        delete_all(u_id, conn)?;
+
+        diesel::insert_into(user_recovery_codes)
+            .values(&forms)
+            .execute(conn)
+    })
+}
+
+/// Marks an unused recovery code as used.
+/// Returns false when the code is unknown or already used.
+pub fn use_code(
+    u_id: &i32,
+    hash: &str,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_updated = diesel::update(user_recovery_codes
+        .filter(user_id.eq(u_id))
+        .filter(code_hash.eq(hash))
+        .filter(used_at.is_null()))
+        .set(used_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(nb_updated == 1)
+}
+
+pub fn delete_all(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    diesel::delete(user_recovery_codes.filter(user_id.eq(u_id)))
+        .execute(conn)
+}
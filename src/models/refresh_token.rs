@@ -0,0 +1,97 @@
+use crate::schema::refresh_tokens::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::{NaiveDateTime, Utc};
+use crate::models::{forms::refresh_token::RefreshTokenForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Queryable)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub token_id: Uuid,
+    pub family_id: Uuid,
+    pub user_id: i32,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl RefreshToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at < Utc::now().naive_utc()
+    }
+}
+
+pub fn create(
+    form: RefreshTokenForm,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::insert_into(refresh_tokens)
+        .values(form)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_by_token_id(
+    t_id: &Uuid,
+    conn: &PgConnection
+) -> ORMResult<RefreshToken> {
+    refresh_tokens.filter(token_id.eq(t_id))
+        .get_result::<RefreshToken>(conn)
+}
+
+/// Marks a token as used.
+/// Returns false when it was already used by a concurrent request.
+pub fn mark_used(
+    t_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_updated = diesel::update(refresh_tokens
+        .filter(id.eq(t_id))
+        .filter(used_at.is_null()))
+        .set(used_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(nb_updated == 1)
+}
+
+pub fn revoke_family(
+    f_id: &Uuid,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::update(refresh_tokens
+        .filter(family_id.eq(f_id))
+        .filter(revoked_at.is_null()))
+        .set(revoked_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn revoke_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::update(refresh_tokens
+        .filter(user_id.eq(u_id))
+        .filter(revoked_at.is_null()))
+        .set(revoked_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+
+pub fn get_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<RefreshToken>> {
+    refresh_tokens.filter(user_id.eq(u_id))
+        .order(created_at.desc())
+        .load::<RefreshToken>(conn)
+}
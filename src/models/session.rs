@@ -0,0 +1,135 @@
+use crate::schema::sessions::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::{NaiveDateTime, Utc};
+use crate::models::{forms::session::SessionForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Queryable)]
+pub struct Session {
+    #[serde(skip_serializing)]
+    pub id: i32,
+    pub session_id: Uuid,
+    #[serde(skip_serializing)]
+    pub session_key: String,
+    #[serde(skip_serializing)]
+    pub user_id: Option<i32>,
+    #[serde(skip_serializing)]
+    pub state: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Creates a session, the expired sessions are removed at the same time.
+pub fn create(
+    form: SessionForm,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::delete(sessions.filter(expires_at.lt(Utc::now().naive_utc())))
+        .execute(conn)?;
+
+    diesel::insert_into(sessions)
+        .values(form)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_by_session_key(
+    key: &str,
+    conn: &PgConnection
+) -> ORMResult<Session> {
+    sessions.filter(session_key.eq(key))
+        .filter(expires_at.gt(Utc::now().naive_utc()))
+        .get_result::<Session>(conn)
+}
+
+pub fn get_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<Session>> {
+    sessions.filter(user_id.eq(u_id))
+        .filter(expires_at.gt(Utc::now().naive_utc()))
+        .order(last_seen_at.desc())
+        .load::<Session>(conn)
+}
+
+/// Returns false when the session does not exist anymore.
+pub fn update(
+    key: &str,
+    form: SessionForm,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_updated = diesel::update(sessions.filter(session_key.eq(key)))
+        .set(form)
+        .execute(conn)?;
+
+    Ok(nb_updated == 1)
+}
+
+pub fn update_expires_at(
+    key: &str,
+    new_expires_at: NaiveDateTime,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::update(sessions.filter(session_key.eq(key)))
+        .set((
+            last_seen_at.eq(Utc::now().naive_utc()),
+            expires_at.eq(new_expires_at)
+        )).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn delete_by_session_key(
+    key: &str,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::delete(sessions.filter(session_key.eq(key)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Returns false when the user has no such session.
+pub fn delete_by_session_id(
+    u_id: &i32,
+    s_id: &Uuid,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_deleted = diesel::delete(sessions
+        .filter(user_id.eq(u_id))
+        .filter(session_id.eq(s_id)))
+        .execute(conn)?;
+
+    Ok(nb_deleted == 1)
+}
+
+pub fn delete_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::delete(sessions.filter(user_id.eq(u_id)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+
+/// Deletes the sessions of a user but the one given.
+pub fn delete_others_by_user_id(
+    u_id: &i32,
+    kept_session_id: &Uuid,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::delete(sessions
+        .filter(user_id.eq(u_id))
+        .filter(session_id.ne(kept_session_id)))
+        .execute(conn)?;
+
+    Ok(())
+}
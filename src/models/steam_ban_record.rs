@@ -0,0 +1,46 @@
+use crate::schema::steam_ban_records::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::NaiveDateTime;
+use crate::models::{forms::steam_ban_record::SteamBanRecordForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+
+/// Ban flags reported by Steam at a login
+#[derive(Serialize, Queryable)]
+pub struct SteamBanRecord {
+    pub id: i32,
+    pub user_id: i32,
+    pub steam_id: String,
+    pub vac_banned: bool,
+    pub publisher_banned: bool,
+    pub created_at: NaiveDateTime,
+}
+
+pub fn create(
+    form: SteamBanRecordForm,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::insert_into(steam_ban_records)
+        .values(form)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<SteamBanRecord>> {
+    steam_ban_records.filter(user_id.eq(u_id))
+        .order(created_at.desc())
+        .load::<SteamBanRecord>(conn)
+}
+
+pub fn get_latest_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<SteamBanRecord> {
+    steam_ban_records.filter(user_id.eq(u_id))
+        .order(created_at.desc())
+        .first::<SteamBanRecord>(conn)
+}
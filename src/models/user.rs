@@ -1,13 +1,11 @@
-use crate::{handlers::auth::ResetPassData, schema::users};
+use crate::schema::users;
 use crate::schema::users::dsl::*;
 use crate::diesel::prelude::*;
-use crate::chrono::{Duration, Utc, NaiveDateTime};
+use crate::chrono::NaiveDateTime;
+use crate::enums::{Languages, UserRoles};
 use diesel::{PgConnection};
 use serde::{Deserialize, Serialize};
-use crate::errors::*;
-use crate::app_conf::SECRET_KEY;
-use crate::models::{forms::user::UserForm, ORMResult};
-use diesel::result::Error;
+use crate::models::{forms::user::{UserForm, UserProfileForm}, lower, ORMResult};
 
 #[derive(Serialize, Deserialize, Queryable, AsChangeset)]
 #[changeset_options(treat_none_as_null="true")]
@@ -18,35 +16,37 @@ pub struct User {
     #[serde(skip_serializing)]
     pub hash: String,
     #[serde(skip_serializing)]
-    pub reset_password_hash: Option<String>,
-    #[serde(skip_serializing)]
-    pub password_hash_expire_at: Option<NaiveDateTime>,
-    #[serde(skip_serializing)]
     pub created_at: NaiveDateTime,
-    pub steam_id: String,
+    pub steam_id: Option<String>,
     pub first_name: String,
     pub last_name: String,
     pub birth_date: NaiveDateTime,
     #[serde(skip_serializing)]
     pub email_confirmation_required: bool,
+    #[serde(skip_serializing)]
+    pub password_pepper_id: String,
+    #[serde(skip_serializing)]
+    pub deletion_requested_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub role: UserRoles,
+    pub language: Languages,
+    #[serde(skip_serializing)]
+    pub token_version: i32,
+    #[serde(skip_serializing)]
+    pub pending_email: Option<String>,
 }
 
-impl User {
-    pub fn is_password_ok(&self, password: &str) -> AppResult<bool> {        
-        argon2::verify_encoded_ext(
-            &self.hash, 
-            password.as_bytes(), 
-            SECRET_KEY.as_bytes(), 
-            &[])
-            .map_err(|err| {
-                dbg!(err);
-                AppError::Unauthorized
-            })
-    }
+/// Hash of the accounts created with Steam until a password is set by a reset
+pub const NO_PASSWORD_HASH: &str = "Waiting for init";
 
+impl User {
     pub fn can_login(&self) -> bool {
         !self.email_confirmation_required
     }
+
+    pub fn has_password(&self) -> bool {
+        self.hash != NO_PASSWORD_HASH
+    }
 }
 
 pub fn get(
@@ -61,7 +61,7 @@ pub fn get_by_email(
     em8l: &str, 
     conn: &PgConnection
 ) -> ORMResult<User> {
-    users.filter(email.eq(em8l))
+    users.filter(lower(email).eq(em8l.to_lowercase()))
         .get_result::<User>(conn)
 }
 
@@ -73,117 +73,193 @@ pub fn get_by_steam_id(
         .get_result::<User>(conn)
 }
 
-pub fn get_by_reset_password_hash(
-    h: &str, 
+/// Users whose email or nickname contains the query or whose Steam id is the query, the newest first.
+pub fn search(
+    query: Option<&str>,
+    with_role: Option<UserRoles>,
+    limit: i64,
+    offset: i64,
     conn: &PgConnection
-) -> ORMResult<User> {
-    users.filter(reset_password_hash.eq(h))
-        .get_result::<User>(conn) 
+) -> ORMResult<Vec<User>> {
+    let mut request = users.into_boxed();
+    if let Some(query) = query {
+        let pattern = format!("%{}%", query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        request = request.filter(email.ilike(pattern.clone())
+            .or(nickname.ilike(pattern))
+            .or(steam_id.eq(query)));
+    }
+    if let Some(with_role) = with_role {
+        request = request.filter(role.eq(with_role));
+    }
+
+    request.order(created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<User>(conn)
+}
+
+pub fn is_nickname_taken(
+    nick: &str,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        users.filter(lower(nickname).eq(nick.to_lowercase()))))
+        .get_result::<bool>(conn)
 }
 
 pub fn create(
     data: UserForm,
-    email_confirmation_hash: &str,
     conn: &PgConnection
-) -> ORMResult<(User, i64)> {
+) -> ORMResult<User> {
     use crate::schema::users::dsl::users;
-    let em8l = data.email;
 
-    conn.transaction::<(User, i64), Error, _>(move || {
-        diesel::insert_into(users)
-            .values(data)
-            .execute(conn)?;
-
-        let expire_timestamp = update_reset_password_hash(em8l, email_confirmation_hash, conn)?;
-
-        let user_id = users
-            .select(id)
-            .order(id.desc())
-            .first(conn)?;
+    diesel::insert_into(users)
+        .values(data)
+        .get_result::<User>(conn)
+}
 
-        let user = get(&user_id, conn)?;   
+pub fn update_email(
+    new_email: &str,
+    ssteam_id: &u64,
+    conn: &PgConnection
+) -> ORMResult<User> {
+    diesel::update(users.filter(steam_id.eq(ssteam_id.to_string())))
+        .set(email.eq(new_email))
+        .get_result::<User>(conn)
+}
 
-        Ok((user, expire_timestamp))
-    })
+pub fn update_profile(
+    i_d: &i32,
+    form: UserProfileForm,
+    conn: &PgConnection
+) -> ORMResult<User> {
+    diesel::update(users.filter(id.eq(i_d)))
+        .set(form)
+        .get_result::<User>(conn)
 }
 
-pub fn update_reset_password_hash(
-    em8l: &str, 
-    h: &str, 
+/// Changes the email of a user, the new one has to be confirmed before the next login.
+/// Keeps the new address until it is confirmed, see `apply_pending_email`
+pub fn set_pending_email(
+    i_d: &i32,
+    new_email: &str,
     conn: &PgConnection
-) -> ORMResult<i64> {
-    let time_in_4_hours = (Utc::now() + Duration::hours(4)).timestamp();
+) -> ORMResult<User> {
+    diesel::update(users.filter(id.eq(i_d)))
+        .set(pending_email.eq(new_email))
+        .get_result::<User>(conn)
+}
 
-    diesel::update(users.filter(email.eq(em8l)))
+/// Replaces the address by the confirmed pending one
+pub fn apply_pending_email(
+    i_d: &i32,
+    new_email: &str,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    let no_email: Option<String> = None;
+    diesel::update(users.filter(id.eq(i_d)))
         .set((
-            reset_password_hash.eq(h),
-            password_hash_expire_at.eq(NaiveDateTime::from_timestamp_opt(time_in_4_hours, 0))
+            email.eq(new_email),
+            pending_email.eq(no_email),
+            email_confirmation_required.eq(false)
         )).execute(conn)?;
 
-    Ok(time_in_4_hours)
+    Ok(())
 }
 
-pub fn update_email(
-    new_email: &str,
-    ssteam_id: &u64,
-    email_confirmation_hash: &str,
+/// Links (`Some`) or unlinks (`None`) a Steam account
+pub fn set_steam_id(
+    i_d: &i32,
+    new_steam_id: Option<&str>,
     conn: &PgConnection
-) -> ORMResult<(User, i64)> {
-    conn.transaction::<(User, i64), Error, _>(move || {
-        diesel::update(users.filter(steam_id.eq(ssteam_id.to_string())))
-            .set(email.eq(new_email)).execute(conn)?;
-
-        let expire_timestamp = update_reset_password_hash(new_email, email_confirmation_hash, conn)?;
-
-        let user = get_by_email(new_email, conn)?;   
-
-        Ok((user, expire_timestamp))
-    })
+) -> ORMResult<User> {
+    diesel::update(users.filter(id.eq(i_d)))
+        .set(steam_id.eq(new_steam_id))
+        .get_result::<User>(conn)
 }
 
-pub fn update_password(
-    data: &ResetPassData,
+pub fn update_hash(
+    i_d: &i32,
     new_hash: &str,
+    pepper_id: &str,
     conn: &PgConnection
 ) -> ORMResult<()> {
-    let new_reset_password_hash: Option<String> = None; 
-    let new_expire_at: Option<NaiveDateTime> = None;
-
-    diesel::update(users.filter(reset_password_hash.eq(data.hash.clone())))
+    diesel::update(users.filter(id.eq(i_d)))
         .set((
-            reset_password_hash.eq(new_reset_password_hash),
-            password_hash_expire_at.eq(new_expire_at),
-            hash.eq(new_hash)
+            hash.eq(new_hash),
+            password_pepper_id.eq(pepper_id)
         )).execute(conn)?;
 
     Ok(())
 }
 
-pub fn cancel_reset_password_hash(
-    h: &str, 
+pub fn get_token_version(
+    i_d: &i32,
     conn: &PgConnection
-) -> ORMResult<()> {   
-    let new_hash: Option<String> = None; 
-    let new_expire_at: Option<NaiveDateTime> = None;
-    diesel::update(users.filter(reset_password_hash.eq(h)))
-        .set((
-            reset_password_hash.eq(new_hash),
-            password_hash_expire_at.eq(new_expire_at)
-        )).execute(conn)?;
+) -> ORMResult<i32> {
+    users.select(token_version)
+        .filter(id.eq(i_d))
+        .first::<i32>(conn)
+}
+
+/// Ends the access tokens issued until now
+pub fn increment_token_version(
+    i_d: &i32,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::update(users.filter(id.eq(i_d)))
+        .set(token_version.eq(token_version + 1))
+        .execute(conn)?;
 
     Ok(())
 }
 
-pub fn confirm_email(reset_pass_hash: &str, conn: &PgConnection) -> ORMResult<()> {
-    let new_reset_password_hash: Option<String> = None; 
-    let new_expire_at: Option<NaiveDateTime> = None;
+pub fn confirm_email(i_d: &i32, conn: &PgConnection) -> ORMResult<()> {
+    diesel::update(users.filter(id.eq(i_d)))
+        .set(email_confirmation_required.eq(false))
+        .execute(conn)?;
 
-    diesel::update(users.filter(reset_password_hash.eq(reset_pass_hash.clone())))
-        .set((
-            reset_password_hash.eq(new_reset_password_hash),
-            password_hash_expire_at.eq(new_expire_at),
-            email_confirmation_required.eq(false)
-        )).execute(conn)?;
+    Ok(())
+}
+
+
+pub fn set_role(
+    i_d: &i32,
+    new_role: UserRoles,
+    conn: &PgConnection
+) -> ORMResult<User> {
+    diesel::update(users.filter(id.eq(i_d)))
+        .set(role.eq(new_role))
+        .get_result::<User>(conn)
+}
+
+/// Schedules or cancels (`None`) the deletion of a user.
+pub fn set_deletion_requested_at(
+    i_d: &i32,
+    requested_at: Option<NaiveDateTime>,
+    conn: &PgConnection
+) -> ORMResult<()> {
+    diesel::update(users.filter(id.eq(i_d)))
+        .set(deletion_requested_at.eq(requested_at))
+        .execute(conn)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Users whose deletion was requested before `requested_before`
+pub fn get_all_deletion_requested_before(
+    requested_before: NaiveDateTime,
+    conn: &PgConnection
+) -> ORMResult<Vec<User>> {
+    users.filter(deletion_requested_at.lt(requested_before))
+        .load::<User>(conn)
+}
+
+/// Deletes a user, everything referencing it is removed by the foreign keys.
+pub fn delete(
+    i_d: &i32,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    diesel::delete(users.filter(id.eq(i_d)))
+        .execute(conn)
+}
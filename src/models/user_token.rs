@@ -0,0 +1,90 @@
+use crate::schema::user_tokens::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::{NaiveDateTime, Utc};
+use crate::enums::UserTokenPurposes;
+use crate::models::{forms::user_token::UserTokenForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Queryable)]
+pub struct UserToken {
+    pub id: i32,
+    pub token_id: Uuid,
+    pub user_id: i32,
+    pub purpose: UserTokenPurposes,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub request_ip: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl UserToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at < Utc::now().naive_utc()
+    }
+}
+
+/// Creates a token, the pending tokens of the same purpose are revoked.
+pub fn create(
+    form: UserTokenForm,
+    conn: &PgConnection
+) -> ORMResult<UserToken> {
+    conn.transaction(|| {
+        diesel::delete(user_tokens
+            .filter(user_id.eq(form.user_id))
+            .filter(purpose.eq(form.purpose))
+            .filter(used_at.is_null()))
+            .execute(conn)?;
+
+        diesel::insert_into(user_tokens)
+            .values(form)
+            .get_result::<UserToken>(conn)
+    })
+}
+
+pub fn get_by_token_id(
+    t_id: &Uuid,
+    conn: &PgConnection
+) -> ORMResult<UserToken> {
+    user_tokens.filter(token_id.eq(t_id))
+        .get_result::<UserToken>(conn)
+}
+
+pub fn get_unused_by_token_hash(
+    t_hash: &str,
+    t_purpose: &UserTokenPurposes,
+    conn: &PgConnection
+) -> ORMResult<UserToken> {
+    user_tokens.filter(token_hash.eq(t_hash))
+        .filter(purpose.eq(t_purpose))
+        .filter(used_at.is_null())
+        .first::<UserToken>(conn)
+}
+
+/// Marks a token as used.
+/// Returns false when it was already used by a concurrent request.
+pub fn mark_used(
+    t_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_updated = diesel::update(user_tokens
+        .filter(id.eq(t_id))
+        .filter(used_at.is_null()))
+        .set(used_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(nb_updated == 1)
+}
+
+
+pub fn get_all_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<Vec<UserToken>> {
+    user_tokens.filter(user_id.eq(u_id))
+        .order(created_at.desc())
+        .load::<UserToken>(conn)
+}
@@ -0,0 +1,80 @@
+use crate::schema::user_totp::dsl::*;
+use crate::diesel::prelude::*;
+use crate::chrono::{NaiveDateTime, Utc};
+use crate::models::{forms::user_totp::UserTotpForm, ORMResult};
+use diesel::{PgConnection};
+use serde::{Serialize};
+
+#[derive(Serialize, Queryable)]
+pub struct UserTotp {
+    pub id: i32,
+    pub user_id: i32,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub enabled_at: Option<NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+impl UserTotp {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+}
+
+pub fn get_by_user_id(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<UserTotp> {
+    user_totp.filter(user_id.eq(u_id))
+        .get_result::<UserTotp>(conn)
+}
+
+/// Stores a new pending secret, replacing the one of an unfinished enrolment.
+pub fn create_pending(
+    form: UserTotpForm,
+    conn: &PgConnection
+) -> ORMResult<UserTotp> {
+    diesel::insert_into(user_totp)
+        .values(&form)
+        .on_conflict(user_id)
+        .do_update()
+        .set((
+            secret.eq(form.secret), 
+            enabled_at.eq(None::<NaiveDateTime>), 
+            last_used_step.eq(None::<i64>)))
+        .get_result::<UserTotp>(conn)
+}
+
+pub fn enable(
+    i_d: &i32,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    diesel::update(user_totp.filter(id.eq(i_d)))
+        .set(enabled_at.eq(Utc::now().naive_utc()))
+        .execute(conn)
+}
+
+/// Records the time step of an accepted code.
+/// Returns false when this step or a later one was already used, so that a code can't be replayed.
+pub fn use_step(
+    i_d: &i32,
+    step: i64,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    let nb_updated = diesel::update(user_totp
+        .filter(id.eq(i_d))
+        .filter(last_used_step.is_null().or(last_used_step.lt(step))))
+        .set(last_used_step.eq(step))
+        .execute(conn)?;
+
+    Ok(nb_updated == 1)
+}
+
+pub fn delete(
+    u_id: &i32,
+    conn: &PgConnection
+) -> ORMResult<usize> {
+    diesel::delete(user_totp.filter(user_id.eq(u_id)))
+        .execute(conn)
+}
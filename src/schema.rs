@@ -1,3 +1,45 @@
+table! {
+    use diesel::sql_types::*;
+
+    account_lockouts (lockout_key) {
+        lockout_key -> Varchar,
+        failures -> Int4,
+        locked_until -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::enums::*;
+
+    audit_events (id) {
+        id -> Int4,
+        user_id -> Nullable<Int4>,
+        event_type -> Enum_audit_event_types,
+        outcome -> Enum_audit_outcomes,
+        ip -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        details -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::enums::*;
+
+    bans (id) {
+        id -> Int4,
+        user_id -> Int4,
+        scope -> Enum_ban_scopes,
+        reason -> Text,
+        expires_at -> Nullable<Timestamp>,
+        issued_by -> Nullable<Int4>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::enums::*;
@@ -30,29 +72,200 @@ table! {
 
 table! {
     use diesel::sql_types::*;
+    use crate::enums::*;
+
+    email_outbox (id) {
+        id -> Int4,
+        user_id -> Int4,
+        recipient -> Varchar,
+        subject -> Text,
+        text_body -> Text,
+        html_body -> Text,
+        status -> Enum_email_statuses,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    flexmatch_events (id) {
+        id -> Int4,
+        event_id -> Varchar,
+        event_type -> Varchar,
+        processed_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::enums::*;
+
+    matchmaking_tickets (id) {
+        id -> Int4,
+        ticket_id -> Uuid,
+        configuration_name -> Varchar,
+        status -> Enum_matchmaking_statuses,
+        started_at -> Timestamp,
+        ended_at -> Nullable<Timestamp>,
+        is_backfill -> Bool,
+        game_session_arn -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::enums::*;
 
     users (id) {
         id -> Int4,
         email -> Varchar,
         nickname -> Varchar,
         hash -> Varchar,
-        reset_password_hash -> Nullable<Varchar>,
-        password_hash_expire_at -> Nullable<Timestamp>,
         created_at -> Timestamp,
-        steam_id -> Text,
+        steam_id -> Nullable<Text>,
         first_name -> Varchar,
         last_name -> Varchar,
         birth_date -> Timestamp,
         email_confirmation_required -> Bool,
+        password_pepper_id -> Varchar,
+        deletion_requested_at -> Nullable<Timestamp>,
+        role -> Enum_user_roles,
+        language -> Enum_languages,
+        token_version -> Int4,
+        pending_email -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    rate_limit_attempts (id) {
+        id -> Int4,
+        attempt_key -> Varchar,
+        attempted_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    refresh_tokens (id) {
+        id -> Int4,
+        token_id -> Uuid,
+        family_id -> Uuid,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    sessions (id) {
+        id -> Int4,
+        session_id -> Uuid,
+        session_key -> Varchar,
+        user_id -> Nullable<Int4>,
+        state -> Text,
+        ip -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_seen_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    steam_ban_records (id) {
+        id -> Int4,
+        user_id -> Int4,
+        steam_id -> Text,
+        vac_banned -> Bool,
+        publisher_banned -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    user_recovery_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::enums::*;
+
+    user_tokens (id) {
+        id -> Int4,
+        token_id -> Uuid,
+        user_id -> Int4,
+        purpose -> Enum_user_token_purposes,
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        request_ip -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    user_totp (id) {
+        id -> Int4,
+        user_id -> Int4,
+        secret -> Varchar,
+        enabled_at -> Nullable<Timestamp>,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamp,
     }
 }
 
+joinable!(audit_events -> users (user_id));
+joinable!(bans -> users (user_id));
 joinable!(custom_room_slots -> custom_rooms (custom_room_id));
 joinable!(custom_room_slots -> users (user_id));
 joinable!(custom_rooms -> users (user_id));
+joinable!(email_outbox -> users (user_id));
+joinable!(refresh_tokens -> users (user_id));
+joinable!(sessions -> users (user_id));
+joinable!(steam_ban_records -> users (user_id));
+joinable!(user_recovery_codes -> users (user_id));
+joinable!(user_tokens -> users (user_id));
+joinable!(user_totp -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    account_lockouts,
+    audit_events,
+    bans,
     custom_room_slots,
     custom_rooms,
+    email_outbox,
+    flexmatch_events,
+    matchmaking_tickets,
+    rate_limit_attempts,
+    refresh_tokens,
+    sessions,
+    steam_ban_records,
+    user_recovery_codes,
+    user_tokens,
+    user_totp,
     users,
 );
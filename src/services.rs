@@ -22,9 +22,20 @@ fn make_path_and_query(path: &str, params: &HashMap<&str, String>) -> String {
 pub mod email;
 pub mod websocket;
 pub mod custom_room;
+pub mod matchmaking;
 pub mod aws;
+pub mod sns;
 pub mod steam;
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod crypto;
+pub mod ban;
+pub mod rate_limit;
+pub mod session;
+pub mod token;
+pub mod totp;
+pub mod user;
 
 // Serialize and deserialize logic for dealing with nested values reprsented as
 // JSON strings.
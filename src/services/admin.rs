@@ -0,0 +1,136 @@
+use actix::Addr;
+use chrono::{Duration, Utc};
+use diesel::{OptionalExtension, PgConnection};
+use serde::Serialize;
+use crate::enums::UserRoles;
+use crate::errors::{AppError, AppResult};
+use crate::handlers::admin::{AuditEventsQuery, IssueBanData, SearchUsersQuery};
+use crate::models::audit_event::{AuditEvent, AuditEventFilter};
+use crate::models::ban::{self, Ban};
+use crate::models::steam_ban_record::{self, SteamBanRecord};
+use crate::models::user::{self, User};
+use crate::services::user::AccountDto;
+use crate::services::websocket::{LiveSessionCountMessage, WebsocketLobby};
+use crate::services::{audit as audit_service, ban as ban_service, totp as totp_service};
+
+pub const DEFAULT_SEARCH_LIMIT: i32 = 20;
+
+/// An account with its moderation history
+#[derive(Serialize)]
+pub struct AdminUserDto {
+    #[serde(flatten)]
+    pub account: AccountDto,
+    pub two_factor_enabled: bool,
+    pub bans: Vec<Ban>,
+    pub steam_ban_records: Vec<SteamBanRecord>,
+}
+
+#[derive(Serialize)]
+pub struct LiveSessionsDto {
+    pub connected_users: usize,
+}
+
+/// Fails unless the user has at least the role, returns the user.
+pub fn check_role(user_id: i32, role: UserRoles, conn: &PgConnection) -> AppResult<User> {
+    let user = user::get(&user_id, conn)?;
+    if user.role < role {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(user)
+}
+
+pub fn search_users(query: &SearchUsersQuery, conn: &PgConnection) -> AppResult<Vec<AccountDto>> {
+    let users = user::search(
+        query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()),
+        query.role,
+        query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT) as i64,
+        query.offset.unwrap_or(0) as i64,
+        conn)?;
+
+    Ok(users.into_iter().map(AccountDto::from).collect())
+}
+
+pub fn get_user(user_id: i32, conn: &PgConnection) -> AppResult<AdminUserDto> {
+    let user = user::get(&user_id, conn)?;
+
+    Ok(AdminUserDto {
+        two_factor_enabled: totp_service::is_enabled(user_id, conn)?,
+        bans: ban_service::get_all(user_id, conn)?,
+        steam_ban_records: steam_ban_record::get_all_by_user_id(&user_id, conn)?,
+        account: AccountDto::from(user),
+    })
+}
+
+/// Bans a user, staff members can only ban users with a lower role.
+pub fn issue_ban(
+    moderator: &User,
+    user_id: i32,
+    data: &IssueBanData,
+    ws: Addr<WebsocketLobby>,
+    conn: &PgConnection
+) -> AppResult<Ban> {
+    let user = user::get(&user_id, conn)?;
+    if user.role >= moderator.role {
+        return Err(AppError::Forbidden);
+    }
+
+    ban_service::issue(
+        user.id,
+        data.scope,
+        data.reason.trim(),
+        data.duration_hours.map(|hours| (Utc::now() + Duration::hours(hours as i64)).naive_utc()),
+        Some(moderator.id),
+        ws,
+        conn)
+}
+
+/// Lifts a ban, staff members can only revoke the bans of users with a lower role issued by
+/// someone without a higher role than theirs.
+pub fn revoke_ban(moderator: &User, ban_id: i32, conn: &PgConnection) -> AppResult<Ban> {
+    let ban = ban::get(&ban_id, conn)?;
+    if user::get(&ban.user_id, conn)?.role >= moderator.role {
+        return Err(AppError::Forbidden);
+    }
+    if let Some(issued_by) = ban.issued_by {
+        // the issuer may have been deleted since
+        if let Some(issuer) = user::get(&issued_by, conn).optional()? {
+            if issuer.role > moderator.role {
+                return Err(AppError::Forbidden);
+            }
+        }
+    }
+
+    ban_service::revoke(ban_id, conn)
+}
+
+/// Changes the role of a user, admins can't change their own so that one always remains.
+pub fn set_role(admin: &User, user_id: i32, role: UserRoles, conn: &PgConnection) -> AppResult<AccountDto> {
+    if admin.id == user_id {
+        return Err(AppError::BadRequest(String::from("You can't change your own role.")));
+    }
+
+    Ok(AccountDto::from(user::set_role(&user_id, role, conn)?))
+}
+
+pub fn search_audit_events(query: &AuditEventsQuery, conn: &PgConnection) -> AppResult<Vec<AuditEvent>> {
+    audit_service::search(
+        AuditEventFilter {
+            user_id: query.user_id,
+            event_type: query.event_type,
+            outcome: query.outcome,
+            ip: query.ip.as_deref().map(str::trim).filter(|ip| !ip.is_empty()),
+            from: query.from.map(|from| from.naive_utc()),
+            to: query.to.map(|to| to.naive_utc()),
+        },
+        query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT) as i64,
+        query.offset.unwrap_or(0) as i64,
+        conn)
+}
+
+pub async fn get_live_sessions(ws: Addr<WebsocketLobby>) -> AppResult<LiveSessionsDto> {
+    let connected_users = ws.send(LiveSessionCountMessage).await
+        .map_err(|err| AppError::InternalServerError(err.to_string()))?;
+
+    Ok(LiveSessionsDto { connected_users })
+}
@@ -0,0 +1,135 @@
+use actix_web::{web, HttpRequest};
+use chrono::{Duration, Utc};
+use diesel::PgConnection;
+use crate::enums::{AuditEventTypes, AuditOutcomes};
+use crate::errors::{AppError, AppResult};
+use crate::handlers::{get_request_ip, get_request_user_agent};
+use crate::models::audit_event::{self, AuditEvent, AuditEventFilter};
+use crate::models::forms::audit_event::AuditEventForm;
+use crate::models::user;
+use crate::Pool;
+
+const RECENT_ACTIVITY_LIMIT: i64 = 50;
+const UNATTRIBUTED_RETENTION_DAYS: i64 = 30;
+
+/// Client of a request, taken before the request data is moved into a blocking task
+#[derive(Clone)]
+pub struct AuditContext {
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl AuditContext {
+    pub fn from_request(request: &HttpRequest) -> Self {
+        AuditContext {
+            ip: get_request_ip(request),
+            user_agent: get_request_user_agent(request),
+        }
+    }
+}
+
+/// Failure reason of a result, `None` when it succeeded. Only the kind of error is kept, its message
+/// may hold what the user sent.
+pub fn failure_of<T>(result: &AppResult<T>) -> Option<String> {
+    result.as_ref().err().map(|err| reason_code(err).to_string())
+}
+
+fn reason_code(error: &AppError) -> &'static str {
+    match error {
+        AppError::ServiceUnavailable(_) => "service_unavailable",
+        AppError::InternalServerError(_) => "internal_error",
+        AppError::BadRequest(_) => "bad_request",
+        AppError::Unauthorized => "unauthorized",
+        AppError::Forbidden => "forbidden",
+        AppError::TooManyRequests(_) => "too_many_requests",
+        AppError::Validation(_) => "invalid_payload",
+        AppError::Conflict(_) => "conflict",
+        AppError::Banned(_) => "banned",
+        AppError::DeletionPending(_) => "deletion_pending",
+    }
+}
+
+fn t_record(
+    context: &AuditContext,
+    user_id: Option<i32>,
+    event_type: AuditEventTypes,
+    failure: Option<&str>,
+    conn: &PgConnection
+) {
+    let result = audit_event::create(AuditEventForm {
+        user_id,
+        event_type,
+        outcome: if failure.is_some() { AuditOutcomes::Failure } else { AuditOutcomes::Success },
+        ip: context.ip.as_deref(),
+        user_agent: context.user_agent.as_deref(),
+        details: failure,
+    }, conn);
+
+    if let Err(err) = result {
+        println!("Audit event {} of user {:?} was not recorded: {}", event_type, user_id, err);
+    }
+}
+
+/// Records an event, the request goes on if it fails.
+pub async fn record(
+    pool: &web::Data<Pool>,
+    context: &AuditContext,
+    user_id: Option<i32>,
+    event_type: AuditEventTypes,
+    failure: Option<String>
+) {
+    let (pool, context) = (pool.clone(), context.clone());
+    let _ = web::block(move ||
+        t_record(&context, user_id, event_type, failure.as_deref(), &pool.get().unwrap())).await;
+}
+
+/// Records an event of the account using the email, if any.
+/// Failed logins are seen by the owner of the account this way.
+pub async fn record_by_email(
+    pool: &web::Data<Pool>,
+    context: &AuditContext,
+    email: &str,
+    event_type: AuditEventTypes,
+    failure: Option<String>
+) {
+    let (pool, context, email) = (pool.clone(), context.clone(), email.to_string());
+    let _ = web::block(move || {
+        let conn = &pool.get().unwrap();
+        let user_id = user::get_by_email(&email, conn).ok().map(|user| user.id);
+        t_record(&context, user_id, event_type, failure.as_deref(), conn)
+    }).await;
+}
+
+/// Days the events without account are kept, `AUDIT_UNATTRIBUTED_RETENTION_DAYS`
+pub fn unattributed_retention() -> Duration {
+    Duration::days(std::env::var("AUDIT_UNATTRIBUTED_RETENTION_DAYS").ok()
+        .map(|days| days.parse::<i64>().expect("AUDIT_UNATTRIBUTED_RETENTION_DAYS must be a number of days"))
+        .unwrap_or(UNATTRIBUTED_RETENTION_DAYS))
+}
+
+/// Deletes the events without account past their retention: failed logins of unknown emails and the
+/// events of deleted accounts. Returns how many were deleted.
+pub fn purge_unattributed(conn: &PgConnection) -> AppResult<usize> {
+    Ok(audit_event::delete_unattributed_before(Utc::now().naive_utc() - unattributed_retention(), conn)?)
+}
+
+pub fn get_recent_activity(user_id: i32, conn: &PgConnection) -> AppResult<Vec<AuditEvent>> {
+    Ok(audit_event::get_recent_by_user_id(&user_id, RECENT_ACTIVITY_LIMIT, conn)?)
+}
+
+pub fn search(filter: AuditEventFilter, limit: i64, offset: i64, conn: &PgConnection) -> AppResult<Vec<AuditEvent>> {
+    Ok(audit_event::search(filter, limit, offset, conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_only_keep_the_kind_of_error() {
+        let result: AppResult<()> = Err(AppError::BadRequest(String::from("Unknown user player@spikegames.eu.")));
+        assert_eq!(failure_of(&result).as_deref(), Some("bad_request"));
+        assert_eq!(failure_of(&Err::<(), _>(AppError::Unauthorized)).as_deref(), Some("unauthorized"));
+        assert_eq!(failure_of(&Ok(())), None);
+    }
+}
@@ -1,97 +1,290 @@
-use argon2::Config;
-use diesel::PgConnection;
+use argon2::{Config, Variant};
+use diesel::{Connection, PgConnection};
+use diesel::result::Error as DBError;
 use rand::Rng;
 use crate::app_conf::SECRET_KEY;
 use crate::errors::{AppResult, AppError};
 use crate::app_conf::get_base_url;
-use crate::services::email::EmailService;
-use chrono::{Utc, NaiveDateTime};
-use crate::models::user::{self};
-use crate::services::steam;
+use crate::services::email::{outbox, template::{self, EmailTemplates}};
+use chrono::{Duration, Utc, NaiveDateTime};
+use crate::enums::UserTokenPurposes;
+use crate::models::user::{self, User};
+use crate::models::user_token;
+use crate::models::forms::user_token::UserTokenForm;
+use crate::services::{steam, ban as ban_service};
 use actix_web::web;
 use crate::Pool;
+use std::collections::HashMap;
+use uuid::Uuid;
 
-pub fn new_reset_password_hash() -> AppResult<String> {
-    let rng = rand::thread_rng().gen::<i64>().to_string();
-    hash_password(&rng)
+const SALT_LENGTH: usize = 16;
+const TOKEN_SECRET_LENGTH: usize = 32;
+const TOKEN_VALIDITY_HOURS: i64 = 4;
+const LOGIN_CHALLENGE_VALIDITY_MINUTES: i64 = 5;
+
+lazy_static::lazy_static! {
+    static ref PASSWORD_HASH_CONFIG: PasswordHashConfig = PasswordHashConfig::from_env();
+}
+
+/// Argon2 parameters of new password hashes. The server secret is used as a pepper,
+/// each pepper has an id stored with the hash so that it can be rotated.
+pub struct PasswordHashConfig {
+    pub variant: Variant,
+    pub mem_cost: u32,
+    pub time_cost: u32,
+    pub lanes: u32,
+    pub pepper_id: String,
+    peppers: HashMap<String, String>,
+}
+
+impl PasswordHashConfig {
+    pub fn from_env() -> Self {
+        let number = |name: &str, default: u32| std::env::var(name).ok()
+            .map(|value| value.parse::<u32>().unwrap_or_else(|_| panic!("{} must be a number", name)))
+            .unwrap_or(default);
+
+        // PASSWORD_PEPPERS=id:secret,id:secret keeps the previous peppers after a rotation,
+        // the pepper "0" is SECRET_KEY unless listed.
+        let mut peppers = HashMap::new();
+        peppers.insert(String::from("0"), SECRET_KEY.clone());
+        for pepper in std::env::var("PASSWORD_PEPPERS").unwrap_or_default().split(',') {
+            if let Some((pepper_id, secret)) = pepper.trim().split_once(':') {
+                peppers.insert(pepper_id.to_string(), secret.to_string());
+            }
+        }
+        let pepper_id = std::env::var("PASSWORD_PEPPER_ID").unwrap_or_else(|_| String::from("0"));
+        if !peppers.contains_key(&pepper_id) {
+            panic!("PASSWORD_PEPPER_ID {} is missing from PASSWORD_PEPPERS", pepper_id);
+        }
+
+        PasswordHashConfig {
+            variant: Variant::from_str(&std::env::var("PASSWORD_HASH_VARIANT")
+                .unwrap_or_else(|_| String::from("argon2id")))
+                .expect("PASSWORD_HASH_VARIANT must be argon2i, argon2d or argon2id"),
+            mem_cost: number("PASSWORD_HASH_MEMORY_KIB", 19456),
+            time_cost: number("PASSWORD_HASH_ITERATIONS", 2),
+            lanes: number("PASSWORD_HASH_LANES", 1),
+            pepper_id,
+            peppers,
+        }
+    }
+
+    fn get_pepper(&self, pepper_id: &str) -> AppResult<&str> {
+        self.peppers.get(pepper_id)
+            .map(|pepper| pepper.as_str())
+            .ok_or_else(|| AppError::InternalServerError(format!("Unknown password pepper: {}", pepper_id)))
+    }
+
+    /// Whether a hash was made with other parameters, another pepper or the legacy fixed salt.
+    fn is_outdated(&self, encoded: &str, pepper_id: &str) -> bool {
+        let params = format!("${}$v=19$m={},t={},p={}$", 
+            self.variant.as_lowercase_str(), 
+            self.mem_cost, 
+            self.time_cost, 
+            self.lanes);
+        let legacy_salt = format!("{}{}$", params, base64::encode_config(SECRET_KEY.as_bytes(), base64::STANDARD_NO_PAD));
+
+        pepper_id != self.pepper_id
+            || !encoded.starts_with(&params)
+            || encoded.starts_with(&legacy_salt)
+    }
 }
 
+/// Hashes with a random salt and the current pepper, see `current_pepper_id`.
 pub fn hash_password(to_hash: &str) -> AppResult<String> {
+    let hash_config = &*PASSWORD_HASH_CONFIG;
     let config = Config {
-        secret: SECRET_KEY.as_bytes(),
+        variant: hash_config.variant,
+        mem_cost: hash_config.mem_cost,
+        time_cost: hash_config.time_cost,
+        lanes: hash_config.lanes,
+        secret: hash_config.get_pepper(&hash_config.pepper_id)?.as_bytes(),
         ..Default::default()
     };
+    let salt = rand::thread_rng().gen::<[u8; SALT_LENGTH]>();
     
-    argon2::hash_encoded(to_hash.as_bytes(), SECRET_KEY.as_bytes(), &config)
+    argon2::hash_encoded(to_hash.as_bytes(), &salt, &config)
         .map_err(|err| {
         AppError::InternalServerError(err.to_string())
     })
 }
 
-pub fn check_reset_password_hash(hash: &str, conn: &PgConnection) -> AppResult<()> {
-    let expired_error = Err(AppError::BadRequest(String::from("The link you used has expired. Make a new request.")));
+pub fn current_pepper_id() -> &'static str {
+    &PASSWORD_HASH_CONFIG.pepper_id
+}
 
-    let user = user::get_by_reset_password_hash(hash, conn)?;
-    if let Some(expire_date) = user.password_hash_expire_at {
-        let now = NaiveDateTime::from_timestamp_opt(Utc::now().timestamp(), 0).unwrap();
-        if expire_date >= now {
-            Ok(())
-        } else {
-            if let Err(err) = user::cancel_reset_password_hash(&hash, conn) {
-                return Err(AppError::BadRequest(err.to_string()));
-            }
-            return expired_error;
-        }
-    } else {
-        return expired_error;
+pub fn verify_password(encoded: &str, pepper_id: &str, password: &str) -> AppResult<bool> {
+    let pepper = PASSWORD_HASH_CONFIG.get_pepper(pepper_id)?;
+
+    argon2::verify_encoded_ext(encoded, password.as_bytes(), pepper.as_bytes(), &[])
+        .map_err(|err| {
+            dbg!(err);
+            AppError::Unauthorized
+        })
+}
+
+pub fn needs_rehash(encoded: &str, pepper_id: &str) -> bool {
+    PASSWORD_HASH_CONFIG.is_outdated(encoded, pepper_id)
+}
+
+/// Creates a single-use token of the form `<token id>.<secret>` to send by email or to hand back
+/// as a login challenge, only a hash of the secret is stored. Returns the token and its expiration timestamp.
+pub fn new_user_token(
+    user_id: i32, 
+    purpose: UserTokenPurposes, 
+    request_ip: Option<&str>, 
+    conn: &PgConnection
+) -> AppResult<(String, i64)> {
+    let token_id = Uuid::new_v4();
+    let secret = base64::encode_config(
+        rand::thread_rng().gen::<[u8; TOKEN_SECRET_LENGTH]>(), 
+        base64::URL_SAFE_NO_PAD);
+    let expires_at = Utc::now() + get_token_validity(purpose);
+
+    user_token::create(UserTokenForm {
+        token_id: &token_id,
+        user_id,
+        purpose,
+        token_hash: &hash_token_secret(&secret),
+        expires_at: expires_at.naive_utc(),
+        request_ip,
+    }, conn)?;
+
+    Ok((format!("{}.{}", token_id.to_simple(), secret), expires_at.timestamp()))
+}
+
+/// Checks and consumes a token created by `new_user_token`, returns the id of its user.
+pub fn use_user_token(token: &str, purpose: UserTokenPurposes, conn: &PgConnection) -> AppResult<i32> {
+    let invalid_error = Err(AppError::BadRequest(String::from("The link you used is invalid. Make a new request.")));
+
+    let (user_token, secret) = match token.split_once('.') {
+        Some((token_id, secret)) => match Uuid::parse_str(token_id) {
+            Ok(token_id) => (user_token::get_by_token_id(&token_id, conn), secret),
+            Err(_) => return invalid_error
+        },
+        // links sent before the tokens are their own secret, see the user_tokens migration
+        None => (user_token::get_unused_by_token_hash(&hash_token_secret(token), &purpose, conn), token)
+    };
+    let user_token = match user_token {
+        Ok(user_token) => user_token,
+        Err(DBError::NotFound) => return invalid_error,
+        Err(err) => return Err(AppError::InternalServerError(err.to_string()))
+    };
+
+    let secret_hash = hash_token_secret(secret);
+    if user_token.purpose != purpose 
+        || secret_hash.len() != user_token.token_hash.len()
+        || !openssl::memcmp::eq(secret_hash.as_bytes(), user_token.token_hash.as_bytes())
+        || user_token.used_at.is_some() {
+        return invalid_error
+    }
+    if user_token.is_expired() {
+        return Err(AppError::BadRequest(String::from("The link you used has expired. Make a new request.")));
+    }
+    if !user_token::mark_used(&user_token.id, conn)? {
+        return invalid_error
     }
+
+    Ok(user_token.user_id)
 }
 
-pub async fn send_confirmation_email(email: &str, expire_timestamp: i64, hash: &str) -> AppResult<()> {
-    let url = format!("{}/static/email_confirmation.html?id={}", get_base_url(), hash);
-    let expire_time = NaiveDateTime::from_timestamp_opt(
-        expire_timestamp, 0).unwrap().format("%c");
-    let studio_logo_url = format!("{}/static/assets/images/logo_studio.png", 
-        get_base_url());
-    let link = format!("<p>Hello, </p><p>Welcome to rigidity!</p><p>Please click on the following link to confirm you email address: <a href='{}'>confirm link</a></p><p>Your link we'll expire at {} (UTC time)</p></br></br><img src='{}'>", url, expire_time, studio_logo_url);
+fn get_token_validity(purpose: UserTokenPurposes) -> Duration {
+    match purpose {
+        UserTokenPurposes::LoginChallenge => Duration::minutes(LOGIN_CHALLENGE_VALIDITY_MINUTES),
+        _ => Duration::hours(TOKEN_VALIDITY_HOURS)
+    }
+}
 
-    let email_service = EmailService::new(
-        email,
-        String::from("Rigidity email confirmation"),
-        link
-    );
-    
-    email_service.send().await?;
-    Ok(())
+fn hash_token_secret(secret: &str) -> String {
+    base64::encode(openssl::sha::sha256(secret.as_bytes()))
+}
+
+fn format_expire_time(expire_timestamp: i64) -> String {
+    NaiveDateTime::from_timestamp_opt(expire_timestamp, 0).unwrap()
+        .format("%Y-%m-%d %H:%M UTC")
+        .to_string()
+}
+
+/// Queues the email confirmation of the user, in the transaction creating the token.
+pub fn queue_confirmation_email(
+    user: &User, token: &str, expire_timestamp: i64, conn: &PgConnection) -> AppResult<()> {
+    let url = format!("{}/static/email_confirmation.html?id={}", get_base_url(), token);
+    let content = template::render(
+        EmailTemplates::EmailConfirmation,
+        user.language,
+        &[("url", &url), ("expire_time", &format_expire_time(expire_timestamp))]);
+
+    outbox::queue(user, &content, conn)
+}
+
+/// Queues the confirmation of the new address of an email change and a notice to the current one,
+/// in the transaction creating the token.
+pub fn queue_email_change_emails(
+    user: &User, new_email: &str, token: &str, expire_timestamp: i64, conn: &PgConnection) -> AppResult<()> {
+    let url = format!("{}/static/email_confirmation.html?id={}", get_base_url(), token);
+    let confirmation = template::render(
+        EmailTemplates::EmailChange,
+        user.language,
+        &[("url", &url), ("expire_time", &format_expire_time(expire_timestamp))]);
+    outbox::queue_to(user, new_email, &confirmation, conn)?;
+
+    let reset_url = format!("{}/static/ask_password_reset.html", get_base_url());
+    let notice = template::render(
+        EmailTemplates::EmailChangeNotice,
+        user.language,
+        &[("new_email", new_email), ("reset_url", &reset_url)]);
+    outbox::queue(user, &notice, conn)
+}
+
+/// Queues the password reset email of the user, in the transaction creating the token.
+pub fn queue_password_reset_email(
+    user: &User, token: &str, expire_timestamp: i64, conn: &PgConnection) -> AppResult<()> {
+    let url = format!("{}/static/reset_password.html?id={}", get_base_url(), token);
+    let content = template::render(
+        EmailTemplates::PasswordReset,
+        user.language,
+        &[("url", &url), ("expire_time", &format_expire_time(expire_timestamp))]);
+
+    outbox::queue(user, &content, conn)
 }
 
-pub fn email_confirmation(hash: &str, conn: &PgConnection) -> AppResult<()> {
-    check_reset_password_hash(hash, conn)?;
-    user::confirm_email(hash, conn)?;
+/// Returns the id of the user whose email is confirmed, the pending address of an email change
+/// replaces the current one.
+pub fn email_confirmation(token: &str, conn: &PgConnection) -> AppResult<i32> {
+    conn.transaction(|| {
+        let user_id = use_user_token(token, UserTokenPurposes::EmailConfirmation, conn)?;
+        match user::get(&user_id, conn)?.pending_email {
+            // the address may have been taken since the request, the unique index answers a conflict
+            Some(pending_email) => user::apply_pending_email(&user_id, &pending_email, conn)?,
+            None => user::confirm_email(&user_id, conn)?
+        }
 
-    Ok(())
+        Ok(user_id)
+    })
 }
 
 pub async fn update_email_confirmation(
-    email: String, steam_id: u64, pool: web::Data<Pool>) -> AppResult<()> {
-    let c_email = email.clone();
-    let (expire_time_stamp, email_confirmation_hash) = web::block(move || 
-        t_update_email_confirmation(email, steam_id, pool)).await??;
-    send_confirmation_email(&c_email, expire_time_stamp, &email_confirmation_hash).await?;
-    
-    Ok(())
+    email: String, steam_id: u64, request_ip: Option<String>, pool: web::Data<Pool>) -> AppResult<()> {
+    web::block(move || 
+        t_update_email_confirmation(email, steam_id, request_ip, pool)).await?
 }
 
 pub fn t_update_email_confirmation(
-    email: String, steam_id: u64, pool: web::Data<Pool>) -> AppResult<(i64, String)> {
+    email: String, steam_id: u64, request_ip: Option<String>, pool: web::Data<Pool>) -> AppResult<()> {
     let conn = &pool.get().unwrap(); 
     let user = user::get_by_steam_id(&steam_id.to_string(), conn)?;
 
     if user.email_confirmation_required {
-        let email_confirmation_hash = new_reset_password_hash()?;
-        let (_user, expire_time_stamp) = user::update_email(
-            &email, &steam_id, &email_confirmation_hash, conn)?;
-            Ok((expire_time_stamp, email_confirmation_hash))
+        conn.transaction(|| {
+            let user = user::update_email(&email, &steam_id, conn)?;
+            let (token, expire_timestamp) = new_user_token(
+                user.id, 
+                UserTokenPurposes::EmailConfirmation, 
+                request_ip.as_deref(), 
+                conn)?;
+
+            queue_confirmation_email(&user, &token, expire_timestamp, conn)
+        })
     } else {
         return Err(AppError::Forbidden);
     }
@@ -99,8 +292,55 @@ pub fn t_update_email_confirmation(
 
 pub async fn steam_authenticate_and_ownership_check(
     data: &steam::SteamAuthData) -> AppResult<u64> {
-    let steam_id = steam::authenticate_user_ticket(data).await?;
-    steam::check_app_ownership(&data.app_id, &steam_id).await?; 
+    let ticket = steam::authenticate_user_ticket(data).await?;
+    ban_service::check_steam(&ticket)?;
+    steam::check_app_ownership(&data.app_id, &ticket.steam_id).await?; 
 
-    Ok(steam_id)
-}
\ No newline at end of file
+    Ok(ticket.steam_id)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "spike" hashed before the random salts, with `SECRET_KEY` as salt and secret
+    const LEGACY_HASH: &str = "$argon2i$v=19$m=4096,t=3,p=1$MDEyMzAxMjMwMTIzMDEyMzAxMjMwMTIzMDEyMzAxMjMwMTIzMDEyMzAxMjMwMTIzMDEyMzAxMjMwMTIzMDEyMw$jhm7eD03qd7LhoZbEfrFmIesOYi3EW18mtpxoRD+GTI";
+
+    fn legacy_parameters() -> PasswordHashConfig {
+        let mut peppers = HashMap::new();
+        peppers.insert(String::from("0"), SECRET_KEY.clone());
+        PasswordHashConfig {
+            variant: Variant::Argon2i,
+            mem_cost: 4096,
+            time_cost: 3,
+            lanes: 1,
+            pepper_id: String::from("0"),
+            peppers,
+        }
+    }
+
+    #[test]
+    fn legacy_fixed_salt_is_outdated() {
+        let hash_config = legacy_parameters();
+
+        assert!(verify_password(LEGACY_HASH, "0", "spike").unwrap());
+        assert!(hash_config.is_outdated(LEGACY_HASH, "0"));
+    }
+
+    #[test]
+    fn random_salt_with_current_parameters_is_up_to_date() {
+        let hash_config = legacy_parameters();
+        let config = Config {
+            variant: hash_config.variant,
+            mem_cost: hash_config.mem_cost,
+            time_cost: hash_config.time_cost,
+            lanes: hash_config.lanes,
+            secret: SECRET_KEY.as_bytes(),
+            ..Default::default()
+        };
+        let encoded = argon2::hash_encoded(b"spike", &[7; SALT_LENGTH], &config).unwrap();
+
+        assert!(!hash_config.is_outdated(&encoded, "0"));
+        assert!(hash_config.is_outdated(&encoded, "1"));
+        assert!(PasswordHashConfig { time_cost: 2, ..legacy_parameters() }.is_outdated(&encoded, "0"));
+    }
+}
@@ -1,9 +1,62 @@
-use rusoto_gamelift::GameLiftClient;
+use rusoto_gamelift::{
+    GameLift,
+    GameLiftClient, 
+    MatchmakingTicket as GameLiftTicket,
+    StartMatchmakingInput,
+    StartMatchmakingOutput,
+    StopMatchmakingInput,
+    DescribeMatchmakingInput,
+    DescribeMatchmakingOutput,
+    StartMatchBackfillInput,
+    StartMatchBackfillOutput};
 use rusoto_core::credential::{EnvironmentProvider};
 use rusoto_core::request::HttpClient;
 use rusoto_core::region::Region;
 use serde::Deserialize;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::Arc;
+use async_trait::async_trait;
+use actix::Addr;
+use crate::enums::MatchmakingStatuses;
+use crate::errors::{AppError, AppResult};
+use crate::services::websocket::WebsocketLobby;
+use crate::Pool;
+
+pub mod simulator;
+
+/// GameLift matchmaking calls used by the server, implemented by the AWS client
+/// and by the local simulator.
+#[async_trait]
+pub trait GameLiftBackend: Send + Sync {
+    async fn start_matchmaking(&self, input: StartMatchmakingInput) -> AppResult<StartMatchmakingOutput>;
+    async fn stop_matchmaking(&self, input: StopMatchmakingInput) -> AppResult<()>;
+    async fn describe_matchmaking(&self, input: DescribeMatchmakingInput) -> AppResult<DescribeMatchmakingOutput>;
+    async fn start_match_backfill(&self, input: StartMatchBackfillInput) -> AppResult<StartMatchBackfillOutput>;
+}
+
+#[async_trait]
+impl GameLiftBackend for GameLiftClient {
+    async fn start_matchmaking(&self, input: StartMatchmakingInput) -> AppResult<StartMatchmakingOutput> {
+        GameLift::start_matchmaking(self, input).await
+            .map_err(|err| AppError::BadRequest(err.to_string()))
+    }
+
+    async fn stop_matchmaking(&self, input: StopMatchmakingInput) -> AppResult<()> {
+        GameLift::stop_matchmaking(self, input).await
+            .map(|_| ())
+            .map_err(|err| AppError::BadRequest(err.to_string()))
+    }
+
+    async fn describe_matchmaking(&self, input: DescribeMatchmakingInput) -> AppResult<DescribeMatchmakingOutput> {
+        GameLift::describe_matchmaking(self, input).await
+            .map_err(|err| AppError::BadRequest(err.to_string()))
+    }
+
+    async fn start_match_backfill(&self, input: StartMatchBackfillInput) -> AppResult<StartMatchBackfillOutput> {
+        GameLift::start_match_backfill(self, input).await
+            .map_err(|err| AppError::BadRequest(err.to_string()))
+    }
+}
 
 pub async fn get_gamelift_client() -> GameLiftClient {
     let cred = EnvironmentProvider::default();
@@ -11,6 +64,19 @@ pub async fn get_gamelift_client() -> GameLiftClient {
     GameLiftClient::new_with(client, cred, Region::EuWest1)
 }
 
+/// GameLift implementation selected by `GAMELIFT_BACKEND`: `aws` (default) or `simulator`.
+pub async fn get_gamelift_backend(
+    ws: Addr<WebsocketLobby>, 
+    pool: Pool
+) -> Arc<dyn GameLiftBackend> {
+    match std::env::var("GAMELIFT_BACKEND").unwrap_or_default().as_str() {
+        "simulator" => Arc::new(simulator::GameLiftSimulator::new(
+            simulator::SimulatorConfig::from_env(), ws, pool)),
+        "" | "aws" => Arc::new(get_gamelift_client().await),
+        backend => panic!("Unknown GAMELIFT_BACKEND: {}", backend)
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub enum FlexMatchEvents {
     MatchmakingSearching,
@@ -23,6 +89,33 @@ pub enum FlexMatchEvents {
     MatchmakingFailed,
 }
 
+impl FlexMatchEvents {
+    /// Event matching a ticket status returned by DescribeMatchmaking
+    pub fn from_aws_status(value: &str) -> Option<FlexMatchEvents> {
+        match value {
+            "SEARCHING" => Some(FlexMatchEvents::MatchmakingSearching),
+            "REQUIRES_ACCEPTANCE" => Some(FlexMatchEvents::AcceptMatch),
+            "PLACING" => Some(FlexMatchEvents::PotentialMatchCreated),
+            "COMPLETED" => Some(FlexMatchEvents::MatchmakingSucceeded),
+            "CANCELLED" => Some(FlexMatchEvents::MatchmakingCancelled),
+            "TIMED_OUT" => Some(FlexMatchEvents::MatchmakingTimedOut),
+            "FAILED" => Some(FlexMatchEvents::MatchmakingFailed),
+            _ => None,
+        }
+    }
+
+    pub fn to_matchmaking_status(&self) -> Option<MatchmakingStatuses> {
+        match self {
+            FlexMatchEvents::MatchmakingSearching => Some(MatchmakingStatuses::Searching),
+            FlexMatchEvents::MatchmakingSucceeded => Some(MatchmakingStatuses::Succeeded),
+            FlexMatchEvents::MatchmakingTimedOut => Some(MatchmakingStatuses::TimedOut),
+            FlexMatchEvents::MatchmakingCancelled => Some(MatchmakingStatuses::Cancelled),
+            FlexMatchEvents::MatchmakingFailed => Some(MatchmakingStatuses::Failed),
+            _ => None,
+        }
+    }
+}
+
 impl Display for FlexMatchEvents {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         write!(f, "{:?}", self)
@@ -39,6 +132,46 @@ pub struct FlexMatchData<T>
     pub detail: T
 }
 
+impl FlexMatchData<FlexMatchSucceededDetail> {
+    /// Builds the event SNS would have sent for a ticket returned by DescribeMatchmaking.
+    pub fn new_from_completed_ticket(ticket: &GameLiftTicket) -> Option<Self> {
+        let ticket_id = ticket.ticket_id.clone()?;
+        let connection_info = ticket.game_session_connection_info.as_ref()?;
+        let mut players = Vec::new();
+        for player in connection_info.matched_player_sessions.as_ref()? {
+            players.push(FlexMatchPlayerGameSession {
+                player_id: player.player_id.clone()?,
+                player_session_id: player.player_session_id.clone()?,
+            });
+        }
+
+        Some(FlexMatchData {
+            id: format!("describe-matchmaking-{}", ticket_id),
+            account: String::new(),
+            region: String::new(),
+            resources: ticket.configuration_arn.iter().cloned().collect(),
+            detail: FlexMatchSucceededDetail {
+                tickets: vec![FlexMatchTicket { 
+                    ticket_id,
+                    players: ticket.players.iter().flatten()
+                        .filter_map(|player| Some(FlexMatchTicketPlayer {
+                            player_id: player.player_id.clone()?,
+                            team: player.team.clone(),
+                        }))
+                        .collect()
+                }],
+                e_type: FlexMatchEvents::MatchmakingSucceeded,
+                match_id: connection_info.game_session_arn.clone().unwrap_or_default(),
+                game_session_info: FlexMatchGameSession {
+                    ip_address: connection_info.ip_address.clone()?,
+                    port: connection_info.port? as i32,
+                    players,
+                }
+            }
+        })
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct FlexMatchDetail {
     pub tickets: Vec<FlexMatchTicket>,
@@ -85,5 +218,23 @@ pub struct FlexMatchPlayerGameSession {
 #[derive(Deserialize, Debug)]
 pub struct FlexMatchTicket {
     #[serde(rename = "ticketId")]
-    pub ticket_id: String
+    pub ticket_id: String,
+    #[serde(default)]
+    pub players: Vec<FlexMatchTicketPlayer>
+}
+
+impl FlexMatchTicket {
+    /// User ids of the players of the ticket, player ids not issued by this server are skipped.
+    pub fn get_player_ids(&self) -> Vec<i32> {
+        self.players.iter()
+            .filter_map(|player| player.player_id.parse::<i32>().ok())
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FlexMatchTicketPlayer {
+    #[serde(rename = "playerId")]
+    pub player_id: String,
+    pub team: Option<String>
 }
\ No newline at end of file
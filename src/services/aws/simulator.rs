@@ -0,0 +1,591 @@
+use actix::Addr;
+use async_trait::async_trait;
+use chrono::Utc;
+use rusoto_gamelift::{
+    GameSessionConnectionInfo,
+    MatchedPlayerSession,
+    MatchmakingTicket as GameLiftTicket,
+    Player,
+    StartMatchmakingInput,
+    StartMatchmakingOutput,
+    StopMatchmakingInput,
+    DescribeMatchmakingInput,
+    DescribeMatchmakingOutput,
+    StartMatchBackfillInput,
+    StartMatchBackfillOutput};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+use crate::errors::{AppError, AppResult};
+use crate::services::matchmaking::handle_flexmatch_event;
+use crate::services::websocket::WebsocketLobby;
+use crate::Pool;
+use super::{FlexMatchEvents, GameLiftBackend};
+
+const SEARCH_DELAY: Duration = Duration::from_secs(1);
+
+pub struct SimulatorConfig {
+    /// Number of players of each team, a match is formed when the tickets of a configuration
+    /// fill every team, the players of a ticket staying together. Each ticket is a match on its own when empty.
+    pub team_sizes: Vec<usize>,
+    pub match_delay: Duration,
+    pub timeout: Duration,
+    pub ip_address: String,
+    pub port: i64,
+}
+
+impl SimulatorConfig {
+    pub fn from_env() -> Self {
+        let team_sizes = std::env::var("GAMELIFT_SIMULATOR_TEAM_SIZES").unwrap_or_default()
+            .split(',')
+            .filter(|size| !size.trim().is_empty())
+            .map(|size| size.trim().parse::<usize>()
+                .expect("GAMELIFT_SIMULATOR_TEAM_SIZES must be a comma separated list of numbers"))
+            .collect();
+        let secs = |name: &str, default: u64| std::env::var(name).ok()
+            .map(|value| value.parse::<u64>().unwrap_or_else(|_| panic!("{} must be a number of seconds", name)))
+            .unwrap_or(default);
+
+        SimulatorConfig {
+            team_sizes,
+            match_delay: Duration::from_secs(secs("GAMELIFT_SIMULATOR_MATCH_DELAY_SECS", 3)),
+            timeout: Duration::from_secs(secs("GAMELIFT_SIMULATOR_TIMEOUT_SECS", 120)),
+            ip_address: std::env::var("GAMELIFT_SIMULATOR_IP_ADDRESS")
+                .unwrap_or_else(|_| String::from("127.0.0.1")),
+            port: std::env::var("GAMELIFT_SIMULATOR_PORT").ok()
+                .and_then(|port| port.parse::<i64>().ok())
+                .unwrap_or(7777),
+        }
+    }
+}
+
+struct SimulatedTicket {
+    ticket_id: String,
+    configuration_name: String,
+    players: Vec<Player>,
+    status: &'static str,
+    start_time: f64,
+    end_time: Option<f64>,
+    connection_info: Option<GameSessionConnectionInfo>,
+    /// Game session to fill, set for backfill tickets
+    game_session_arn: Option<String>,
+}
+
+impl SimulatedTicket {
+    fn to_gamelift_ticket(&self) -> GameLiftTicket {
+        GameLiftTicket {
+            configuration_name: Some(self.configuration_name.clone()),
+            start_time: Some(self.start_time),
+            end_time: self.end_time,
+            game_session_connection_info: self.connection_info.clone(),
+            players: Some(self.players.clone()),
+            status: Some(self.status.to_string()),
+            ticket_id: Some(self.ticket_id.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn end(&mut self, status: &'static str) {
+        self.status = status;
+        self.end_time = Some(now());
+    }
+}
+
+struct SimulatorState {
+    config: SimulatorConfig,
+    tickets: Mutex<HashMap<String, SimulatedTicket>>,
+    /// Game sessions created by the simulator, by ARN
+    game_sessions: Mutex<HashMap<String, GameSessionConnectionInfo>>,
+    ws: Addr<WebsocketLobby>,
+    pool: Pool,
+}
+
+/// In-process stand-in for GameLift FlexMatch used for offline development.
+/// Tickets are matched after a delay and the FlexMatch events are processed
+/// as if they were received from SNS.
+pub struct GameLiftSimulator {
+    state: Arc<SimulatorState>,
+}
+
+impl GameLiftSimulator {
+    pub fn new(config: SimulatorConfig, ws: Addr<WebsocketLobby>, pool: Pool) -> Self {
+        GameLiftSimulator {
+            state: Arc::new(SimulatorState {
+                config,
+                tickets: Mutex::new(HashMap::new()),
+                game_sessions: Mutex::new(HashMap::new()),
+                ws,
+                pool,
+            })
+        }
+    }
+}
+
+#[async_trait]
+impl GameLiftBackend for GameLiftSimulator {
+    async fn start_matchmaking(&self, input: StartMatchmakingInput) -> AppResult<StartMatchmakingOutput> {
+        let ticket = SimulatedTicket {
+            ticket_id: input.ticket_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            configuration_name: input.configuration_name,
+            players: input.players,
+            status: "QUEUED",
+            start_time: now(),
+            end_time: None,
+            connection_info: None,
+            game_session_arn: None,
+        };
+        let output = StartMatchmakingOutput {
+            matchmaking_ticket: Some(ticket.to_gamelift_ticket())
+        };
+        let ticket_id = ticket.ticket_id.clone();
+        self.state.tickets.lock().unwrap().insert(ticket_id.clone(), ticket);
+
+        schedule_search(self.state.clone(), ticket_id);
+
+        Ok(output)
+    }
+
+    async fn stop_matchmaking(&self, input: StopMatchmakingInput) -> AppResult<()> {
+        let event = {
+            let mut tickets = self.state.tickets.lock().unwrap();
+            match tickets.get_mut(&input.ticket_id) {
+                Some(ticket) if ticket.status == "QUEUED" || ticket.status == "SEARCHING" => {
+                    ticket.end("CANCELLED");
+                    new_event(FlexMatchEvents::MatchmakingCancelled, &[&*ticket], None)
+                },
+                Some(_) => return Err(AppError::BadRequest(String::from("Matchmaking ticket can't be cancelled anymore."))),
+                None => return Err(AppError::BadRequest(format!("Unknown matchmaking ticket: {}", input.ticket_id)))
+            }
+        };
+
+        emit_later(self.state.clone(), Duration::from_secs(0), event);
+        Ok(())
+    }
+
+    async fn describe_matchmaking(&self, input: DescribeMatchmakingInput) -> AppResult<DescribeMatchmakingOutput> {
+        let tickets = self.state.tickets.lock().unwrap();
+        let ticket_list = input.ticket_ids.iter()
+            .filter_map(|ticket_id| tickets.get(ticket_id))
+            .map(|ticket| ticket.to_gamelift_ticket())
+            .collect();
+
+        Ok(DescribeMatchmakingOutput {
+            ticket_list: Some(ticket_list)
+        })
+    }
+
+    async fn start_match_backfill(&self, input: StartMatchBackfillInput) -> AppResult<StartMatchBackfillOutput> {
+        let game_session_arn = match input.game_session_arn {
+            Some(arn) if self.state.game_sessions.lock().unwrap().contains_key(&arn) => arn,
+            Some(arn) => return Err(AppError::BadRequest(format!("Unknown game session: {}", arn))),
+            None => return Err(AppError::BadRequest(String::from("Missing game session ARN.")))
+        };
+        let ticket = SimulatedTicket {
+            ticket_id: input.ticket_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            configuration_name: input.configuration_name,
+            players: input.players,
+            status: "QUEUED",
+            start_time: now(),
+            end_time: None,
+            connection_info: None,
+            game_session_arn: Some(game_session_arn),
+        };
+        let output = StartMatchBackfillOutput {
+            matchmaking_ticket: Some(ticket.to_gamelift_ticket())
+        };
+        let ticket_id = ticket.ticket_id.clone();
+        self.state.tickets.lock().unwrap().insert(ticket_id.clone(), ticket);
+
+        schedule_search(self.state.clone(), ticket_id);
+
+        Ok(output)
+    }
+}
+
+fn now() -> f64 {
+    Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+fn schedule_search(state: Arc<SimulatorState>, ticket_id: String) {
+    actix::spawn(async move {
+        actix::clock::sleep(SEARCH_DELAY).await;
+
+        let event = {
+            let mut tickets = state.tickets.lock().unwrap();
+            match tickets.get_mut(&ticket_id) {
+                Some(ticket) if ticket.status == "QUEUED" => {
+                    ticket.status = "SEARCHING";
+                    new_event(FlexMatchEvents::MatchmakingSearching, &[&*ticket], None)
+                },
+                _ => return
+            }
+        };
+        emit(&state, event).await;
+
+        form_match(state.clone());
+        schedule_timeout(state, ticket_id);
+    });
+}
+
+fn schedule_timeout(state: Arc<SimulatorState>, ticket_id: String) {
+    actix::spawn(async move {
+        actix::clock::sleep(state.config.timeout).await;
+
+        let event = {
+            let mut tickets = state.tickets.lock().unwrap();
+            match tickets.get_mut(&ticket_id) {
+                Some(ticket) if ticket.status == "SEARCHING" => {
+                    ticket.end("TIMED_OUT");
+                    new_event(FlexMatchEvents::MatchmakingTimedOut, &[&*ticket], None)
+                },
+                _ => return
+            }
+        };
+        emit(&state, event).await;
+    });
+}
+
+/// Forms every match the searching tickets allow, backfill tickets being served first,
+/// and emits their succeeded events after the match delay.
+fn form_match(state: Arc<SimulatorState>) {
+    let events = {
+        let mut tickets = state.tickets.lock().unwrap();
+        let mut game_sessions = state.game_sessions.lock().unwrap();
+        form_matches(&state.config, &mut tickets, &mut game_sessions)
+    };
+
+    for event in events {
+        emit_later(state.clone(), state.config.match_delay, event);
+    }
+}
+
+fn form_matches(
+    config: &SimulatorConfig,
+    tickets: &mut HashMap<String, SimulatedTicket>,
+    game_sessions: &mut HashMap<String, GameSessionConnectionInfo>
+) -> Vec<String> {
+    let mut events = Vec::new();
+    while let Some(event) = form_backfill_match(tickets, game_sessions) {
+        events.push(event);
+    }
+    while let Some(event) = form_new_match(config, tickets, game_sessions) {
+        events.push(event);
+    }
+
+    events
+}
+
+/// Gathers the oldest searching tickets of a configuration until every team is full.
+fn form_new_match(
+    config: &SimulatorConfig,
+    tickets: &mut HashMap<String, SimulatedTicket>,
+    game_sessions: &mut HashMap<String, GameSessionConnectionInfo>
+) -> Option<String> {
+    let mut searching: Vec<&SimulatedTicket> = tickets.values()
+        .filter(|ticket| ticket.status == "SEARCHING" && ticket.game_session_arn.is_none())
+        .collect();
+    searching.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
+
+    let mut configurations: Vec<&str> = Vec::new();
+    for ticket in &searching {
+        if !configurations.contains(&ticket.configuration_name.as_str()) {
+            configurations.push(&ticket.configuration_name);
+        }
+    }
+
+    let assignment = configurations.iter().find_map(|configuration_name| {
+        let candidates: Vec<&SimulatedTicket> = searching.iter()
+            .filter(|ticket| ticket.configuration_name == *configuration_name)
+            .copied()
+            .collect();
+        assign_teams(&config.team_sizes, &candidates)
+    })?;
+
+    for (ticket_id, team) in &assignment {
+        if let Some(ticket) = tickets.get_mut(ticket_id) {
+            for player in ticket.players.iter_mut() {
+                player.team = Some(team.to_string());
+            }
+        }
+    }
+    let matched_ids: Vec<String> = assignment.into_iter()
+        .map(|(ticket_id, _)| ticket_id)
+        .collect();
+
+    let connection_info = GameSessionConnectionInfo {
+        game_session_arn: Some(format!("arn:aws:gamelift:local::gamesession/simulator/{}", Uuid::new_v4())),
+        ip_address: Some(config.ip_address.clone()),
+        port: Some(config.port),
+        matched_player_sessions: Some(matched_ids.iter()
+            .filter_map(|ticket_id| tickets.get(ticket_id))
+            .flat_map(|ticket| ticket.players.iter())
+            .map(|player| MatchedPlayerSession {
+                player_id: player.player_id.clone(),
+                player_session_id: Some(format!("psess-{}", Uuid::new_v4())),
+            })
+            .collect()),
+        ..Default::default()
+    };
+
+    let game_session_arn = connection_info.game_session_arn.clone().unwrap();
+    println!("GameLift simulator created game session {}", game_session_arn);
+    game_sessions.insert(game_session_arn, connection_info.clone());
+
+    Some(complete_tickets(tickets, &matched_ids, &connection_info))
+}
+
+/// Team of each ticket of a match, `tickets` being the searching tickets of a configuration
+/// from the oldest. A ticket joins the first team with room for all its players and the match
+/// is formed once every team is full. Without team sizes each ticket is a match on its own.
+fn assign_teams(team_sizes: &[usize], tickets: &[&SimulatedTicket]) -> Option<Vec<(String, usize)>> {
+    if team_sizes.is_empty() {
+        return tickets.first().map(|ticket| vec![(ticket.ticket_id.clone(), 0)]);
+    }
+
+    let mut free_slots = team_sizes.to_vec();
+    let mut assignment = Vec::new();
+    for ticket in tickets {
+        let nb_players = ticket.players.len();
+        if nb_players == 0 {
+            continue;
+        }
+        if let Some(team) = free_slots.iter().position(|free| *free >= nb_players) {
+            free_slots[team] -= nb_players;
+            assignment.push((ticket.ticket_id.clone(), team));
+
+            if free_slots.iter().all(|free| *free == 0) {
+                return Some(assignment);
+            }
+        }
+    }
+
+    None
+}
+
+fn form_backfill_match(
+    tickets: &mut HashMap<String, SimulatedTicket>,
+    game_sessions: &HashMap<String, GameSessionConnectionInfo>
+) -> Option<String> {
+    let mut searching: Vec<&SimulatedTicket> = tickets.values()
+        .filter(|ticket| ticket.status == "SEARCHING")
+        .collect();
+    searching.sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
+
+    let backfill = searching.iter()
+        .find(|ticket| ticket.game_session_arn.is_some()
+            && searching.iter().any(|other| other.game_session_arn.is_none()
+                && other.configuration_name == ticket.configuration_name))?;
+    let ticket = searching.iter()
+        .find(|ticket| ticket.game_session_arn.is_none() 
+            && ticket.configuration_name == backfill.configuration_name)?;
+    let matched_ids = vec![backfill.ticket_id.clone(), ticket.ticket_id.clone()];
+
+    let mut connection_info = game_sessions
+        .get(backfill.game_session_arn.as_ref().unwrap())?
+        .clone();
+    connection_info.matched_player_sessions = Some(ticket.players.iter()
+        .map(|player| MatchedPlayerSession {
+            player_id: player.player_id.clone(),
+            player_session_id: Some(format!("psess-{}", Uuid::new_v4())),
+        })
+        .collect());
+
+    Some(complete_tickets(tickets, &matched_ids, &connection_info))
+}
+
+/// Marks the tickets as completed and builds the matching succeeded event.
+fn complete_tickets(
+    tickets: &mut HashMap<String, SimulatedTicket>,
+    matched_ids: &[String],
+    connection_info: &GameSessionConnectionInfo
+) -> String {
+    let mut matched = Vec::new();
+    for (ticket_id, ticket) in tickets.iter_mut() {
+        if matched_ids.contains(ticket_id) {
+            ticket.end("COMPLETED");
+            ticket.connection_info = Some(connection_info.clone());
+            matched.push(&*ticket);
+        }
+    }
+
+    new_event(FlexMatchEvents::MatchmakingSucceeded, &matched, Some(connection_info))
+}
+
+/// FlexMatch event as published on SNS, see
+/// https://docs.aws.amazon.com/gamelift/latest/flexmatchguide/match-events.html
+fn new_event(
+    e_type: FlexMatchEvents,
+    tickets: &[&SimulatedTicket],
+    connection_info: Option<&GameSessionConnectionInfo>
+) -> String {
+    let tickets: Vec<_> = tickets.iter().map(|ticket| json!({
+        "ticketId": ticket.ticket_id,
+        "startTime": ticket.start_time,
+        "players": ticket.players.iter().map(|player| json!({
+            "playerId": player.player_id,
+            "team": player.team,
+        })).collect::<Vec<_>>(),
+    })).collect();
+
+    let mut detail = json!({
+        "type": e_type.to_string(),
+        "tickets": tickets,
+    });
+    if let Some(info) = connection_info {
+        detail["matchId"] = json!(Uuid::new_v4().to_string());
+        detail["gameSessionInfo"] = json!({
+            "gameSessionArn": info.game_session_arn,
+            "ipAddress": info.ip_address,
+            "port": info.port,
+            "players": info.matched_player_sessions.iter().flatten().map(|session| json!({
+                "playerId": session.player_id,
+                "playerSessionId": session.player_session_id,
+            })).collect::<Vec<_>>(),
+        });
+    }
+
+    json!({
+        "id": Uuid::new_v4().to_string(),
+        "account": "000000000000",
+        "region": "local",
+        "resources": [],
+        "detail": detail,
+    }).to_string()
+}
+
+fn emit_later(state: Arc<SimulatorState>, delay: Duration, event: String) {
+    actix::spawn(async move {
+        actix::clock::sleep(delay).await;
+        emit(&state, event).await;
+    });
+}
+
+async fn emit(state: &SimulatorState, event: String) {
+    let conn = &state.pool.get().unwrap();
+    if let Err(err) = handle_flexmatch_event(&event, state.ws.clone(), conn).await {
+        println!("GameLift simulator event failed: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(team_sizes: Vec<usize>) -> SimulatorConfig {
+        SimulatorConfig {
+            team_sizes,
+            match_delay: Duration::from_secs(0),
+            timeout: Duration::from_secs(120),
+            ip_address: String::from("127.0.0.1"),
+            port: 7777,
+        }
+    }
+
+    fn searching_ticket(ticket_id: &str, configuration_name: &str, player_ids: &[&str], start_time: f64) -> SimulatedTicket {
+        SimulatedTicket {
+            ticket_id: ticket_id.to_string(),
+            configuration_name: configuration_name.to_string(),
+            players: player_ids.iter()
+                .map(|player_id| Player {
+                    player_id: Some(player_id.to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+            status: "SEARCHING",
+            start_time,
+            end_time: None,
+            connection_info: None,
+            game_session_arn: None,
+        }
+    }
+
+    fn tickets_of(list: Vec<SimulatedTicket>) -> HashMap<String, SimulatedTicket> {
+        list.into_iter()
+            .map(|ticket| (ticket.ticket_id.clone(), ticket))
+            .collect()
+    }
+
+    fn team_of(tickets: &HashMap<String, SimulatedTicket>, ticket_id: &str) -> Option<String> {
+        tickets[ticket_id].players[0].team.clone()
+    }
+
+    #[test]
+    fn fills_each_team_with_whole_tickets() {
+        let a = searching_ticket("a", "map", &["1", "2"], 1.0);
+        let b = searching_ticket("b", "map", &["3", "4"], 2.0);
+        let c = searching_ticket("c", "map", &["5"], 3.0);
+        let d = searching_ticket("d", "map", &["6"], 4.0);
+
+        // "b" can't be split between the teams, the younger tickets fill the second one
+        assert_eq!(
+            assign_teams(&[2, 2], &[&a, &c, &b, &d]),
+            Some(vec![(String::from("a"), 0), (String::from("c"), 1), (String::from("d"), 1)]));
+        assert_eq!(
+            assign_teams(&[2, 2], &[&a, &b]),
+            Some(vec![(String::from("a"), 0), (String::from("b"), 1)]));
+        assert_eq!(assign_teams(&[3, 1], &[&a, &b, &c]), None);
+        assert_eq!(assign_teams(&[], &[&c, &d]), Some(vec![(String::from("c"), 0)]));
+    }
+
+    #[test]
+    fn forms_every_match_of_a_trigger() {
+        let mut tickets = tickets_of(vec![
+            searching_ticket("a", "map", &["1"], 1.0),
+            searching_ticket("b", "map", &["2"], 2.0),
+            searching_ticket("c", "other", &["3"], 3.0),
+            searching_ticket("d", "map", &["4"], 4.0),
+            searching_ticket("e", "other", &["5"], 5.0),
+            searching_ticket("f", "map", &["6"], 6.0),
+            searching_ticket("g", "map", &["7"], 7.0),
+        ]);
+        let mut game_sessions = HashMap::new();
+
+        let events = form_matches(&config(vec![1, 1]), &mut tickets, &mut game_sessions);
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(game_sessions.len(), 3);
+        for ticket_id in ["a", "b", "c", "d", "e", "f"] {
+            assert_eq!(tickets[ticket_id].status, "COMPLETED");
+        }
+        assert_eq!(tickets["g"].status, "SEARCHING");
+        assert_eq!(team_of(&tickets, "a"), Some(String::from("0")));
+        assert_eq!(team_of(&tickets, "b"), Some(String::from("1")));
+        assert_eq!(team_of(&tickets, "c"), Some(String::from("0")));
+        assert_eq!(team_of(&tickets, "e"), Some(String::from("1")));
+        assert_eq!(
+            tickets["a"].connection_info.as_ref().unwrap().game_session_arn,
+            tickets["b"].connection_info.as_ref().unwrap().game_session_arn);
+    }
+
+    #[test]
+    fn serves_every_backfill_ticket_of_a_trigger() {
+        let mut game_sessions = HashMap::new();
+        let mut tickets = tickets_of(vec![
+            searching_ticket("a", "map", &["1"], 1.0),
+            searching_ticket("b", "map", &["2"], 2.0),
+        ]);
+        form_matches(&config(vec![1, 1]), &mut tickets, &mut game_sessions);
+        let game_session_arn = game_sessions.keys().next().unwrap().clone();
+
+        let mut first_backfill = searching_ticket("backfill-1", "map", &["1"], 3.0);
+        first_backfill.game_session_arn = Some(game_session_arn.clone());
+        let mut second_backfill = searching_ticket("backfill-2", "map", &["2"], 4.0);
+        second_backfill.game_session_arn = Some(game_session_arn);
+        let mut tickets = tickets_of(vec![
+            first_backfill,
+            second_backfill,
+            searching_ticket("c", "map", &["3"], 5.0),
+            searching_ticket("d", "map", &["4"], 6.0),
+        ]);
+
+        let events = form_matches(&config(vec![1, 1]), &mut tickets, &mut game_sessions);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(game_sessions.len(), 1);
+        for ticket_id in ["backfill-1", "backfill-2", "c", "d"] {
+            assert_eq!(tickets[ticket_id].status, "COMPLETED");
+        }
+    }
+}
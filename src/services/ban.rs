@@ -0,0 +1,139 @@
+use actix::Addr;
+use chrono::NaiveDateTime;
+use diesel::{Connection, OptionalExtension, PgConnection};
+use serde::Serialize;
+use crate::enums::BanScopes;
+use crate::errors::{AppError, AppResult};
+use crate::models::ban::{self, Ban};
+use crate::models::forms::{ban::BanForm, steam_ban_record::SteamBanRecordForm};
+use crate::models::steam_ban_record;
+use crate::services::steam::SteamTicket;
+use crate::services::websocket::{DisconnectUserMessage, WebsocketLobby};
+use crate::services::{custom_room as custom_room_service, session as session_service, token as token_service};
+
+/// What a banned user is told, answered as `{"ban": {...}}` with a 403
+#[derive(Debug, Serialize)]
+pub struct BanDto {
+    pub scope: BanScopes,
+    pub reason: String,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl From<Ban> for BanDto {
+    fn from(ban: Ban) -> Self {
+        BanDto {
+            scope: ban.scope,
+            reason: ban.reason,
+            expires_at: ban.expires_at,
+        }
+    }
+}
+
+/// Scopes in which a ban cuts the websocket, a matchmaking ban only refuses the matchmaking actions
+fn disconnects_websocket(scope: BanScopes) -> bool {
+    scope == BanScopes::Login
+}
+
+/// Fails with the ban of the scope in force for the user, if any.
+pub fn check(user_id: i32, scope: BanScopes, conn: &PgConnection) -> AppResult<()> {
+    match ban::get_active(&user_id, scope, conn)? {
+        Some(ban) => Err(AppError::Banned(BanDto::from(ban))),
+        None => Ok(())
+    }
+}
+
+/// Checks the bans which forbid the websocket
+pub fn check_websocket(user_id: i32, conn: &PgConnection) -> AppResult<()> {
+    check(user_id, BanScopes::Login, conn)
+}
+
+pub fn get_all(user_id: i32, conn: &PgConnection) -> AppResult<Vec<Ban>> {
+    Ok(ban::get_all_by_user_id(&user_id, conn)?)
+}
+
+pub fn get_active_scopes(user_id: i32, conn: &PgConnection) -> AppResult<Vec<BanScopes>> {
+    Ok(ban::get_active_scopes(&user_id, conn)?)
+}
+
+/// Bans a user, a permanent ban has no expiration.
+/// A login ban signs the user out everywhere, ending its access tokens, and closes its websocket which
+/// takes it out of its custom room. A matchmaking ban only takes it out of a room being matchmade.
+pub fn issue(
+    user_id: i32,
+    scope: BanScopes,
+    reason: &str,
+    expires_at: Option<NaiveDateTime>,
+    issued_by: Option<i32>,
+    ws: Addr<WebsocketLobby>,
+    conn: &PgConnection
+) -> AppResult<Ban> {
+    let ban = conn.transaction(|| {
+        let ban = ban::create(BanForm {
+            user_id,
+            scope,
+            reason,
+            expires_at,
+            issued_by,
+        }, conn)?;
+        if scope == BanScopes::Login {
+            session_service::delete_all(user_id, conn)?;
+            token_service::revoke_all(user_id, conn)?;
+        }
+
+        Ok::<_, AppError>(ban)
+    })?;
+
+    if disconnects_websocket(scope) {
+        // also covers a user in a custom room without a live socket
+        custom_room_service::handle_websocket_closing(&user_id, ws.clone(), conn);
+        ws.do_send(DisconnectUserMessage::new(&user_id, format!("Banned: {}", reason)));
+    } else if scope == BanScopes::Matchmaking && custom_room_service::is_matchmaking(&user_id, conn) {
+        custom_room_service::handle_websocket_closing(&user_id, ws, conn);
+    }
+
+    Ok(ban)
+}
+
+/// Lifts a ban before its expiration
+pub fn revoke(ban_id: i32, conn: &PgConnection) -> AppResult<Ban> {
+    ban::get(&ban_id, conn)?;
+    if !ban::revoke(&ban_id, conn)? {
+        return Err(AppError::BadRequest(String::from("The ban is already revoked.")));
+    }
+
+    Ok(ban::get(&ban_id, conn)?)
+}
+
+/// Keeps the ban flags reported by Steam at a login of the user
+pub fn record_steam_flags(user_id: i32, ticket: &SteamTicket, conn: &PgConnection) -> AppResult<()> {
+    Ok(steam_ban_record::create(SteamBanRecordForm {
+        user_id,
+        steam_id: &ticket.steam_id.to_string(),
+        vac_banned: ticket.vac_banned,
+        publisher_banned: ticket.publisher_banned,
+    }, conn)?)
+}
+
+/// Keeps the ban flags of a Steam sign-in without ticket, which only tells the VAC ban: the publisher ban
+/// is the last one reported at a login of the game client.
+pub fn record_steam_player_flags(user_id: i32, ticket: &mut SteamTicket, conn: &PgConnection) -> AppResult<()> {
+    ticket.publisher_banned = steam_ban_record::get_latest_by_user_id(&user_id, conn).optional()?
+        .is_some_and(|record| record.publisher_banned);
+
+    record_steam_flags(user_id, ticket, conn)
+}
+
+/// Steam accounts with a VAC or publisher ban can't log in, create or link an account.
+pub fn check_steam(ticket: &SteamTicket) -> AppResult<()> {
+    let reason = match (ticket.vac_banned, ticket.publisher_banned) {
+        (_, true) => "Steam publisher ban",
+        (true, false) => "Steam VAC ban",
+        (false, false) => return Ok(())
+    };
+
+    Err(AppError::Banned(BanDto {
+        scope: BanScopes::Login,
+        reason: reason.to_string(),
+        expires_at: None,
+    }))
+}
@@ -0,0 +1,57 @@
+use openssl::symm::{Cipher, decrypt_aead, encrypt_aead};
+use rand::Rng;
+use crate::app_conf::SECRET_KEY;
+use crate::errors::{AppError, AppResult};
+
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+fn to_error(err: openssl::error::ErrorStack) -> AppError {
+    AppError::InternalServerError(err.to_string())
+}
+
+/// Key derived from `SECRET_KEY` for a single use, so that data of one use can't be decrypted as another
+fn encryption_key(purpose: &str) -> [u8; 32] {
+    openssl::sha::sha256(format!("{}.{}", purpose, *SECRET_KEY).as_bytes())
+}
+
+/// Encrypts with AES-256-GCM as `base64(nonce | tag | ciphertext)`
+pub fn encrypt(purpose: &str, data: &[u8]) -> AppResult<String> {
+    let nonce = rand::thread_rng().gen::<[u8; NONCE_LENGTH]>();
+    let mut tag = [0u8; TAG_LENGTH];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &encryption_key(purpose),
+        Some(&nonce),
+        &[],
+        data,
+        &mut tag).map_err(to_error)?;
+
+    Ok(base64::encode([&nonce[..], &tag[..], &ciphertext[..]].concat()))
+}
+
+pub fn decrypt(purpose: &str, encrypted: &str) -> AppResult<Vec<u8>> {
+    let data = base64::decode(encrypted)
+        .map_err(|err| AppError::InternalServerError(err.to_string()))?;
+    if data.len() < NONCE_LENGTH + TAG_LENGTH {
+        return Err(AppError::InternalServerError(format!("Invalid encrypted {}", purpose)));
+    }
+    let (nonce, rest) = data.split_at(NONCE_LENGTH);
+    let (tag, ciphertext) = rest.split_at(TAG_LENGTH);
+
+    decrypt_aead(Cipher::aes_256_gcm(), &encryption_key(purpose), Some(nonce), &[], ciphertext, tag)
+        .map_err(to_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypts_only_for_the_same_purpose() {
+        let encrypted = encrypt("test", b"secret").unwrap();
+
+        assert_eq!(decrypt("test", &encrypted).unwrap(), b"secret");
+        assert!(decrypt("other", &encrypted).is_err());
+    }
+}
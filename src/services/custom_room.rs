@@ -1,16 +1,21 @@
-use crate::models::{user, custom_room, custom_room::{CustomRoom, CustomRoomSlot}};
+use crate::models::{user, custom_room, custom_room::{CustomRoom, CustomRoomSlot}, matchmaking_ticket};
 use actix::{Addr};
-use rusoto_gamelift::*;
+use rusoto_gamelift::{StopMatchmakingInput, DescribeMatchmakingInput};
 use crate::services::websocket::{ServerMessage, BroadcastExceptMessage, WebsocketLobby, MultiForwardMessage, ForwardMessage};
 use crate::models::forms::custom_room::{CustomRoomSlotForm};
+use crate::models::forms::matchmaking_ticket::MatchmakingTicketForm;
 use serde::{Serialize};
-use crate::handlers::custom_room::dtos::CustomRoomDto;
+use crate::handlers::custom_room::dtos::{CustomRoomDto, MatchmakingStatusDto};
 use crate::handlers::custom_room::{CustomRoomData, SwitchSlotData};
 use crate::errors::{AppResult, AppError};
-use crate::enums::Archetypes;
-use diesel::{PgConnection};
+use crate::enums::{Archetypes, BanScopes, MatchmakingStatuses};
+use diesel::{Connection, PgConnection};
+use diesel::result::Error as DBError;
 use uuid::Uuid;
 use crate::services::aws::{FlexMatchEvents, FlexMatchData, FlexMatchSucceededDetail};
+use crate::services::matchmaking;
+use crate::services::ban as ban_service;
+use crate::services::aws::GameLiftBackend;
 
 pub fn get_all(
     conn: &PgConnection
@@ -316,7 +321,7 @@ pub async fn start_matchmaking(
     custom_room_id: i32,
     user_id: i32, 
     ws: Addr<WebsocketLobby>,
-    gamelift: &GameLiftClient,
+    gamelift: &dyn GameLiftBackend,
     conn: &PgConnection
 ) -> AppResult<()> {
     match custom_room::get_with_users(&custom_room_id, conn) {
@@ -324,45 +329,64 @@ pub async fn start_matchmaking(
             if custom_room.user_id != user_id {
                 return Err(AppError::BadRequest(String::from("Only the room owner can start matchmaking.")))
             }
+            for (_slot, user) in &tuples {
+                match ban_service::check(user.id, BanScopes::Matchmaking, conn) {
+                    Err(AppError::Banned(_)) if user.id != user_id => return Err(AppError::BadRequest(
+                        format!("{} is banned from matchmaking.", user.nickname))),
+                    result => result?
+                }
+            }
 
+            // the ticket is recorded before GameLift knows it, its first events can arrive before the response
             let ticket_id = Uuid::new_v4();
+            if let Err(err) = conn.transaction::<_, DBError, _>(|| {
+                custom_room::update_ticket(&custom_room_id, &Some(ticket_id), conn)?;
+                matchmaking_ticket::create(
+                    MatchmakingTicketForm {
+                        ticket_id: &ticket_id,
+                        configuration_name: &custom_room.current_map.to_string(),
+                        is_backfill: false,
+                        game_session_arn: None,
+                    },
+                    conn)
+            }) {
+                return Err(AppError::InternalServerError(err.to_string()));
+            }
+
             let start_matchmaking_input = custom_room.get_start_matchmaking_input(&tuples, &ticket_id);
-            match gamelift.start_matchmaking(start_matchmaking_input).await {
-                Ok(result) => {
-                    if let Some(_matchmaking_ticket) = result.matchmaking_ticket {                                               
-                        if let Err(err) = custom_room::update_ticket(
-                            &custom_room_id, 
-                            &Some(ticket_id), 
-                            conn) {
-                            return Err(AppError::InternalServerError(err.to_string()));
-                        }
-    
-                        #[derive(Serialize)]
-                        struct Empty{}
-                        let data = &Empty{};
-                        let mut slots = Vec::new();
-                        for (slot, _user) in tuples {
-                            slots.push(slot);
-                        }
-                        if let Err(err) = send_multi_forward_message(
-                            ws, 
-                            &user_id, 
-                            (custom_room, slots), 
-                            String::from("start-matchmaking"), 
-                            conn, 
-                            data) {
-                            return Err(AppError::BadRequest(err.to_string()));
-                        }
-                        
-                        return Ok(())
-                    }
-                    
-                    Err(AppError::InternalServerError(String::from("Problem with aws matchmaking.")))
-                }, 
-                Err(err) => {
-                    Err(AppError::BadRequest(err.to_string()))
+            let started = match gamelift.start_matchmaking(start_matchmaking_input).await {
+                Ok(result) if result.matchmaking_ticket.is_some() => Ok(()),
+                Ok(_) => Err(AppError::InternalServerError(String::from("Problem with aws matchmaking."))),
+                Err(err) => Err(err)
+            };
+            if let Err(err) = started {
+                if let Err(err) = conn.transaction::<_, DBError, _>(|| {
+                    matchmaking_ticket::update_status(&ticket_id, &MatchmakingStatuses::Failed, conn)?;
+                    custom_room::update_ticket(&custom_room_id, &None, conn)
+                }) {
+                    return Err(AppError::InternalServerError(err.to_string()));
                 }
+                return Err(err);
+            }
+
+            #[derive(Serialize)]
+            struct Empty{}
+            let data = &Empty{};
+            let mut slots = Vec::new();
+            for (slot, _user) in tuples {
+                slots.push(slot);
+            }
+            if let Err(err) = send_multi_forward_message(
+                ws, 
+                &user_id, 
+                (custom_room, slots), 
+                String::from("start-matchmaking"), 
+                conn, 
+                data) {
+                return Err(AppError::BadRequest(err.to_string()));
             }
+
+            Ok(())
         },
         Err(err) => Err(AppError::BadRequest(err.to_string()))
     }
@@ -372,7 +396,7 @@ pub async fn stop_matchmaking(
     custom_room_id: i32,
     user_id: i32, 
     ws: Addr<WebsocketLobby>,
-    gamelift: &GameLiftClient,
+    gamelift: &dyn GameLiftBackend,
     conn: &PgConnection
 ) -> AppResult<()> {
     match custom_room::get(&custom_room_id, conn) {
@@ -384,8 +408,9 @@ pub async fn stop_matchmaking(
                 return Err(AppError::BadRequest(String::from("No matchmaking started for this room.")))
             }
 
+            let ticket_id = tuple.0.matchmaking_ticket.unwrap();
             match gamelift.stop_matchmaking(StopMatchmakingInput {
-                ticket_id: tuple.0.matchmaking_ticket.unwrap().to_string()
+                ticket_id: ticket_id.to_string()
             }).await {
                 Ok(_result) => {
                     if let Err(err) = custom_room::update_ticket(
@@ -394,6 +419,12 @@ pub async fn stop_matchmaking(
                         conn) {
                         return Err(AppError::InternalServerError(err.to_string()));
                     }
+                    if let Err(err) = matchmaking_ticket::update_status(
+                        &ticket_id,
+                        &MatchmakingStatuses::Cancelled,
+                        conn) {
+                        return Err(AppError::InternalServerError(err.to_string()));
+                    }
                     #[derive(Serialize)]
                     struct Empty{}
                     let data = &Empty{};
@@ -410,7 +441,7 @@ pub async fn stop_matchmaking(
                     Ok(())
                 },            
                 Err(err) => {
-                    Err(AppError::BadRequest(err.to_string()))
+                    Err(err)
                 }
             }
         },
@@ -418,53 +449,170 @@ pub async fn stop_matchmaking(
     }
 }
 
+/// Cancels a matchmaking ticket for a moderator, the room searching with it is told as when its owner stops.
+pub async fn force_stop_matchmaking(
+    ticket_id: Uuid,
+    moderator_id: i32,
+    ws: Addr<WebsocketLobby>,
+    gamelift: &dyn GameLiftBackend,
+    conn: &PgConnection
+) -> AppResult<()> {
+    match matchmaking_ticket::get_by_ticket_id(&ticket_id, conn) {
+        Ok(ticket) if ticket.status.is_terminal() => 
+            return Err(AppError::BadRequest(String::from("The matchmaking ticket is already over."))),
+        Ok(_ticket) => (),
+        Err(err) => return Err(AppError::BadRequest(err.to_string()))
+    }
+
+    gamelift.stop_matchmaking(StopMatchmakingInput {
+        ticket_id: ticket_id.to_string()
+    }).await?;
+    if let Err(err) = matchmaking_ticket::update_status(
+        &ticket_id,
+        &MatchmakingStatuses::Cancelled,
+        conn) {
+        return Err(AppError::InternalServerError(err.to_string()));
+    }
+
+    if let Ok(tuple) = custom_room::get_by_ticket_id(ticket_id, conn) {
+        if let Err(err) = custom_room::update_ticket(&tuple.0.id, &None, conn) {
+            return Err(AppError::InternalServerError(err.to_string()));
+        }
+        #[derive(Serialize)]
+        struct Empty{}
+        send_multi_forward_message(
+            ws, 
+            &moderator_id, 
+            tuple, 
+            String::from("stop-matchmaking"), 
+            conn, 
+            &Empty{})?;
+    }
+
+    Ok(())
+}
+
+/// Deletes a room for a moderator, its matchmaking is stopped first and every member is told.
+pub async fn force_delete(
+    custom_room_id: i32,
+    moderator_id: i32,
+    ws: Addr<WebsocketLobby>,
+    gamelift: &dyn GameLiftBackend,
+    conn: &PgConnection
+) -> AppResult<()> {
+    let custom_room = match custom_room::get_without_associations(&custom_room_id, conn) {
+        Ok(custom_room) => custom_room,
+        Err(err) => return Err(AppError::BadRequest(err.to_string()))
+    };
+    if let Some(ticket_id) = custom_room.matchmaking_ticket {
+        if matches!(matchmaking_ticket::get_by_ticket_id(&ticket_id, conn), Ok(ticket) if !ticket.status.is_terminal()) {
+            force_stop_matchmaking(ticket_id, moderator_id, ws.clone(), gamelift, conn).await?;
+        }
+    }
+
+    match custom_room::get(&custom_room_id, conn) {
+        Ok(tuple) => {
+            if let Err(err) = custom_room::delete(&tuple.0.user_id, conn) {
+                return Err(AppError::BadRequest(err.to_string()));
+            }
+            #[derive(Serialize)]
+            struct Empty{}
+            send_multi_forward_message(
+                ws, 
+                &moderator_id, 
+                tuple, 
+                String::from("delete"), 
+                conn, 
+                &Empty{})?;
+
+            Ok(())
+        },
+        Err(err) => Err(AppError::BadRequest(err.to_string()))
+    }
+}
+
 pub fn matchmaking_succeeded(
     data: FlexMatchData<FlexMatchSucceededDetail>,
     ws: Addr<WebsocketLobby>,
     conn: &PgConnection
 ) -> AppResult<()> {
-    let ticket_id = Uuid::parse_str(&data.detail.tickets[0].ticket_id).unwrap();
-    
-    match custom_room::get_by_ticket_id(ticket_id, conn) {
-        Ok((custom_room, slots)) => {
-            #[derive(Serialize)]
-            struct WsData<'a> {
-                pub ip_address: &'a str,
-                pub port: &'a i32,
-                pub player_id: &'a str,
-                pub player_session_id: &'a str
+    #[derive(Serialize)]
+    struct WsData<'a> {
+        pub ip_address: &'a str,
+        pub port: &'a i32,
+        pub player_id: &'a str,
+        pub player_session_id: &'a str
+    }
+
+    // FlexMatch can merge several rooms or solo tickets in a single match
+    for ticket in &data.detail.tickets {
+        let ticket_id = match Uuid::parse_str(&ticket.ticket_id) {
+            Ok(ticket_id) => ticket_id,
+            Err(_) => continue // not created by this server
+        };
+        // the status is written with the room changes, a failure leaves the whole ticket
+        // to the next delivery of the event and the players are notified once committed
+        let user_ids = conn.transaction(|| {
+            if is_stale_event(&ticket_id, &MatchmakingStatuses::Succeeded, conn)? {
+                return Ok(None);
             }
-            
-            for slot in slots {
-                let str_user_id = slot.user_id.to_string();
-                for player in &data.detail.game_session_info.players {
-                    if player.player_id == str_user_id {
-                        let ws_data = WsData {
-                            ip_address: &data.detail.game_session_info.ip_address,
-                            port: &data.detail.game_session_info.port,
-                            player_id: &player.player_id,
-                            player_session_id: &player.player_session_id
-                        };
-
-                        let msg = ForwardMessage::new(
-                            &slot.user_id,
-                            ServerMessage::new(
-                                String::from("/matchmaking/custom-room"),
-                                String::from("matchmaking-succeeded"),
-                                &ws_data)
-                        );
-                        let _ = ws.do_send(msg);   
-                        break;
-                    }        
+            if let Err(err) = matchmaking_ticket::update_status(
+                &ticket_id, 
+                &MatchmakingStatuses::Succeeded, 
+                conn) {
+                return Err(AppError::InternalServerError(err.to_string()))
+            }
+
+            // players of a backfill ticket are already in the game session
+            match matchmaking::is_backfill_ticket(&ticket_id, conn) {
+                Ok(true) => return Ok(None),
+                Ok(false) => {},
+                Err(err) => return Err(AppError::InternalServerError(err.to_string()))
+            }
+
+            match custom_room::get_by_ticket_id(ticket_id, conn) {
+                Ok((custom_room, slots)) => {
+                    if let Err(err) = custom_room::delete(&custom_room.user_id, conn) {
+                        return Err(AppError::InternalServerError(err.to_string()))
+                    }
+                    Ok(Some(slots.iter().map(|slot| slot.user_id).collect::<Vec<i32>>()))
+                },
+                Err(DBError::NotFound) => {
+                    // no room anymore, the players of the ticket are still part of the match
+                    Ok(Some(ticket.get_player_ids()))
+                },
+                Err(err) => {
+                    Err(AppError::InternalServerError(err.to_string()))
                 }
             }
-                
-            if let Err(err) = custom_room::delete(&custom_room.user_id, conn) {
-                return Err(AppError::InternalServerError(err.to_string()))
+        })?;
+        let user_ids = match user_ids {
+            Some(user_ids) => user_ids,
+            None => continue
+        };
+
+        for user_id in user_ids {
+            let str_user_id = user_id.to_string();
+            for player in &data.detail.game_session_info.players {
+                if player.player_id == str_user_id {
+                    let ws_data = WsData {
+                        ip_address: &data.detail.game_session_info.ip_address,
+                        port: &data.detail.game_session_info.port,
+                        player_id: &player.player_id,
+                        player_session_id: &player.player_session_id
+                    };
+
+                    let msg = ForwardMessage::new(
+                        &user_id,
+                        ServerMessage::new(
+                            String::from("/matchmaking/custom-room"),
+                            String::from("matchmaking-succeeded"),
+                            &ws_data)
+                    );
+                    let _ = ws.do_send(msg);   
+                    break;
+                }        
             }
-        },
-        Err(err) => {
-            return Err(AppError::InternalServerError(err.to_string()))
         }
     }
 
@@ -472,19 +620,84 @@ pub fn matchmaking_succeeded(
 }
 
 pub async fn matchmaking_failed(
-    reason: FlexMatchEvents,
+    reason: &FlexMatchEvents,
+    ticket_id: &str,
+    ws: Addr<WebsocketLobby>,
+    conn: &PgConnection
+) -> AppResult<()> {
+    let uuid_ticket_id = parse_ticket_id(ticket_id)?;
+    // the status is written with the release of the room, the players are notified once committed
+    let user_ids = conn.transaction(|| {
+        if let Some(status) = reason.to_matchmaking_status() {
+            if is_stale_event(&uuid_ticket_id, &status, conn)? {
+                return Ok(None)
+            }
+            if let Err(err) = matchmaking_ticket::update_status(&uuid_ticket_id, &status, conn) {
+                return Err(AppError::InternalServerError(err.to_string()))
+            }
+        }
+
+        match custom_room::get_by_ticket_id(uuid_ticket_id, conn) {
+            Ok((custom_room, slots)) => {
+                if let Err(err) = custom_room::update_ticket(&custom_room.id, &None, conn) {
+                    return Err(AppError::InternalServerError(err.to_string()))
+                }
+                Ok(Some(slots.iter().map(|slot| slot.user_id).collect::<Vec<i32>>()))
+            },
+            Err(DBError::NotFound) => {
+                // the room was deleted or its ticket replaced, nothing left to notify
+                Ok(None)
+            },
+            Err(err) => {
+                Err(AppError::InternalServerError(err.to_string()))
+            }
+        }
+    })?;
+
+    if let Some(user_ids) = user_ids {
+        #[derive(Serialize)]
+        struct WsData {
+            pub reason: String
+        }
+
+        let msg = MultiForwardMessage::new(
+            &user_ids,
+            ServerMessage::new(
+                String::from("/matchmaking/custom-room"),
+                String::from("matchmaking-failed"),
+                &WsData {reason: reason.to_string()})
+        );
+        let _ = ws.do_send(msg); 
+    }
+
+    Ok(())
+}
+
+pub fn matchmaking_searching(
     ticket_id: &str,
     ws: Addr<WebsocketLobby>,
     conn: &PgConnection
 ) -> AppResult<()> {
-    let uuid_ticket_id = Uuid::parse_str(ticket_id).unwrap();
+    let uuid_ticket_id = parse_ticket_id(ticket_id)?;
+    if is_stale_event(&uuid_ticket_id, &MatchmakingStatuses::Searching, conn)? {
+        return Ok(())
+    }
+
     match custom_room::get_by_ticket_id(uuid_ticket_id, conn) {
-        Ok((custom_room, slots)) => {
-            #[derive(Serialize)]
-            struct WsData {
-                pub reason: String
+        Ok((_custom_room, slots)) => {
+            if let Err(err) = matchmaking_ticket::update_status(
+                &uuid_ticket_id, 
+                &MatchmakingStatuses::Searching, 
+                conn) {
+                return Err(AppError::InternalServerError(err.to_string()))
             }
 
+            let progress = match matchmaking_ticket::get_by_ticket_id(&uuid_ticket_id, conn)
+                .and_then(|ticket| matchmaking::get_progress(&ticket, conn)) {
+                Ok(progress) => progress,
+                Err(err) => return Err(AppError::InternalServerError(err.to_string()))
+            };
+
             let mut user_ids = Vec::new();
             for slot in slots {
                 user_ids.push(slot.user_id);
@@ -494,14 +707,13 @@ pub async fn matchmaking_failed(
                 &user_ids,
                 ServerMessage::new(
                     String::from("/matchmaking/custom-room"),
-                    String::from("matchmaking-failed"),
-                    &WsData {reason: reason.to_string()})
+                    String::from("matchmaking-searching"),
+                    &progress)
             );
-            let _ = ws.do_send(msg); 
-            
-            if let Err(err) = custom_room::update_ticket(&custom_room.id, &None, conn) {
-                return Err(AppError::InternalServerError(err.to_string()))
-            }
+            let _ = ws.do_send(msg);
+        },
+        Err(DBError::NotFound) => {
+            // the room was deleted or its ticket replaced, nothing left to notify
         },
         Err(err) => {
             return Err(AppError::InternalServerError(err.to_string()))
@@ -511,6 +723,77 @@ pub async fn matchmaking_failed(
     Ok(())
 }
 
+pub async fn get_matchmaking_status(
+    custom_room_id: i32,
+    user_id: i32,
+    gamelift: &dyn GameLiftBackend,
+    conn: &PgConnection
+) -> AppResult<MatchmakingStatusDto> {
+    let (custom_room, slots) = match custom_room::get(&custom_room_id, conn) {
+        Ok(tuple) => tuple,
+        Err(err) => return Err(AppError::BadRequest(err.to_string()))
+    };
+    if !slots.iter().any(|slot| slot.user_id == user_id) {
+        return Err(AppError::BadRequest(String::from("Only room members can see the matchmaking status.")))
+    }
+    let ticket_id = match custom_room.matchmaking_ticket {
+        Some(ticket_id) => ticket_id,
+        None => return Err(AppError::BadRequest(String::from("No matchmaking started for this room.")))
+    };
+
+    let mut status = match matchmaking_ticket::get_by_ticket_id(&ticket_id, conn)
+        .and_then(|ticket| matchmaking::get_progress(&ticket, conn)) {
+        Ok(status) => status,
+        Err(err) => return Err(AppError::InternalServerError(err.to_string()))
+    };
+
+    match gamelift.describe_matchmaking(DescribeMatchmakingInput {
+        ticket_ids: vec![ticket_id.to_string()]
+    }).await {
+        Ok(result) => {
+            let aws_ticket = result.ticket_list
+                .and_then(|tickets| tickets.into_iter().next());
+            if let Some(aws_ticket) = aws_ticket {
+                if let Some(aws_status) = aws_ticket.status.as_deref()
+                    .and_then(MatchmakingStatuses::from_aws_status) {
+                    status.status = aws_status;
+                }
+                status.status_reason = aws_ticket.status_reason;
+                status.status_message = aws_ticket.status_message;
+                if status.estimated_wait_seconds.is_none() {
+                    status.estimated_wait_seconds = aws_ticket.estimated_wait_time;
+                }
+            }
+
+            Ok(status)
+        },
+        Err(err) => Err(err)
+    }
+}
+
+fn is_stale_event(
+    ticket_id: &Uuid, 
+    status: &MatchmakingStatuses, 
+    conn: &PgConnection
+) -> AppResult<bool> {
+    matchmaking::accept_transition(ticket_id, status, conn)
+        .map(|accepted| !accepted)
+        .map_err(|err| AppError::InternalServerError(err.to_string()))
+}
+
+fn parse_ticket_id(ticket_id: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(ticket_id)
+        .map_err(|_| AppError::BadRequest(format!("Invalid matchmaking ticket id: {}", ticket_id)))
+}
+
+/// Whether the custom room of the user is looking for a match
+pub fn is_matchmaking(user_id: &i32, conn: &PgConnection) -> bool {
+    custom_room::get_slot_by_user_id(user_id, conn)
+        .and_then(|slot| custom_room::get_without_associations(&slot.custom_room_id, conn))
+        .map(|custom_room| custom_room.matchmaking_ticket.is_some())
+        .unwrap_or(false)
+}
+
 pub fn handle_websocket_closing(
     user_id: &i32, 
     ws: Addr<WebsocketLobby>,
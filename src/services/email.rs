@@ -1,101 +1,87 @@
-use actix_web::{http, http::uri::Builder};
-use crate::errors::{AppResult, AppError};
-use serde::{Serialize, Deserialize};
-use awc::Client;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::errors::AppResult;
 
-pub struct EmailService<'a> {
-    pub to: &'a str,
+pub mod file;
+pub mod http;
+pub mod outbox;
+pub mod sender;
+pub mod smtp;
+pub mod template;
+
+const BASE64_LINE_LENGTH: usize = 76;
+
+/// An email ready to be delivered
+pub struct EmailMessage {
+    pub from: String,
+    pub to: String,
     pub subject: String,
+    pub text: String,
     pub html: String,
-    #[cfg(debug_assertions)]
-    pub force_in_debug: bool,
 }
 
-impl<'a> EmailService<'a> {
-    pub fn new(to: &'a str, subject: String, html: String) -> Self {
-        EmailService {
-            to,
-            subject,
-            html,
-            #[cfg(debug_assertions)]
-            force_in_debug: true
-        }
-    }
-
-    #[cfg(debug_assertions)]
-    pub async fn send(&self) -> AppResult<()> {
-        if self.force_in_debug {
-            self._send().await
-        } else {
-            Ok(())
-        }
-    }
+impl EmailMessage {
+    /// RFC 5322 multipart message with base64 text and HTML parts, as sent over SMTP and kept by the file sink
+    pub fn to_mime(&self) -> String {
+        let domain = self.from.rsplit('@').next().unwrap_or("localhost");
+        let boundary = Uuid::new_v4().to_simple().to_string();
 
-    #[cfg(not(debug_assertions))]
-    pub async fn send(&self) -> AppResult<()> {
-        self._send().await
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nMIME-Version: 1.0\r\n\
+            Content-Type: multipart/alternative; boundary=\"{}\"\r\n\r\n\
+            --{}\r\n{}--{}\r\n{}--{}--\r\n",
+            self.from,
+            self.to,
+            encode_header(&self.subject),
+            Utc::now().to_rfc2822(),
+            Uuid::new_v4(),
+            domain,
+            boundary,
+            boundary,
+            mime_part("text/plain", &self.text.replace('\n', "\r\n")),
+            boundary,
+            mime_part("text/html", &self.html),
+            boundary)
     }
+}
 
-    async fn _send(&self) -> AppResult<()> {
-        let email = Email {
-            sender: Address { 
-                email: &std::env::var("EMAIL_DEFAULT_ADDRESS")
-                    .expect("EMAIL_DEFAULT_ADDRESS must be set")
-            },
-            to: vec![Address {
-                email: self.to
-            }],
-            subject: &self.subject,
-            html_content: &self.html
-        };
-
-        let uri = Builder::new()
-            .scheme("https")
-            .authority(std::env::var("EMAIL_DOMAIN")
-                .expect("EMAIL_DOMAIN must be set"))
-            .path_and_query("/v3/smtp/email")
-            .build()?;
-
-        let client = Client::default();
-        let mut response = client.post(uri)
-            .insert_header((http::header::ACCEPT, "application/json"))
-            .insert_header(("api-key", std::env::var("EMAIL_KEY")
-                .expect("EMAIL_KEY must be set")))
-            .insert_header((http::header::CONTENT_TYPE, "application/json"))
-            .send_json(&email).await?;
+fn mime_part(content_type: &str, content: &str) -> String {
+    let body = base64::encode(content).into_bytes();
+    let body = body.chunks(BASE64_LINE_LENGTH)
+        .map(|line| String::from_utf8_lossy(line).into_owned())
+        .collect::<Vec<_>>()
+        .join("\r\n");
 
-        if response.status() != http::StatusCode::CREATED {
-            let body = response.body().await?;
-            match serde_json::from_slice::<EmailServiceResponseError>(&body) {
-                Ok(r) => {
-                    return Err(AppError::InternalServerError(format!("Error from email service code: {}, message: {}", r.code, r.message)))
-                },
-                Err(_) => {
-                    return Err(AppError::InternalServerError(format!("Unknown error from email service.")))
-                }
-            }            
-        } 
+    format!(
+        "Content-Type: {}; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+        content_type,
+        body)
+}
 
-        Ok(())
+/// Non-ASCII headers are sent as RFC 2047 encoded words
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::encode(value))
     }
 }
 
-#[derive(Serialize)]
-struct Address<'a> {
-    pub email: &'a str
+/// Delivery of the emails, through the HTTP API of the provider, an SMTP server
+/// or a local file during development.
+#[async_trait(?Send)]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, email: &EmailMessage) -> AppResult<()>;
 }
 
-#[derive(Serialize)]
-struct Email<'a> {
-    pub sender: Address<'a>,
-    pub to: Vec<Address<'a>>,
-    pub subject: &'a str,
-    #[serde(rename = "htmlContent")]
-    pub html_content: &'a str
+/// Transport selected by `EMAIL_TRANSPORT`: `http` (default), `smtp` or `file`.
+pub fn get_email_transport() -> Arc<dyn EmailTransport> {
+    match std::env::var("EMAIL_TRANSPORT").unwrap_or_default().as_str() {
+        "" | "http" => Arc::new(http::HttpTransport::from_env()),
+        "smtp" => Arc::new(smtp::SmtpTransport::from_env()),
+        "file" => Arc::new(file::FileTransport::from_env()),
+        transport => panic!("Unknown EMAIL_TRANSPORT: {}", transport)
+    }
 }
-
-#[derive(Deserialize)]
-struct EmailServiceResponseError {
-    pub code: String,
-    pub message: String
-}
\ No newline at end of file
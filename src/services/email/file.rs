@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+use crate::errors::{AppError, AppResult};
+use super::{EmailMessage, EmailTransport};
+
+/// Appends the emails to an mbox file, `EMAIL_FILE_PATH` (`emails.mbox` by default),
+/// to read them locally without sending anything.
+pub struct FileTransport {
+    path: String,
+    // messages of concurrent workers must not interleave
+    lock: Mutex<()>,
+}
+
+impl FileTransport {
+    pub fn from_env() -> Self {
+        FileTransport {
+            path: std::env::var("EMAIL_FILE_PATH").unwrap_or_else(|_| "emails.mbox".to_string()),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl EmailTransport for FileTransport {
+    async fn send(&self, email: &EmailMessage) -> AppResult<()> {
+        let message = email.to_mime().replace("\r\n", "\n");
+        let _lock = self.lock.lock().unwrap();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| AppError::InternalServerError(format!("Can't open {}: {}", self.path, err)))?;
+        // the body is base64 so no line starts with "From "
+        write!(file, "From {} {}\n{}\n", email.from, Utc::now().format("%a %b %e %T %Y"), message)
+            .map_err(|err| AppError::InternalServerError(format!("Can't write {}: {}", self.path, err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_email(to: &str, subject: &str) -> EmailMessage {
+        EmailMessage {
+            from: String::from("no-reply@rigidity.com"),
+            to: to.to_string(),
+            subject: subject.to_string(),
+            text: String::from("Hello,\n\nWelcome to Rigidity."),
+            html: String::from("<p>Hello,</p><p>Welcome to Rigidity.</p>"),
+        }
+    }
+
+    /// Decoded content of the base64 parts of a message
+    fn decode_parts(message: &str) -> Vec<String> {
+        message.split("Content-Transfer-Encoding: base64\n\n")
+            .skip(1)
+            .map(|part| {
+                let body: String = part.lines().take_while(|line| !line.is_empty() && !line.starts_with("--")).collect();
+                String::from_utf8(base64::decode(body).unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn appends_each_email_to_the_mbox_file() {
+        let path = std::env::temp_dir().join(format!("rigidity-{}.mbox", uuid::Uuid::new_v4()));
+        let transport = FileTransport {
+            path: path.to_string_lossy().into_owned(),
+            lock: Mutex::new(()),
+        };
+
+        transport.send(&new_email("player@spikegames.eu", "Welcome")).await.unwrap();
+        transport.send(&new_email("other@spikegames.eu", "Bienvenue à Rigidity")).await.unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!content.contains('\r'));
+        let messages: Vec<&str> = content.split("\nFrom no-reply@rigidity.com ").collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("From no-reply@rigidity.com "));
+
+        assert!(messages[0].contains("\nTo: player@spikegames.eu\nSubject: Welcome\n"));
+        assert!(messages[1].contains("\nTo: other@spikegames.eu\n"));
+        assert!(messages[1].contains(&format!("\nSubject: =?UTF-8?B?{}?=\n", base64::encode("Bienvenue à Rigidity"))));
+        assert_eq!(decode_parts(messages[0]), vec![
+            String::from("Hello,\r\n\r\nWelcome to Rigidity."),
+            String::from("<p>Hello,</p><p>Welcome to Rigidity.</p>"),
+        ]);
+    }
+}
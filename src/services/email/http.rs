@@ -0,0 +1,87 @@
+use actix_web::{http, http::uri::Builder};
+use async_trait::async_trait;
+use awc::Client;
+use serde::{Serialize, Deserialize};
+use crate::errors::{AppResult, AppError};
+use super::{EmailMessage, EmailTransport};
+
+/// HTTP API of the email provider, `EMAIL_DOMAIN` and `EMAIL_KEY`
+pub struct HttpTransport {
+    domain: String,
+    key: String,
+}
+
+impl HttpTransport {
+    pub fn from_env() -> Self {
+        HttpTransport {
+            domain: std::env::var("EMAIL_DOMAIN").expect("EMAIL_DOMAIN must be set"),
+            key: std::env::var("EMAIL_KEY").expect("EMAIL_KEY must be set"),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl EmailTransport for HttpTransport {
+    async fn send(&self, email: &EmailMessage) -> AppResult<()> {
+        let email = Email {
+            sender: Address {
+                email: &email.from
+            },
+            to: vec![Address {
+                email: &email.to
+            }],
+            subject: &email.subject,
+            text_content: &email.text,
+            html_content: &email.html
+        };
+
+        let uri = Builder::new()
+            .scheme("https")
+            .authority(self.domain.as_str())
+            .path_and_query("/v3/smtp/email")
+            .build()?;
+
+        let client = Client::default();
+        let mut response = client.post(uri)
+            .insert_header((http::header::ACCEPT, "application/json"))
+            .insert_header(("api-key", self.key.as_str()))
+            .insert_header((http::header::CONTENT_TYPE, "application/json"))
+            .send_json(&email).await?;
+
+        if response.status() != http::StatusCode::CREATED {
+            let body = response.body().await?;
+            match serde_json::from_slice::<EmailServiceResponseError>(&body) {
+                Ok(r) => {
+                    return Err(AppError::InternalServerError(format!("Error from email service code: {}, message: {}", r.code, r.message)))
+                },
+                Err(_) => {
+                    return Err(AppError::InternalServerError(String::from("Unknown error from email service.")))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct Address<'a> {
+    pub email: &'a str
+}
+
+#[derive(Serialize)]
+struct Email<'a> {
+    pub sender: Address<'a>,
+    pub to: Vec<Address<'a>>,
+    pub subject: &'a str,
+    #[serde(rename = "textContent")]
+    pub text_content: &'a str,
+    #[serde(rename = "htmlContent")]
+    pub html_content: &'a str
+}
+
+#[derive(Deserialize)]
+struct EmailServiceResponseError {
+    pub code: String,
+    pub message: String
+}
@@ -0,0 +1,42 @@
+use chrono::Utc;
+use diesel::PgConnection;
+use crate::errors::{AppError, AppResult};
+use crate::models::email_outbox::{self, OutboxEmail};
+use crate::models::forms::email_outbox::OutboxEmailForm;
+use crate::models::user::User;
+use crate::services::crypto;
+use super::template::EmailContent;
+
+/// The bodies hold valid tokens, they are stored encrypted with a key of their own
+pub const BODY_PURPOSE: &str = "email-body";
+
+/// Writes an email to the outbox, to call in the transaction of what it announces
+/// so that it is sent if and only if that is committed. The `EmailSender` delivers it.
+pub fn queue(user: &User, content: &EmailContent, conn: &PgConnection) -> AppResult<()> {
+    queue_to(user, &user.email, content, conn)
+}
+
+/// Same as `queue` for another address of the user, such as the new one of an email change
+pub fn queue_to(user: &User, recipient: &str, content: &EmailContent, conn: &PgConnection) -> AppResult<()> {
+    Ok(email_outbox::create(OutboxEmailForm {
+        user_id: user.id,
+        recipient,
+        subject: &content.subject,
+        text_body: &crypto::encrypt(BODY_PURPOSE, content.text.as_bytes())?,
+        html_body: &crypto::encrypt(BODY_PURPOSE, content.html.as_bytes())?,
+        next_attempt_at: Utc::now().naive_utc(),
+    }, conn)?)
+}
+
+/// Decrypted text and HTML bodies of an email of the outbox
+pub fn decrypt_bodies(email: &OutboxEmail) -> AppResult<(String, String)> {
+    let decrypt = |body: &str| crypto::decrypt(BODY_PURPOSE, body)
+        .and_then(|body| String::from_utf8(body).map_err(|err| AppError::InternalServerError(err.to_string())));
+
+    Ok((decrypt(&email.text_body)?, decrypt(&email.html_body)?))
+}
+
+/// Emails given up after their last attempt, newest first
+pub fn get_failed(limit: i64, offset: i64, conn: &PgConnection) -> AppResult<Vec<OutboxEmail>> {
+    Ok(email_outbox::get_all_failed(limit, offset, conn)?)
+}
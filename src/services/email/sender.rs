@@ -0,0 +1,140 @@
+use actix::prelude::{Actor, Context, AsyncContext, WrapFuture, ActorFutureExt};
+use actix_web::web;
+use chrono::{Duration, NaiveDateTime, Utc};
+use std::sync::Arc;
+use crate::Pool;
+use crate::errors::AppResult;
+use crate::models::email_outbox;
+use super::{outbox, EmailMessage, EmailTransport};
+
+/// Emails sent at most at every interval
+const BATCH_SIZE: i64 = 20;
+/// Time given to the sending of an email before it can be taken again
+const LEASE_SECS: i64 = 300;
+
+/// Exponential backoff of the failed emails
+#[derive(Clone)]
+pub struct RetryConfig {
+    pub base_delay: Duration,
+    pub max_attempts: i32,
+}
+
+impl RetryConfig {
+    /// `EMAIL_RETRY_BASE_SECS` (30 by default) doubled at every attempt, `EMAIL_MAX_ATTEMPTS` (8 by default).
+    pub fn from_env() -> Self {
+        RetryConfig {
+            base_delay: Duration::seconds(std::env::var("EMAIL_RETRY_BASE_SECS").ok()
+                .map(|secs| secs.parse::<i64>().expect("EMAIL_RETRY_BASE_SECS must be a number of seconds"))
+                .unwrap_or(30)),
+            max_attempts: std::env::var("EMAIL_MAX_ATTEMPTS").ok()
+                .map(|attempts| attempts.parse::<i32>().expect("EMAIL_MAX_ATTEMPTS must be a number"))
+                .unwrap_or(8),
+        }
+    }
+
+    /// Time of the attempt following `attempts` failed ones, `None` when the email is given up.
+    pub fn next_attempt_at(&self, attempts: i32) -> Option<NaiveDateTime> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+
+        Some(Utc::now().naive_utc() + self.base_delay * 2_i32.pow((attempts - 1).clamp(0, 20) as u32))
+    }
+}
+
+/// Delivers the emails of the outbox, failed ones are retried later and given up
+/// after the last attempt.
+pub struct EmailSender {
+    interval: std::time::Duration,
+    is_sending: bool,
+    from: String,
+    retry: RetryConfig,
+    transport: Arc<dyn EmailTransport>,
+    pool: Pool,
+}
+
+impl EmailSender {
+    pub fn new(interval: std::time::Duration, transport: Arc<dyn EmailTransport>, pool: Pool) -> Self {
+        EmailSender {
+            interval,
+            is_sending: false,
+            from: std::env::var("EMAIL_DEFAULT_ADDRESS").expect("EMAIL_DEFAULT_ADDRESS must be set"),
+            retry: RetryConfig::from_env(),
+            transport,
+            pool,
+        }
+    }
+}
+
+impl Actor for EmailSender {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.interval, |act, ctx| {
+            // a slow batch must not overlap with the next one
+            if act.is_sending {
+                return;
+            }
+            act.is_sending = true;
+
+            let send = send_due(act.from.clone(), act.retry.clone(), act.transport.clone(), act.pool.clone());
+            ctx.spawn(send
+                .into_actor(act)
+                .map(|result, act, _| {
+                    if let Err(err) = result {
+                        println!("Email sending failed: {}", err);
+                    }
+                    act.is_sending = false;
+                }));
+        });
+    }
+}
+
+async fn send_due(
+    from: String,
+    retry: RetryConfig,
+    transport: Arc<dyn EmailTransport>,
+    pool: Pool
+) -> AppResult<()> {
+    // one email is claimed at a time so that its lease only has to outlast its own sending, and
+    // the queries run on the blocking thread pool without holding a connection during the sending
+    for _ in 0..BATCH_SIZE {
+        let claim_pool = pool.clone();
+        let email = match web::block(move || {
+            let now = Utc::now().naive_utc();
+            email_outbox::claim_due(now, now + Duration::seconds(LEASE_SECS), 1, &claim_pool.get().unwrap())
+        }).await??.pop() {
+            Some(email) => email,
+            None => break
+        };
+
+        let sent = match outbox::decrypt_bodies(&email) {
+            Ok((text, html)) => transport.send(&EmailMessage {
+                from: from.clone(),
+                to: email.recipient.clone(),
+                subject: email.subject.clone(),
+                text,
+                html,
+            }).await,
+            Err(err) => Err(err)
+        };
+
+        let result_pool = pool.clone();
+        let retry = retry.clone();
+        web::block(move || {
+            let conn = &result_pool.get().unwrap();
+            match sent {
+                Ok(()) => email_outbox::delete(&email.id, conn),
+                Err(err) => {
+                    let retry_at = retry.next_attempt_at(email.attempts + 1);
+                    if retry_at.is_none() {
+                        println!("Email {} of user {} given up: {:?}", email.id, email.user_id, err);
+                    }
+                    email_outbox::record_failure(&email.id, &format!("{:?}", err), retry_at, conn)
+                }
+            }
+        }).await??;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use actix_web::web;
+use async_trait::async_trait;
+use openssl::ssl::{SslConnector, SslMethod};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use crate::errors::{AppError, AppResult};
+use super::{EmailMessage, EmailTransport};
+
+const SMTP_TIMEOUT_SECS: u64 = 30;
+
+/// SMTP server upgraded with STARTTLS, `SMTP_HOST` and `SMTP_PORT` (587 by default),
+/// authenticated when `SMTP_USERNAME` and `SMTP_PASSWORD` are set.
+#[derive(Clone)]
+pub struct SmtpTransport {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+}
+
+impl SmtpTransport {
+    pub fn from_env() -> Self {
+        let username = std::env::var("SMTP_USERNAME").ok().filter(|username| !username.is_empty());
+
+        SmtpTransport {
+            host: std::env::var("SMTP_HOST").expect("SMTP_HOST must be set"),
+            port: std::env::var("SMTP_PORT").ok()
+                .map(|port| port.parse::<u16>().expect("SMTP_PORT must be a port number"))
+                .unwrap_or(587),
+            credentials: username.map(|username| (
+                username,
+                std::env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set with SMTP_USERNAME"))),
+        }
+    }
+
+    fn t_send(&self, from: &str, to: &str, message: &str) -> AppResult<()> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port)).map_err(smtp_error)?;
+        stream.set_read_timeout(Some(Duration::from_secs(SMTP_TIMEOUT_SECS))).map_err(smtp_error)?;
+        stream.set_write_timeout(Some(Duration::from_secs(SMTP_TIMEOUT_SECS))).map_err(smtp_error)?;
+
+        let mut conn = SmtpConnection::new(stream);
+        conn.read_reply(220)?;
+        conn.command("EHLO localhost", 250)?;
+        conn.command("STARTTLS", 220)?;
+
+        let connector = SslConnector::builder(SslMethod::tls()).map_err(smtp_error)?.build();
+        let tls_stream = connector.connect(&self.host, conn.into_inner()).map_err(smtp_error)?;
+        let mut conn = SmtpConnection::new(tls_stream);
+        conn.command("EHLO localhost", 250)?;
+
+        if let Some((username, password)) = &self.credentials {
+            let credentials = base64::encode(format!("\0{}\0{}", username, password));
+            conn.command(&format!("AUTH PLAIN {}", credentials), 235)?;
+        }
+        conn.command(&format!("MAIL FROM:<{}>", from), 250)?;
+        conn.command(&format!("RCPT TO:<{}>", to), 250)?;
+        conn.command("DATA", 354)?;
+        // lines starting with a dot are escaped by doubling it
+        let data = message.replace("\r\n.", "\r\n..");
+        conn.command(&format!("{}.", data), 250)?;
+        conn.command("QUIT", 221)
+    }
+}
+
+#[async_trait(?Send)]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &EmailMessage) -> AppResult<()> {
+        let (transport, from, to, message) = (self.clone(), email.from.clone(), email.to.clone(), email.to_mime());
+
+        web::block(move || transport.t_send(&from, &to, &message)).await?
+    }
+}
+
+struct SmtpConnection<S: Read + Write> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write> SmtpConnection<S> {
+    fn new(stream: S) -> Self {
+        SmtpConnection { stream: BufReader::new(stream) }
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    fn command(&mut self, command: &str, expected_code: u16) -> AppResult<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(command.as_bytes()).map_err(smtp_error)?;
+        stream.write_all(b"\r\n").map_err(smtp_error)?;
+        stream.flush().map_err(smtp_error)?;
+
+        self.read_reply(expected_code)
+    }
+
+    /// Reads a reply, the lines of a multiline one are separated from the code by a dash
+    fn read_reply(&mut self, expected_code: u16) -> AppResult<()> {
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).map_err(smtp_error)? == 0 {
+                return Err(AppError::InternalServerError(String::from("SMTP server closed the connection.")));
+            }
+
+            let code = line.get(0..3).and_then(|code| code.parse::<u16>().ok());
+            if code != Some(expected_code) {
+                return Err(AppError::InternalServerError(format!("SMTP server error: {}", line.trim_end())));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn smtp_error<E: std::fmt::Display>(err: E) -> AppError {
+    AppError::InternalServerError(format!("SMTP error: {}", err))
+}
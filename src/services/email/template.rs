@@ -0,0 +1,167 @@
+use crate::app_conf::get_base_url;
+use crate::enums::Languages;
+
+const HTML_LAYOUT: &str = include_str!("../../../templates/emails/layout.html");
+const TEXT_LAYOUT: &str = include_str!("../../../templates/emails/layout.txt");
+
+/// Emails of `templates/emails`, each one has a text and an HTML variant per language.
+/// The first line of the text variant is the subject.
+#[derive(Clone, Copy)]
+pub enum EmailTemplates {
+    EmailConfirmation,
+    /// Confirmation of the new address of an email change
+    EmailChange,
+    /// Warning sent to the previous address of an email change
+    EmailChangeNotice,
+    PasswordReset,
+}
+
+/// Rendered email, sent as a multipart text and HTML message
+pub struct EmailContent {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+fn get_sources(template: EmailTemplates, language: Languages) -> (&'static str, &'static str) {
+    match (template, language) {
+        (EmailTemplates::EmailConfirmation, Languages::En) => (
+            include_str!("../../../templates/emails/en/email_confirmation.txt"),
+            include_str!("../../../templates/emails/en/email_confirmation.html")),
+        (EmailTemplates::EmailConfirmation, Languages::Fr) => (
+            include_str!("../../../templates/emails/fr/email_confirmation.txt"),
+            include_str!("../../../templates/emails/fr/email_confirmation.html")),
+        (EmailTemplates::EmailChange, Languages::En) => (
+            include_str!("../../../templates/emails/en/email_change.txt"),
+            include_str!("../../../templates/emails/en/email_change.html")),
+        (EmailTemplates::EmailChange, Languages::Fr) => (
+            include_str!("../../../templates/emails/fr/email_change.txt"),
+            include_str!("../../../templates/emails/fr/email_change.html")),
+        (EmailTemplates::EmailChangeNotice, Languages::En) => (
+            include_str!("../../../templates/emails/en/email_change_notice.txt"),
+            include_str!("../../../templates/emails/en/email_change_notice.html")),
+        (EmailTemplates::EmailChangeNotice, Languages::Fr) => (
+            include_str!("../../../templates/emails/fr/email_change_notice.txt"),
+            include_str!("../../../templates/emails/fr/email_change_notice.html")),
+        (EmailTemplates::PasswordReset, Languages::En) => (
+            include_str!("../../../templates/emails/en/password_reset.txt"),
+            include_str!("../../../templates/emails/en/password_reset.html")),
+        (EmailTemplates::PasswordReset, Languages::Fr) => (
+            include_str!("../../../templates/emails/fr/password_reset.txt"),
+            include_str!("../../../templates/emails/fr/password_reset.html")),
+    }
+}
+
+/// Renders a template in the language, `{{name}}` placeholders are replaced by the values,
+/// escaped in the HTML variant. Both variants are wrapped in the shared layout.
+pub fn render(template: EmailTemplates, language: Languages, values: &[(&str, &str)]) -> EmailContent {
+    render_for(&get_base_url(), template, language, values)
+}
+
+fn render_for(base_url: &str, template: EmailTemplates, language: Languages, values: &[(&str, &str)]) -> EmailContent {
+    let (text, html) = get_sources(template, language);
+    let (subject, text) = text.split_once('\n').unwrap_or((text, ""));
+    let logo_url = format!("{}/static/assets/images/logo_studio.png", base_url);
+    let layout_values = [("lang", language.code()), ("base_url", base_url), ("logo_url", &logo_url)];
+
+    let text = fill(text.trim(), values, str::to_string);
+    let html = fill(html.trim(), values, escape_html);
+
+    EmailContent {
+        subject: fill(subject.trim(), values, str::to_string),
+        text: fill(TEXT_LAYOUT, &layout_values, str::to_string).replace("{{content}}", &text),
+        html: fill(HTML_LAYOUT, &layout_values, escape_html).replace("{{content}}", &html),
+    }
+}
+
+fn fill(source: &str, values: &[(&str, &str)], escape: fn(&str) -> String) -> String {
+    values.iter().fold(source.to_string(), |filled, (name, value)|
+        filled.replace(&format!("{{{{{}}}}}", name), &escape(value)))
+}
+
+fn escape_html(value: &str) -> String {
+    value.chars().fold(String::with_capacity(value.len()), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rendered emails kept as `<language>/<template>.txt` (subject then text) and `.html`,
+    /// `UPDATE_SNAPSHOTS=1 cargo test` rewrites them after a change of the templates.
+    const SNAPSHOTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/services/email/snapshots");
+    const BASE_URL: &str = "https://rigidity.example";
+    const TEMPLATES: [EmailTemplates; 4] = [
+        EmailTemplates::EmailConfirmation,
+        EmailTemplates::EmailChange,
+        EmailTemplates::EmailChangeNotice,
+        EmailTemplates::PasswordReset,
+    ];
+    const LANGUAGES: [Languages; 2] = [Languages::En, Languages::Fr];
+
+    /// Name of the snapshot and values as given by the auth service
+    fn get_case(template: EmailTemplates) -> (&'static str, Vec<(&'static str, &'static str)>) {
+        let expire_time = ("expire_time", "2023-03-27 14:05 UTC");
+        match template {
+            EmailTemplates::EmailConfirmation => ("email_confirmation", vec![
+                ("url", "https://rigidity.example/static/email_confirmation.html?id=0123abcd.token"), expire_time]),
+            EmailTemplates::EmailChange => ("email_change", vec![
+                ("url", "https://rigidity.example/static/email_confirmation.html?id=0123abcd.token"), expire_time]),
+            // the quote shows the escaping of the HTML variant
+            EmailTemplates::EmailChangeNotice => ("email_change_notice", vec![
+                ("new_email", "o'neil@spikegames.eu"),
+                ("reset_url", "https://rigidity.example/static/ask_password_reset.html")]),
+            EmailTemplates::PasswordReset => ("password_reset", vec![
+                ("url", "https://rigidity.example/static/reset_password.html?id=0123abcd.token"), expire_time]),
+        }
+    }
+
+    fn check_snapshot(path: &str, rendered: &str, mismatches: &mut Vec<String>) {
+        if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+            std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap()).unwrap();
+            std::fs::write(path, rendered).unwrap();
+        } else if std::fs::read_to_string(path).ok().as_deref() != Some(rendered) {
+            mismatches.push(path.to_string());
+        }
+    }
+
+    #[test]
+    fn rendered_emails_match_the_snapshots() {
+        let mut mismatches = vec![];
+        for template in TEMPLATES {
+            let (name, values) = get_case(template);
+            for language in LANGUAGES {
+                let content = render_for(BASE_URL, template, language, &values);
+                let path = format!("{}/{}/{}", SNAPSHOTS_DIR, language.code(), name);
+
+                check_snapshot(&format!("{}.txt", path), &format!("{}\n{}", content.subject, content.text), &mut mismatches);
+                check_snapshot(&format!("{}.html", path), &content.html, &mut mismatches);
+            }
+        }
+
+        assert!(mismatches.is_empty(), "outdated snapshots, check and run with UPDATE_SNAPSHOTS=1: {:?}", mismatches);
+    }
+
+    #[test]
+    fn every_placeholder_is_filled() {
+        for template in TEMPLATES {
+            let (name, values) = get_case(template);
+            for language in LANGUAGES {
+                let content = render_for(BASE_URL, template, language, &values);
+                for rendered in [&content.subject, &content.text, &content.html] {
+                    assert!(!rendered.contains("{{"), "{} in {}", name, language.code());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::models::matchmaking_ticket::{self, MatchmakingTicket};
+use crate::models::{flexmatch_event, ORMResult};
+use crate::handlers::custom_room::dtos::MatchmakingStatusDto;
+use crate::handlers::game_server::BackfillData;
+use crate::models::forms::matchmaking_ticket::MatchmakingTicketForm;
+use crate::models::{custom_room as custom_room_model, user};
+use crate::enums::MatchmakingStatuses;
+use crate::errors::{AppError, AppResult};
+use crate::services::aws::{FlexMatchData, FlexMatchDetail, FlexMatchEvents, FlexMatchSucceededDetail, GameLiftBackend};
+use crate::services::custom_room;
+use crate::services::websocket::WebsocketLobby;
+use actix::Addr;
+use diesel::{PgConnection};
+use diesel::result::Error as DBError;
+use rusoto_gamelift::{Player, StartMatchBackfillInput};
+use serde_json::from_str;
+use uuid::Uuid;
+
+pub mod poller;
+
+const NB_TICKETS_FOR_ESTIMATE: i64 = 20;
+
+pub fn estimate_wait_seconds(
+    configuration_name: &str,
+    conn: &PgConnection
+) -> ORMResult<Option<i64>> {
+    let durations = matchmaking_ticket::get_recent_durations(
+        configuration_name, 
+        NB_TICKETS_FOR_ESTIMATE, 
+        conn)?;
+
+    if durations.is_empty() {
+        return Ok(None)
+    }
+
+    Ok(Some(durations.iter().sum::<i64>() / durations.len() as i64))
+}
+
+pub fn get_progress(
+    ticket: &MatchmakingTicket,
+    conn: &PgConnection
+) -> ORMResult<MatchmakingStatusDto> {
+    Ok(MatchmakingStatusDto {
+        ticket_id: ticket.ticket_id,
+        status: ticket.status,
+        status_reason: None,
+        status_message: None,
+        elapsed_seconds: ticket.get_elapsed_seconds(),
+        estimated_wait_seconds: estimate_wait_seconds(&ticket.configuration_name, conn)?,
+    })
+}
+
+/// Tells if a ticket can move to `new_status`. Events for tickets which already ended or 
+/// which are older than the current ticket state must be ignored.
+/// Tickets created before the tracking of their status are always accepted.
+pub fn accept_transition(
+    ticket_id: &Uuid,
+    new_status: &MatchmakingStatuses,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    match matchmaking_ticket::get_by_ticket_id(ticket_id, conn) {
+        Ok(ticket) => Ok(!ticket.status.is_terminal() && new_status.rank() >= ticket.status.rank()),
+        Err(DBError::NotFound) => Ok(true),
+        Err(err) => Err(err)
+    }
+}
+
+pub fn is_backfill_ticket(
+    ticket_id: &Uuid,
+    conn: &PgConnection
+) -> ORMResult<bool> {
+    match matchmaking_ticket::get_by_ticket_id(ticket_id, conn) {
+        Ok(ticket) => Ok(ticket.is_backfill),
+        Err(DBError::NotFound) => Ok(false),
+        Err(err) => Err(err)
+    }
+}
+
+/// Requests new players for an open slot of a running game session, the matched
+/// players receive the usual matchmaking-succeeded message.
+pub async fn start_backfill(
+    data: BackfillData,
+    gamelift: &dyn GameLiftBackend,
+    conn: &PgConnection
+) -> AppResult<Uuid> {
+    let mut players = Vec::new();
+    for player in data.players {
+        let nickname = match user::get(&player.user_id, conn) {
+            Ok(user) => user.nickname,
+            Err(DBError::NotFound) => return Err(AppError::BadRequest(format!("Unknown user: {}", player.user_id))),
+            Err(err) => return Err(AppError::InternalServerError(err.to_string()))
+        };
+
+        players.push(Player {
+            latency_in_ms: None,
+            player_attributes: Some(custom_room_model::get_gamelift_attributes(
+                player.team, 
+                player.team_position, 
+                &player.archetype, 
+                &nickname)),
+            player_id: Some(player.user_id.to_string()),
+            team: Some(player.team.to_string()),
+        });
+    }
+
+    // the ticket is recorded before GameLift knows it, its first events can arrive before the response
+    let ticket_id = Uuid::new_v4();
+    let configuration_name = data.map.to_string();
+    if let Err(err) = matchmaking_ticket::create(
+        MatchmakingTicketForm {
+            ticket_id: &ticket_id,
+            configuration_name: &configuration_name,
+            is_backfill: true,
+            game_session_arn: Some(&data.game_session_arn),
+        },
+        conn) {
+        return Err(AppError::InternalServerError(err.to_string()));
+    }
+
+    if let Err(err) = gamelift.start_match_backfill(StartMatchBackfillInput {
+        configuration_name: configuration_name.clone(),
+        game_session_arn: Some(data.game_session_arn.clone()),
+        players,
+        ticket_id: Some(ticket_id.to_string()),
+    }).await {
+        if let Err(err) = matchmaking_ticket::update_status(&ticket_id, &MatchmakingStatuses::Failed, conn) {
+            return Err(AppError::InternalServerError(err.to_string()));
+        }
+        return Err(err);
+    }
+
+    Ok(ticket_id)
+}
+
+/// Processes a FlexMatch event, `message` being the json sent by FlexMatch.
+/// Each event is processed once, even when delivered several times.
+pub async fn handle_flexmatch_event(
+    message: &str,
+    ws: Addr<WebsocketLobby>,
+    conn: &PgConnection
+) -> AppResult<()> {
+    let data = from_str::<FlexMatchData<FlexMatchDetail>>(message)
+        .map_err(|_| AppError::BadRequest(String::from("FlexMatch message has wrong format.")))?;
+
+    // SNS delivers at least once, an event already claimed is acknowledged without processing
+    if !flexmatch_event::claim(&data.id, &data.detail.e_type.to_string(), conn)? {
+        return Ok(())
+    }
+
+    if let Err(err) = process_flexmatch_event(data.detail, message, ws, conn).await {
+        flexmatch_event::release(&data.id, conn)?;
+        return Err(err)
+    }
+
+    Ok(())
+}
+
+async fn process_flexmatch_event(
+    detail: FlexMatchDetail,
+    message: &str,
+    ws: Addr<WebsocketLobby>,
+    conn: &PgConnection
+) -> AppResult<()> {
+    match detail.e_type {
+        FlexMatchEvents::MatchmakingSucceeded => {
+            let data = from_str::<FlexMatchData<FlexMatchSucceededDetail>>(message)?;
+            custom_room::matchmaking_succeeded(data, ws, conn)?;
+        },
+        FlexMatchEvents::MatchmakingSearching => {
+            for ticket in &detail.tickets {
+                custom_room::matchmaking_searching(&ticket.ticket_id, ws.clone(), conn)?;
+            }
+        },
+        FlexMatchEvents::MatchmakingTimedOut |
+        FlexMatchEvents::MatchmakingCancelled |
+        FlexMatchEvents::MatchmakingFailed => {
+            for ticket in &detail.tickets {
+                custom_room::matchmaking_failed(&detail.e_type, &ticket.ticket_id, ws.clone(), conn).await?;
+            }
+        }
+        _ => {
+
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use actix::prelude::{Actor, Context, AsyncContext, WrapFuture, ActorFutureExt};
+use actix::Addr;
+use rusoto_gamelift::DescribeMatchmakingInput;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+use crate::Pool;
+use crate::errors::AppResult;
+use crate::enums::MatchmakingStatuses;
+use crate::models::{custom_room, matchmaking_ticket};
+use crate::services::aws::{FlexMatchData, FlexMatchEvents, GameLiftBackend};
+use crate::services::custom_room as custom_room_service;
+use crate::services::websocket::WebsocketLobby;
+
+/// DescribeMatchmaking accepts at most 10 tickets per call.
+const MAX_TICKETS_PER_REQUEST: usize = 10;
+
+/// Fallback for environments which can't receive FlexMatch notifications from SNS:
+/// periodically asks GameLift the status of every ticket stored in `custom_rooms`.
+pub struct MatchmakingPoller {
+    interval: Duration,
+    is_polling: bool,
+    gamelift: Arc<dyn GameLiftBackend>,
+    ws: Addr<WebsocketLobby>,
+    pool: Pool,
+}
+
+impl MatchmakingPoller {
+    pub fn new(
+        interval: Duration, 
+        gamelift: Arc<dyn GameLiftBackend>, 
+        ws: Addr<WebsocketLobby>, 
+        pool: Pool
+    ) -> Self {
+        MatchmakingPoller {
+            interval,
+            is_polling: false,
+            gamelift,
+            ws,
+            pool,
+        }
+    }
+}
+
+impl Actor for MatchmakingPoller {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.interval, |act, ctx| {
+            // a slow poll must not overlap with the next one
+            if act.is_polling {
+                return;
+            }
+            act.is_polling = true;
+
+            let poll = poll_tickets(act.gamelift.clone(), act.ws.clone(), act.pool.clone());
+            ctx.spawn(poll
+                .into_actor(act)
+                .map(|result, act, _| {
+                    if let Err(err) = result {
+                        println!("Matchmaking polling failed: {}", err);
+                    }
+                    act.is_polling = false;
+                }));
+        });
+    }
+}
+
+async fn poll_tickets(
+    gamelift: Arc<dyn GameLiftBackend>,
+    ws: Addr<WebsocketLobby>,
+    pool: Pool
+) -> AppResult<()> {
+    let tickets = custom_room::get_all_matchmaking_tickets(&pool.get().unwrap())?;
+
+    // a failing chunk or ticket must not stop the others from being polled
+    for chunk in tickets.chunks(MAX_TICKETS_PER_REQUEST) {
+        let input = DescribeMatchmakingInput {
+            ticket_ids: chunk.iter().map(|ticket_id| ticket_id.to_string()).collect()
+        };
+        let output = match gamelift.describe_matchmaking(input).await {
+            Ok(output) => output,
+            Err(err) => {
+                println!("DescribeMatchmaking of {} tickets failed: {}", chunk.len(), err);
+                continue;
+            }
+        };
+
+        for ticket in output.ticket_list.unwrap_or_default() {
+            let (ticket_id, event) = match (&ticket.ticket_id, ticket.status.as_deref()
+                .and_then(FlexMatchEvents::from_aws_status)) {
+                (Some(ticket_id), Some(event)) => (ticket_id, event),
+                _ => continue
+            };
+
+            let result = match event {
+                FlexMatchEvents::MatchmakingSearching => {
+                    // a ticket is searching at every poll, the room is only told the first time
+                    let conn = &pool.get().unwrap();
+                    let status = Uuid::parse_str(ticket_id).ok()
+                        .and_then(|uuid_ticket_id| matchmaking_ticket::get_by_ticket_id(&uuid_ticket_id, conn).ok())
+                        .map(|ticket| ticket.status);
+                    if status == Some(MatchmakingStatuses::Searching) {
+                        Ok(())
+                    } else {
+                        custom_room_service::matchmaking_searching(ticket_id, ws.clone(), conn)
+                    }
+                },
+                FlexMatchEvents::MatchmakingSucceeded => {
+                    match FlexMatchData::new_from_completed_ticket(&ticket) {
+                        Some(data) => custom_room_service::matchmaking_succeeded(data, ws.clone(), &pool.get().unwrap()),
+                        None => Ok(())
+                    }
+                },
+                FlexMatchEvents::MatchmakingTimedOut |
+                FlexMatchEvents::MatchmakingCancelled |
+                FlexMatchEvents::MatchmakingFailed => {
+                    custom_room_service::matchmaking_failed(&event, ticket_id, ws.clone(), &pool.get().unwrap()).await
+                },
+                _ => Ok(())
+            };
+
+            if let Err(err) = result {
+                println!("Matchmaking polling of ticket {} failed: {}", ticket_id, err);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,246 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use diesel::PgConnection;
+use crate::errors::{AppError, AppResult};
+use crate::models::rate_limit;
+use crate::Pool;
+
+pub mod postgres;
+
+/// Storage of the attempts and lockouts, kept in memory or in Postgres
+/// when several instances of the server share the limits.
+pub trait RateLimitStore: Send + Sync {
+    /// Records an attempt and returns the number of attempts made since `window_start`.
+    fn record_attempt(&self, key: &str, window_start: NaiveDateTime) -> AppResult<i64>;
+    fn get_locked_until(&self, key: &str) -> AppResult<Option<NaiveDateTime>>;
+    /// Counts a failure and returns the number of consecutive failures.
+    fn record_failure(&self, key: &str) -> AppResult<i32>;
+    fn lock(&self, key: &str, until: NaiveDateTime) -> AppResult<()>;
+    fn reset(&self, key: &str) -> AppResult<()>;
+}
+
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    attempts: Mutex<MemoryAttempts>,
+    lockouts: Mutex<HashMap<String, (i32, Option<NaiveDateTime>)>>,
+}
+
+#[derive(Default)]
+struct MemoryAttempts {
+    by_key: HashMap<String, VecDeque<NaiveDateTime>>,
+    /// Next removal of the keys without attempts in the window
+    next_sweep_at: Option<NaiveDateTime>,
+}
+
+impl RateLimitStore for MemoryRateLimitStore {
+    fn record_attempt(&self, key: &str, window_start: NaiveDateTime) -> AppResult<i64> {
+        let mut attempts = self.attempts.lock().unwrap();
+
+        // the other keys are only swept once per window, their old attempts are not counted anyway
+        let now = now();
+        if attempts.next_sweep_at.is_none_or(|next_sweep_at| next_sweep_at <= now) {
+            attempts.by_key.retain(|_, times| times.back().is_some_and(|time| *time >= window_start));
+            attempts.next_sweep_at = Some(now + (now - window_start));
+        }
+
+        let times = attempts.by_key.entry(key.to_string()).or_default();
+        while times.front().is_some_and(|time| *time < window_start) {
+            times.pop_front();
+        }
+        times.push_back(now);
+
+        Ok(times.len() as i64)
+    }
+
+    fn get_locked_until(&self, key: &str) -> AppResult<Option<NaiveDateTime>> {
+        Ok(self.lockouts.lock().unwrap()
+            .get(key)
+            .and_then(|(_, locked_until)| *locked_until))
+    }
+
+    fn record_failure(&self, key: &str) -> AppResult<i32> {
+        let mut lockouts = self.lockouts.lock().unwrap();
+        let (failures, _) = lockouts.entry(key.to_string()).or_insert((0, None));
+        *failures += 1;
+
+        Ok(*failures)
+    }
+
+    fn lock(&self, key: &str, until: NaiveDateTime) -> AppResult<()> {
+        if let Some((_, locked_until)) = self.lockouts.lock().unwrap().get_mut(key) {
+            *locked_until = Some(until);
+        }
+
+        Ok(())
+    }
+
+    fn reset(&self, key: &str) -> AppResult<()> {
+        self.lockouts.lock().unwrap().remove(key);
+
+        Ok(())
+    }
+}
+
+pub struct RateLimitConfig {
+    pub window: Duration,
+    pub max_attempts_per_ip: i64,
+    pub max_attempts_per_account: i64,
+    /// Number of consecutive failures of an account before it is locked
+    pub lockout_threshold: i32,
+    /// Lockout duration after the threshold, doubled on every new failure
+    pub lockout_base: Duration,
+    pub lockout_max: Duration,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        let number = |name: &str, default: i64| std::env::var(name).ok()
+            .map(|value| value.parse::<i64>().unwrap_or_else(|_| panic!("{} must be a number", name)))
+            .unwrap_or(default);
+
+        RateLimitConfig {
+            window: Duration::seconds(number("RATE_LIMIT_WINDOW_SECS", 900)),
+            max_attempts_per_ip: number("RATE_LIMIT_MAX_ATTEMPTS_PER_IP", 50),
+            max_attempts_per_account: number("RATE_LIMIT_MAX_ATTEMPTS_PER_ACCOUNT", 10),
+            lockout_threshold: number("LOCKOUT_THRESHOLD", 5) as i32,
+            lockout_base: Duration::seconds(number("LOCKOUT_BASE_SECS", 30)),
+            lockout_max: Duration::seconds(number("LOCKOUT_MAX_SECS", 3600)),
+        }
+    }
+
+    fn get_lockout_duration(&self, failures: i32) -> Option<Duration> {
+        if failures < self.lockout_threshold {
+            return None
+        }
+
+        let exponent = (failures - self.lockout_threshold).min(20) as u32;
+        let duration = self.lockout_base * 2_i32.pow(exponent);
+        Some(if duration > self.lockout_max { self.lockout_max } else { duration })
+    }
+}
+
+/// Sliding window limits by client IP and by account, and lockout of accounts
+/// after repeated failures.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, store: Arc<dyn RateLimitStore>) -> Self {
+        RateLimiter { config, store }
+    }
+
+    /// Store selected by `RATE_LIMIT_STORE`: `memory` (default) or `postgres`.
+    pub fn from_env(pool: Pool) -> Self {
+        let store: Arc<dyn RateLimitStore> = match std::env::var("RATE_LIMIT_STORE").unwrap_or_default().as_str() {
+            "" | "memory" => Arc::new(MemoryRateLimitStore::default()),
+            "postgres" => Arc::new(postgres::PgRateLimitStore::new(pool)),
+            store => panic!("Unknown RATE_LIMIT_STORE: {}", store)
+        };
+
+        RateLimiter::new(RateLimitConfig::from_env(), store)
+    }
+
+    /// Records an attempt of `action`, fails when a limit is reached or the account is locked.
+    pub fn check(&self, action: &str, ip: Option<&str>, account: Option<&str>) -> AppResult<()> {
+        let window_start = now() - self.config.window;
+        let too_many = Err(AppError::TooManyRequests(String::from("Too many attempts, try again later.")));
+
+        if let Some(account) = account {
+            if let Some(locked_until) = self.store.get_locked_until(&lockout_key(account))? {
+                if locked_until > now() {
+                    return too_many
+                }
+            }
+        }
+        if let Some(ip) = ip {
+            let key = format!("{}:ip:{}", action, ip);
+            if self.store.record_attempt(&key, window_start)? > self.config.max_attempts_per_ip {
+                return too_many
+            }
+        }
+        if let Some(account) = account {
+            let key = format!("{}:account:{}", action, account.to_lowercase());
+            if self.store.record_attempt(&key, window_start)? > self.config.max_attempts_per_account {
+                return too_many
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn record_failure(&self, account: &str) -> AppResult<()> {
+        let key = lockout_key(account);
+        let failures = self.store.record_failure(&key)?;
+        if let Some(duration) = self.config.get_lockout_duration(failures) {
+            self.store.lock(&key, now() + duration)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn record_success(&self, account: &str) -> AppResult<()> {
+        self.store.reset(&lockout_key(account))
+    }
+}
+
+/// Removes what is stored in Postgres about an account, the memory store forgets it on restart.
+pub fn forget_account(account: &str, conn: &PgConnection) -> AppResult<()> {
+    let escaped_account = account.to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    rate_limit::delete_by_account(
+        &lockout_key(account),
+        &format!("%:account:{}", escaped_account),
+        conn).map_err(|err| AppError::InternalServerError(err.to_string()))
+}
+
+/// Deletes the attempts of the Postgres store older than the window, most keys are never tried
+/// again to clear their own. Returns how many were deleted.
+pub fn purge_stale_attempts(conn: &PgConnection) -> AppResult<usize> {
+    rate_limit::delete_attempts_before(now() - RateLimitConfig::from_env().window, conn)
+        .map_err(|err| AppError::InternalServerError(err.to_string()))
+}
+
+fn lockout_key(account: &str) -> String {
+    format!("account:{}", account.to_lowercase())
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_store_counts_the_attempts_of_the_window() {
+        let store = MemoryRateLimitStore::default();
+        let window_start = now() - Duration::minutes(15);
+
+        assert_eq!(store.record_attempt("login:ip:1", window_start).unwrap(), 1);
+        assert_eq!(store.record_attempt("login:ip:1", window_start).unwrap(), 2);
+        assert_eq!(store.record_attempt("login:ip:2", window_start).unwrap(), 1);
+        // attempts before the window are dropped
+        assert_eq!(store.record_attempt("login:ip:1", now() + Duration::seconds(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn memory_store_sweeps_stale_keys_once_per_window() {
+        let store = MemoryRateLimitStore::default();
+        store.record_attempt("login:ip:1", now() - Duration::minutes(15)).unwrap();
+        store.record_attempt("login:ip:2", now() - Duration::minutes(15)).unwrap();
+        assert_eq!(store.attempts.lock().unwrap().by_key.len(), 2);
+
+        store.attempts.lock().unwrap().next_sweep_at = Some(now());
+        store.record_attempt("login:ip:3", now() + Duration::seconds(1)).unwrap();
+        let attempts = store.attempts.lock().unwrap();
+        assert_eq!(attempts.by_key.len(), 1);
+        assert!(attempts.by_key.contains_key("login:ip:3"));
+    }
+}
@@ -0,0 +1,42 @@
+use chrono::NaiveDateTime;
+use crate::errors::{AppError, AppResult};
+use crate::models::rate_limit;
+use crate::Pool;
+use super::RateLimitStore;
+
+pub struct PgRateLimitStore {
+    pool: Pool,
+}
+
+impl PgRateLimitStore {
+    pub fn new(pool: Pool) -> Self {
+        PgRateLimitStore { pool }
+    }
+}
+
+impl RateLimitStore for PgRateLimitStore {
+    fn record_attempt(&self, key: &str, window_start: NaiveDateTime) -> AppResult<i64> {
+        rate_limit::record_attempt(key, window_start, &self.pool.get().unwrap())
+            .map_err(|err| AppError::InternalServerError(err.to_string()))
+    }
+
+    fn get_locked_until(&self, key: &str) -> AppResult<Option<NaiveDateTime>> {
+        rate_limit::get_locked_until(key, &self.pool.get().unwrap())
+            .map_err(|err| AppError::InternalServerError(err.to_string()))
+    }
+
+    fn record_failure(&self, key: &str) -> AppResult<i32> {
+        rate_limit::record_failure(key, &self.pool.get().unwrap())
+            .map_err(|err| AppError::InternalServerError(err.to_string()))
+    }
+
+    fn lock(&self, key: &str, until: NaiveDateTime) -> AppResult<()> {
+        rate_limit::lock(key, until, &self.pool.get().unwrap())
+            .map_err(|err| AppError::InternalServerError(err.to_string()))
+    }
+
+    fn reset(&self, key: &str) -> AppResult<()> {
+        rate_limit::reset(key, &self.pool.get().unwrap())
+            .map_err(|err| AppError::InternalServerError(err.to_string()))
+    }
+}
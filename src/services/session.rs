@@ -0,0 +1,62 @@
+use actix_session::SessionExt;
+use actix_web::HttpRequest;
+use diesel::PgConnection;
+use uuid::Uuid;
+use crate::errors::{AppError, AppResult};
+use crate::handlers::{get_request_ip, get_request_user_agent};
+use crate::models::session::{self, Session};
+
+pub mod store;
+
+pub const SESSION_ID_KEY: &str = "session_id";
+pub const SESSION_IP_KEY: &str = "ip";
+pub const SESSION_USER_AGENT_KEY: &str = "user_agent";
+
+/// Stores the client metadata in the session of the request, to call after `Identity::login`.
+pub fn insert_metadata(request: &HttpRequest) -> AppResult<()> {
+    let session = request.get_session();
+    let user_agent = get_request_user_agent(request);
+
+    session.insert(SESSION_ID_KEY, Uuid::new_v4().to_string())
+        .and_then(|_| session.insert(SESSION_IP_KEY, get_request_ip(request)))
+        .and_then(|_| session.insert(SESSION_USER_AGENT_KEY, user_agent))
+        .map_err(|err| AppError::InternalServerError(err.to_string()))
+}
+
+/// Id of the session of the request
+pub fn get_current_session_id(request: &HttpRequest) -> Option<Uuid> {
+    request.get_session()
+        .get::<String>(SESSION_ID_KEY)
+        .ok()
+        .flatten()
+        .and_then(|session_id| Uuid::parse_str(&session_id).ok())
+}
+
+pub fn get_all(user_id: i32, conn: &PgConnection) -> AppResult<Vec<Session>> {
+    session::get_all_by_user_id(&user_id, conn)
+        .map_err(|err| AppError::InternalServerError(err.to_string()))
+}
+
+pub fn delete(user_id: i32, session_id: &Uuid, conn: &PgConnection) -> AppResult<()> {
+    match session::delete_by_session_id(&user_id, session_id, conn) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(AppError::BadRequest(String::from("Unknown session."))),
+        Err(err) => Err(AppError::InternalServerError(err.to_string()))
+    }
+}
+
+/// Signs the user out everywhere
+pub fn delete_all(user_id: i32, conn: &PgConnection) -> AppResult<()> {
+    session::delete_all_by_user_id(&user_id, conn)
+        .map_err(|err| AppError::InternalServerError(err.to_string()))
+}
+
+
+/// Signs the user out everywhere but in the current session
+pub fn delete_others(user_id: i32, current_session_id: Option<Uuid>, conn: &PgConnection) -> AppResult<()> {
+    match current_session_id {
+        Some(current_session_id) => session::delete_others_by_user_id(&user_id, &current_session_id, conn)
+            .map_err(|err| AppError::InternalServerError(err.to_string())),
+        None => delete_all(user_id, conn)
+    }
+}
@@ -0,0 +1,140 @@
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use diesel::result::Error as DBError;
+use rand::distributions::{Alphanumeric, DistString};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use uuid::Uuid;
+use crate::models::forms::session::SessionForm;
+use crate::models::session;
+use crate::Pool;
+use super::{SESSION_ID_KEY, SESSION_IP_KEY, SESSION_USER_AGENT_KEY};
+
+const SESSION_KEY_LENGTH: usize = 64;
+const IDENTITY_KEY: &str = "actix_identity.user_id";
+
+type SessionState = HashMap<String, String>;
+
+/// Session storage of `actix-session` kept in Postgres so that sessions can be listed and revoked.
+pub struct PgSessionStore {
+    pool: Pool,
+}
+
+impl PgSessionStore {
+    pub fn new(pool: Pool) -> Self {
+        PgSessionStore { pool }
+    }
+
+    async fn write(
+        &self, 
+        session_key: Option<String>, 
+        session_state: SessionState, 
+        ttl: &Duration
+    ) -> Result<SessionKey, anyhow::Error> {
+        let state = serde_json::to_string(&session_state)?;
+        let expires_at = get_expires_at(ttl);
+        let pool = self.pool.clone();
+
+        let key = web::block(move || -> Result<String, anyhow::Error> {
+            let conn = &pool.get().unwrap();
+            let get = |key: &str| session_state.get(key)
+                .and_then(|value| serde_json::from_str::<String>(value).ok());
+            let session_id = get(SESSION_ID_KEY)
+                .and_then(|session_id| Uuid::parse_str(&session_id).ok())
+                .unwrap_or_else(Uuid::new_v4);
+            let user_id = get(IDENTITY_KEY).and_then(|user_id| user_id.parse::<i32>().ok());
+            let ip = get(SESSION_IP_KEY);
+            let user_agent = get(SESSION_USER_AGENT_KEY);
+
+            let mut form = SessionForm {
+                session_id: &session_id,
+                session_key: "",
+                user_id,
+                state: &state,
+                ip: ip.as_deref(),
+                user_agent: user_agent.as_deref(),
+                last_seen_at: Utc::now().naive_utc(),
+                expires_at,
+            };
+            if let Some(key) = session_key {
+                form.session_key = &key;
+                if session::update(&key, form, conn)? {
+                    return Ok(key)
+                }
+                // never recreate a session revoked during the request
+                return Err(anyhow::anyhow!("Session was revoked."))
+            }
+
+            let key = Alphanumeric.sample_string(&mut rand::thread_rng(), SESSION_KEY_LENGTH);
+            form.session_key = &key;
+            session::create(form, conn)?;
+
+            Ok(key)
+        }).await??;
+
+        Ok(SessionKey::try_from(key)?)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for PgSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let pool = self.pool.clone();
+        let key = session_key.as_ref().to_string();
+
+        let result = web::block(move || 
+            session::get_by_session_key(&key, &pool.get().unwrap()))
+            .await
+            .map_err(|err| LoadError::Other(anyhow::Error::new(err)))?;
+
+        match result {
+            Ok(session) => serde_json::from_str::<SessionState>(&session.state)
+                .map(Some)
+                .map_err(|err| LoadError::Deserialization(anyhow::Error::new(err))),
+            Err(DBError::NotFound) => Ok(None),
+            Err(err) => Err(LoadError::Other(anyhow::Error::new(err)))
+        }
+    }
+
+    async fn save(&self, session_state: SessionState, ttl: &Duration) -> Result<SessionKey, SaveError> {
+        self.write(None, session_state, ttl).await
+            .map_err(SaveError::Other)
+    }
+
+    async fn update(
+        &self, 
+        session_key: SessionKey, 
+        session_state: SessionState, 
+        ttl: &Duration
+    ) -> Result<SessionKey, UpdateError> {
+        self.write(Some(session_key.into()), session_state, ttl).await
+            .map_err(UpdateError::Other)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> Result<(), anyhow::Error> {
+        let pool = self.pool.clone();
+        let key = session_key.as_ref().to_string();
+        let expires_at = get_expires_at(ttl);
+
+        web::block(move || 
+            session::update_expires_at(&key, expires_at, &pool.get().unwrap())).await??;
+
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        let pool = self.pool.clone();
+        let key = session_key.as_ref().to_string();
+
+        web::block(move || 
+            session::delete_by_session_key(&key, &pool.get().unwrap())).await??;
+
+        Ok(())
+    }
+}
+
+fn get_expires_at(ttl: &Duration) -> NaiveDateTime {
+    (Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())).naive_utc()
+}
@@ -0,0 +1,401 @@
+use actix_web::http::{StatusCode, Uri};
+use awc::Client;
+use chrono::{DateTime, Duration, Utc};
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::sign::Verifier;
+use openssl::x509::X509;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use crate::errors::{AppError, AppResult};
+
+/// SNS retries a failed delivery for about one hour with the default delivery policy.
+const MESSAGE_MAX_AGE_MINUTES: i64 = 60;
+const MESSAGE_MAX_CLOCK_SKEW_MINUTES: i64 = 5;
+/// SNS signs with one certificate per region, a handful is enough
+const MAX_CACHED_CERTIFICATES: usize = 16;
+
+#[derive(Deserialize, Debug)]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub m_type: String,
+    #[serde(rename = "MessageId")]
+    pub message_id: String,
+    #[serde(rename = "Token")]
+    pub token: Option<String>,
+    #[serde(rename = "TopicArn")]
+    pub topic_arn: String,
+    #[serde(rename = "Subject")]
+    pub subject: Option<String>,
+    #[serde(rename = "Message")]
+    pub message: String,
+    #[serde(rename = "Timestamp")]
+    pub timestamp: String,
+    #[serde(rename = "SignatureVersion")]
+    pub signature_version: String,
+    #[serde(rename = "Signature")]
+    pub signature: String,
+    #[serde(rename = "SigningCertURL")]
+    pub signing_cert_url: String,
+    #[serde(rename = "SubscribeURL")]
+    pub subscribe_url: Option<String>,
+}
+
+impl SnsMessage {
+    /// Canonical string signed by SNS, see
+    /// https://docs.aws.amazon.com/sns/latest/dg/sns-verify-signature-of-message.html
+    pub fn get_string_to_sign(&self) -> AppResult<String> {
+        let fields = match self.m_type.as_str() {
+            "Notification" => vec![
+                ("Message", Some(&self.message)),
+                ("MessageId", Some(&self.message_id)),
+                ("Subject", self.subject.as_ref()),
+                ("Timestamp", Some(&self.timestamp)),
+                ("TopicArn", Some(&self.topic_arn)),
+                ("Type", Some(&self.m_type)),
+            ],
+            "SubscriptionConfirmation" | "UnsubscribeConfirmation" => vec![
+                ("Message", Some(&self.message)),
+                ("MessageId", Some(&self.message_id)),
+                ("SubscribeURL", self.subscribe_url.as_ref()),
+                ("Timestamp", Some(&self.timestamp)),
+                ("Token", self.token.as_ref()),
+                ("TopicArn", Some(&self.topic_arn)),
+                ("Type", Some(&self.m_type)),
+            ],
+            _ => return Err(AppError::BadRequest(format!("Unknown SNS message type: {}", self.m_type)))
+        };
+
+        let mut result = String::new();
+        for (key, value) in fields {
+            if let Some(value) = value {
+                result.push_str(key);
+                result.push('\n');
+                result.push_str(value);
+                result.push('\n');
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn get_digest(&self) -> AppResult<MessageDigest> {
+        match self.signature_version.as_str() {
+            "1" => Ok(MessageDigest::sha1()),
+            "2" => Ok(MessageDigest::sha256()),
+            _ => Err(AppError::BadRequest(format!("Unsupported SNS signature version: {}", self.signature_version)))
+        }
+    }
+}
+
+/// Checks that an url points to an AWS SNS endpoint (`https://sns.<region>.amazonaws.com[.cn]/...`).
+pub fn is_sns_url(url: &str) -> bool {
+    let uri = match url.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return false
+    };
+    if uri.scheme_str() != Some("https") || uri.port().is_some() {
+        return false
+    }
+
+    match uri.host() {
+        Some(host) => {
+            let region = host.strip_prefix("sns.")
+                .and_then(|h| h.strip_suffix(".amazonaws.com")
+                    .or_else(|| h.strip_suffix(".amazonaws.com.cn")));
+            match region {
+                Some(region) => !region.is_empty() && region.chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+                None => false
+            }
+        },
+        None => false
+    }
+}
+
+/// Certificates by url, the least recently used one is dropped when it is full.
+struct CertificateCache {
+    capacity: usize,
+    entries: VecDeque<(String, X509)>,
+}
+
+impl CertificateCache {
+    fn new(capacity: usize) -> Self {
+        CertificateCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&mut self, url: &str) -> Option<X509> {
+        let position = self.entries.iter().position(|(entry_url, _)| entry_url == url)?;
+        let entry = self.entries.remove(position)?;
+        let certificate = entry.1.clone();
+        self.entries.push_front(entry);
+
+        Some(certificate)
+    }
+
+    fn insert(&mut self, url: &str, certificate: X509) {
+        self.entries.retain(|(entry_url, _)| entry_url != url);
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front((url.to_string(), certificate));
+    }
+}
+
+/// Verifies the authenticity of the messages posted by AWS SNS on `/aws/sns`.
+/// Signing certificates are downloaded once and kept in memory by url.
+pub struct SnsVerifier {
+    allowed_topic_arns: Vec<String>,
+    certificates: Mutex<CertificateCache>,
+}
+
+impl SnsVerifier {
+    pub fn new(allowed_topic_arns: Vec<String>) -> Self {
+        SnsVerifier {
+            allowed_topic_arns,
+            certificates: Mutex::new(CertificateCache::new(MAX_CACHED_CERTIFICATES)),
+        }
+    }
+
+    /// Reads the comma separated topic allow-list from `AWS_SNS_TOPIC_ARNS`.
+    pub fn from_env() -> Self {
+        let topic_arns = std::env::var("AWS_SNS_TOPIC_ARNS").unwrap_or_default()
+            .split(',')
+            .map(|arn| arn.trim().to_string())
+            .filter(|arn| !arn.is_empty())
+            .collect();
+
+        SnsVerifier::new(topic_arns)
+    }
+
+    /// Stores a signing certificate for an url, the certificate won't be downloaded.
+    pub fn add_certificate(&self, url: &str, pem: &[u8]) -> AppResult<()> {
+        let certificate = X509::from_pem(pem)
+            .map_err(|err| AppError::BadRequest(format!("Invalid SNS certificate. {}", err)))?;
+        self.certificates.lock().unwrap().insert(url, certificate);
+
+        Ok(())
+    }
+
+    pub async fn verify(&self, message: &SnsMessage) -> AppResult<()> {
+        if !self.allowed_topic_arns.contains(&message.topic_arn) {
+            return Err(AppError::BadRequest(format!("Unknown SNS topic: {}", message.topic_arn)))
+        }
+        check_timestamp(&message.timestamp)?;
+
+        let digest = message.get_digest()?;
+        let string_to_sign = message.get_string_to_sign()?;
+        let signature = base64::decode(&message.signature)
+            .map_err(|_| AppError::BadRequest(String::from("SNS signature is not valid base64.")))?;
+        let certificate = self.get_certificate(&message.signing_cert_url).await?;
+
+        let valid = certificate.public_key()
+            .and_then(|public_key| {
+                let mut verifier = Verifier::new(digest, &public_key)?;
+                verifier.verify_oneshot(&signature, string_to_sign.as_bytes())
+            })
+            .unwrap_or(false);
+
+        if valid {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden)
+        }
+    }
+
+    async fn get_certificate(&self, url: &str) -> AppResult<X509> {
+        let cached = self.certificates.lock().unwrap().get(url);
+        if let Some(certificate) = cached {
+            return check_certificate_validity(certificate)
+        }
+
+        if !is_sns_url(url) || !url.ends_with(".pem") {
+            return Err(AppError::BadRequest(format!("Untrusted SNS certificate url: {}", url)))
+        }
+
+        // a redirection could lead outside of the checked host
+        let client = Client::builder().disable_redirects().finish();
+        let mut response = client.get(url).send().await?;
+        if response.status() != StatusCode::OK {
+            return Err(AppError::ServiceUnavailable(format!("Can't download SNS certificate, status: {}", response.status())))
+        }
+        let body = response.body().await?;
+        self.add_certificate(url, &body)?;
+
+        match self.certificates.lock().unwrap().get(url) {
+            Some(certificate) => check_certificate_validity(certificate),
+            None => Err(AppError::InternalServerError(String::from("SNS certificate cache error.")))
+        }
+    }
+}
+
+fn check_certificate_validity(certificate: X509) -> AppResult<X509> {
+    let now = Asn1Time::days_from_now(0)
+        .map_err(|err| AppError::InternalServerError(err.to_string()))?;
+
+    if certificate.not_before() <= now && certificate.not_after() >= now {
+        Ok(certificate)
+    } else {
+        Err(AppError::BadRequest(String::from("SNS certificate is expired.")))
+    }
+}
+
+fn check_timestamp(timestamp: &str) -> AppResult<()> {
+    let sent_at = DateTime::parse_from_rfc3339(timestamp)
+        .map_err(|_| AppError::BadRequest(format!("Invalid SNS timestamp: {}", timestamp)))?
+        .with_timezone(&Utc);
+    let now = Utc::now();
+
+    if sent_at < now - Duration::minutes(MESSAGE_MAX_AGE_MINUTES) {
+        return Err(AppError::BadRequest(String::from("SNS message is too old.")))
+    }
+    if sent_at > now + Duration::minutes(MESSAGE_MAX_CLOCK_SKEW_MINUTES) {
+        return Err(AppError::BadRequest(String::from("SNS message timestamp is in the future.")))
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::pkey::{PKey, Private};
+    use openssl::rsa::Rsa;
+    use openssl::sign::Signer;
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    const TOPIC_ARN: &str = "arn:aws:sns:eu-west-1:123456789012:flexmatch";
+    const CERT_URL: &str = "https://sns.eu-west-1.amazonaws.com/SimpleNotificationService-test.pem";
+
+    fn new_certificate(not_before_days: i64, not_after_days: i64) -> (PKey<Private>, X509) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "sns.amazonaws.com").unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::from_unix((Utc::now() + Duration::days(not_before_days)).timestamp()).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::from_unix((Utc::now() + Duration::days(not_after_days)).timestamp()).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        (key, builder.build())
+    }
+
+    fn new_message(signature_version: &str) -> SnsMessage {
+        SnsMessage {
+            m_type: String::from("Notification"),
+            message_id: String::from("22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324"),
+            token: None,
+            topic_arn: String::from(TOPIC_ARN),
+            subject: None,
+            message: String::from("{\"detail\":{\"type\":\"MatchmakingSearching\"}}"),
+            timestamp: Utc::now().to_rfc3339(),
+            signature_version: String::from(signature_version),
+            signature: String::new(),
+            signing_cert_url: String::from(CERT_URL),
+            subscribe_url: None,
+        }
+    }
+
+    fn sign(message: &mut SnsMessage, key: &PKey<Private>) {
+        let mut signer = Signer::new(message.get_digest().unwrap(), key).unwrap();
+        let signature = signer.sign_oneshot_to_vec(message.get_string_to_sign().unwrap().as_bytes()).unwrap();
+        message.signature = base64::encode(signature);
+    }
+
+    fn new_verifier(certificate: &X509) -> SnsVerifier {
+        let verifier = SnsVerifier::new(vec![String::from(TOPIC_ARN)]);
+        verifier.add_certificate(CERT_URL, &certificate.to_pem().unwrap()).unwrap();
+
+        verifier
+    }
+
+    #[actix_web::test]
+    async fn verifies_signatures_of_both_versions() {
+        let (key, certificate) = new_certificate(-1, 1);
+        let verifier = new_verifier(&certificate);
+
+        for version in ["1", "2"] {
+            let mut message = new_message(version);
+            sign(&mut message, &key);
+            assert!(verifier.verify(&message).await.is_ok(), "signature version {}", version);
+        }
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_tampered_message() {
+        let (key, certificate) = new_certificate(-1, 1);
+        let verifier = new_verifier(&certificate);
+        let mut message = new_message("2");
+        sign(&mut message, &key);
+        message.message = String::from("{\"detail\":{\"type\":\"MatchmakingSucceeded\"}}");
+
+        assert!(matches!(verifier.verify(&message).await, Err(AppError::Forbidden)));
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_signature_of_another_key() {
+        let (_, certificate) = new_certificate(-1, 1);
+        let (other_key, _) = new_certificate(-1, 1);
+        let verifier = new_verifier(&certificate);
+        let mut message = new_message("2");
+        sign(&mut message, &other_key);
+
+        assert!(matches!(verifier.verify(&message).await, Err(AppError::Forbidden)));
+    }
+
+    #[actix_web::test]
+    async fn rejects_unknown_topics_old_messages_and_expired_certificates() {
+        let (key, certificate) = new_certificate(-1, 1);
+        let verifier = new_verifier(&certificate);
+
+        let mut message = new_message("2");
+        message.topic_arn = String::from("arn:aws:sns:eu-west-1:123456789012:other");
+        sign(&mut message, &key);
+        assert!(matches!(verifier.verify(&message).await, Err(AppError::BadRequest(_))));
+
+        let mut message = new_message("2");
+        message.timestamp = (Utc::now() - Duration::minutes(MESSAGE_MAX_AGE_MINUTES + 1)).to_rfc3339();
+        sign(&mut message, &key);
+        assert!(matches!(verifier.verify(&message).await, Err(AppError::BadRequest(_))));
+
+        let (key, certificate) = new_certificate(-3, -1);
+        let verifier = new_verifier(&certificate);
+        let mut message = new_message("2");
+        sign(&mut message, &key);
+        assert!(matches!(verifier.verify(&message).await, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn only_trusts_sns_urls() {
+        assert!(is_sns_url(CERT_URL));
+        assert!(is_sns_url("https://sns.cn-north-1.amazonaws.com.cn/cert.pem"));
+        assert!(!is_sns_url("http://sns.eu-west-1.amazonaws.com/cert.pem"));
+        assert!(!is_sns_url("https://sns.eu-west-1.amazonaws.com:8443/cert.pem"));
+        assert!(!is_sns_url("https://sns.eu-west-1.amazonaws.com.evil.com/cert.pem"));
+        assert!(!is_sns_url("https://evil.com/sns.eu-west-1.amazonaws.com/cert.pem"));
+    }
+
+    #[test]
+    fn certificate_cache_drops_the_least_recently_used() {
+        let (_, certificate) = new_certificate(-1, 1);
+        let mut cache = CertificateCache::new(2);
+        cache.insert("a", certificate.clone());
+        cache.insert("b", certificate.clone());
+        assert!(cache.get("a").is_some());
+        cache.insert("c", certificate);
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+        assert_eq!(cache.entries.len(), 2);
+    }
+}
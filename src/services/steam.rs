@@ -1,4 +1,4 @@
-use actix_web::{http, http::uri::Builder};
+use actix_web::http;
 use awc::Client;
 use crate::services::make_path_and_query;
 use std::collections::HashMap;
@@ -6,7 +6,11 @@ use crate::errors::{AppResult, AppError};
 use serde::Deserialize;
 use serde_json;
 
-const STEAM_DOMAIN: &str = "partner.steam-api.com";
+pub mod openid;
+#[cfg(debug_assertions)]
+pub mod stub;
+
+const STEAM_API_URL: &str = "https://partner.steam-api.com";
 const UNIVERSAL_STEAM_APP_ID: u64 = 480;
 
 #[derive(Deserialize)]
@@ -15,6 +19,19 @@ pub struct SteamAuthData {
     pub auth_ticket: String
 }
 
+/// Steam account of a verified ticket with its ban flags
+pub struct SteamTicket {
+    pub steam_id: u64,
+    pub vac_banned: bool,
+    pub publisher_banned: bool,
+}
+
+impl SteamTicket {
+    pub fn is_banned(&self) -> bool {
+        self.vac_banned || self.publisher_banned
+    }
+}
+
 #[derive(Deserialize)]
 struct AuthResponseBase<T> {
     pub response: T
@@ -51,20 +68,21 @@ struct Error {
     // error: ErrorResponse
 }
 
-pub async fn authenticate_user_ticket(data: &SteamAuthData) -> AppResult<u64> {
+/// Web API of Steam, `STEAM_API_URL` can point to the local stub
+fn get_api_uri(path: &str, params: &HashMap<&str, String>) -> String {
+    let api_url = std::env::var("STEAM_API_URL").unwrap_or_else(|_| STEAM_API_URL.to_string());
+    format!("{}{}", api_url, make_path_and_query(path, params))
+}
+
+/// Verifies a ticket of the game client, the ban flags are left to the caller so that they can be recorded.
+pub async fn authenticate_user_ticket(data: &SteamAuthData) -> AppResult<SteamTicket> {
     let mut params = HashMap::new();
     params.insert("key", std::env::var("STEAM_SECRET_ACCESS_KEY").unwrap_or_default());
     params.insert("appid", data.app_id.to_string());
     params.insert("ticket", data.auth_ticket.to_string());
 
-    let uri = Builder::new()
-        .scheme("https")
-        .authority(STEAM_DOMAIN)
-        .path_and_query(make_path_and_query(
-            "/ISteamUserAuth/AuthenticateUserTicket/v1", &params))
-        .build()
-        .unwrap();
-    
+    let uri = get_api_uri("/ISteamUserAuth/AuthenticateUserTicket/v1", &params);
+
     let client = Client::default();
     let mut result = client.get(uri)
         .insert_header((http::header::CONTENT_TYPE, "application/x-www-form-urlencoded"))
@@ -74,9 +92,13 @@ pub async fn authenticate_user_ticket(data: &SteamAuthData) -> AppResult<u64> {
     let body = result.body().await?;
     match serde_json::from_slice::<AuthResponseBase<AuthResponse<AuthenticateUserTicketResponse>>>(&body) {
         Ok(steam_response) => {
-            if steam_response.response.params.result == "OK" && !steam_response.response.params.vac_banned &&
-            ! steam_response.response.params.publisher_banned {
-                Ok(steam_response.response.params.steam_id.parse::<u64>().unwrap())
+            let params = steam_response.response.params;
+            if params.result == "OK" {
+                Ok(SteamTicket {
+                    steam_id: params.steam_id.parse::<u64>().map_err(|_| AppError::Unauthorized)?,
+                    vac_banned: params.vac_banned,
+                    publisher_banned: params.publisher_banned,
+                })
             } else {
                 Err(AppError::Unauthorized)
             }
@@ -118,13 +140,8 @@ pub async fn check_app_ownership(app_id: &u64, steam_id: &u64) -> AppResult<()>
         params.insert("appid", app_id.to_string());
         params.insert("steamid", steam_id.to_string());
     
-        let uri = Builder::new()
-            .scheme("https")
-            .authority(STEAM_DOMAIN)
-            .path_and_query(make_path_and_query("/ISteamUser/CheckAppOwnership/v2", &params))
-            .build()
-            .unwrap();
-        
+        let uri = get_api_uri("/ISteamUser/CheckAppOwnership/v2", &params);
+
         let client = Client::default();
         let mut response = client.get(uri)
             .insert_header((http::header::CONTENT_TYPE, "application/x-www-form-urlencoded"))
@@ -146,4 +163,37 @@ pub async fn check_app_ownership(app_id: &u64, steam_id: &u64) -> AppResult<()>
         }
     }
     
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize)]
+struct PlayerBansResponse {
+    pub players: Vec<PlayerBans>
+}
+
+#[derive(Deserialize)]
+struct PlayerBans {
+    #[serde(rename = "VACBanned")]
+    pub vac_banned: bool,
+}
+
+/// Ban flags of a Steam account signed in without a ticket. Only the VAC ban is known this way,
+/// the publisher ban comes with the tickets.
+pub async fn get_player_bans(steam_id: u64) -> AppResult<SteamTicket> {
+    let mut params = HashMap::new();
+    params.insert("key", std::env::var("STEAM_SECRET_ACCESS_KEY").unwrap_or_default());
+    params.insert("steamids", steam_id.to_string());
+    let uri = get_api_uri("/ISteamUser/GetPlayerBans/v1", &params);
+
+    let mut response = Client::default().get(uri)
+        .send()
+        .await?;
+    let body = response.body().await?;
+    match serde_json::from_slice::<PlayerBansResponse>(&body) {
+        Ok(PlayerBansResponse { players }) if players.len() == 1 => Ok(SteamTicket {
+            steam_id,
+            vac_banned: players[0].vac_banned,
+            publisher_banned: false,
+        }),
+        _ => Err(AppError::Unauthorized)
+    }
+}
@@ -0,0 +1,160 @@
+use awc::Client;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::collections::HashMap;
+use crate::app_conf::get_base_url;
+use crate::errors::{AppError, AppResult};
+
+const STEAM_OPENID_ENDPOINT: &str = "https://steamcommunity.com/openid/login";
+pub(super) const OPENID_NS: &str = "http://specs.openid.net/auth/2.0";
+const IDENTIFIER_SELECT: &str = "http://specs.openid.net/auth/2.0/identifier_select";
+pub(super) const CLAIMED_ID_PREFIX: &str = "https://steamcommunity.com/openid/id/";
+pub const CALLBACK_PATH: &str = "/api-open/steam/openid/callback";
+/// Fields of the assertion which have to be covered by the signature of the provider
+pub(super) const SIGNED_FIELDS: [&str; 4] = ["claimed_id", "identity", "return_to", "response_nonce"];
+
+/// Provider of the sign-in, `STEAM_OPENID_ENDPOINT` can point to a local stub
+pub fn get_endpoint() -> String {
+    std::env::var("STEAM_OPENID_ENDPOINT").unwrap_or_else(|_| STEAM_OPENID_ENDPOINT.to_string())
+}
+
+/// Callback URL, the state ties the answer of the provider to the session which started the sign-in.
+fn get_return_to(state: &str) -> String {
+    format!("{}{}?state={}", get_base_url(), CALLBACK_PATH, utf8_percent_encode(state, NON_ALPHANUMERIC))
+}
+
+/// URL of the Steam sign-in page to redirect the browser to
+pub fn get_login_url(state: &str) -> String {
+    let params = [
+        ("openid.ns", OPENID_NS.to_string()),
+        ("openid.mode", String::from("checkid_setup")),
+        ("openid.return_to", get_return_to(state)),
+        ("openid.realm", get_base_url()),
+        ("openid.identity", IDENTIFIER_SELECT.to_string()),
+        ("openid.claimed_id", IDENTIFIER_SELECT.to_string()),
+    ];
+    let query = params.iter()
+        .map(|(key, value)| format!("{}={}", key, utf8_percent_encode(value, NON_ALPHANUMERIC)))
+        .collect::<Vec<String>>()
+        .join("&");
+
+    format!("{}?{}", get_endpoint(), query)
+}
+
+/// Checks the assertion sent to the callback with the provider (`check_authentication`),
+/// returns the Steam id of the user.
+pub async fn verify(params: &HashMap<String, String>, state: &str) -> AppResult<u64> {
+    verify_assertion(params, &get_endpoint(), &get_return_to(state)).await
+}
+
+async fn verify_assertion(params: &HashMap<String, String>, endpoint: &str, return_to: &str) -> AppResult<u64> {
+    let param = |name: &str| params.get(&format!("openid.{}", name)).map(String::as_str);
+
+    if param("mode") != Some("id_res")
+        || param("ns") != Some(OPENID_NS)
+        || param("op_endpoint") != Some(endpoint)
+        || param("return_to") != Some(return_to)
+        || param("identity") != param("claimed_id") {
+        return Err(AppError::Unauthorized);
+    }
+    let signed: Vec<&str> = param("signed").unwrap_or_default().split(',').collect();
+    if !SIGNED_FIELDS.iter().all(|field| signed.contains(field)) {
+        return Err(AppError::Unauthorized);
+    }
+    let steam_id = match param("claimed_id").and_then(|claimed_id| claimed_id.strip_prefix(CLAIMED_ID_PREFIX)) {
+        Some(steam_id) => steam_id.parse::<u64>().map_err(|_| AppError::Unauthorized)?,
+        None => return Err(AppError::Unauthorized)
+    };
+
+    let mut check_params: HashMap<&str, &str> = params.iter()
+        .filter(|(key, _)| key.starts_with("openid."))
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    check_params.insert("openid.mode", "check_authentication");
+
+    let mut response = Client::default()
+        .post(endpoint)
+        .send_form(&check_params)
+        .await?;
+    let body = response.body().await?;
+    let is_valid = String::from_utf8_lossy(&body)
+        .lines()
+        .any(|line| line.trim() == "is_valid:true");
+
+    if is_valid {
+        Ok(steam_id)
+    } else {
+        Err(AppError::Unauthorized)
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+    use actix_web::{App, HttpServer};
+    use super::super::stub;
+
+    const STEAM_ID: u64 = 76561190000000001;
+    const RETURN_TO: &str = "http://localhost:8080/api-open/steam/openid/callback?state=abc";
+
+    /// Starts the stub provider on a free port, returns its endpoint
+    fn start_stub_provider() -> String {
+        let server = HttpServer::new(|| App::new().configure(stub::routes))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let address = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        format!("http://{}{}/openid/login", address, stub::STUB_PATH)
+    }
+
+    /// Assertion the provider sends back to the callback
+    async fn sign_in(endpoint: &str) -> HashMap<String, String> {
+        let login_url = format!("{}?openid.return_to={}&steam_id={}",
+            endpoint, utf8_percent_encode(RETURN_TO, NON_ALPHANUMERIC), STEAM_ID);
+        let response = Client::builder().disable_redirects().finish()
+            .get(login_url)
+            .send()
+            .await
+            .unwrap();
+        let location = response.headers().get("location").unwrap().to_str().unwrap();
+        let query = location.split_once('?').unwrap().1;
+
+        actix_web::web::Query::<HashMap<String, String>>::from_query(query).unwrap()
+            .into_inner()
+            .into_iter()
+            .filter(|(key, _)| key.starts_with("openid."))
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn accepts_an_assertion_checked_by_the_provider() {
+        let endpoint = start_stub_provider();
+        let params = sign_in(&endpoint).await;
+
+        assert_eq!(verify_assertion(&params, &endpoint, RETURN_TO).await.unwrap(), STEAM_ID);
+    }
+
+    #[actix_web::test]
+    async fn rejects_tampered_or_misdirected_assertions() {
+        let endpoint = start_stub_provider();
+        let params = sign_in(&endpoint).await;
+
+        // the provider refuses the signature of another account
+        let mut tampered = params.clone();
+        let claimed_id = format!("{}{}", CLAIMED_ID_PREFIX, STEAM_ID + 1);
+        tampered.insert(String::from("openid.claimed_id"), claimed_id.clone());
+        tampered.insert(String::from("openid.identity"), claimed_id);
+        assert!(matches!(verify_assertion(&tampered, &endpoint, RETURN_TO).await, Err(AppError::Unauthorized)));
+
+        let mut unsigned_nonce = params.clone();
+        unsigned_nonce.insert(String::from("openid.signed"), String::from("op_endpoint,claimed_id,identity,return_to"));
+        assert!(matches!(verify_assertion(&unsigned_nonce, &endpoint, RETURN_TO).await, Err(AppError::Unauthorized)));
+
+        let other_return_to = "http://localhost:8080/api-open/steam/openid/callback?state=other";
+        assert!(matches!(verify_assertion(&params, &endpoint, other_return_to).await, Err(AppError::Unauthorized)));
+        assert!(matches!(
+            verify_assertion(&params, "https://steamcommunity.com/openid/login", RETURN_TO).await,
+            Err(AppError::Unauthorized)));
+    }
+}
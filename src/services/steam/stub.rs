@@ -0,0 +1,116 @@
+use actix_web::{web, HttpRequest, HttpResponse, http::StatusCode};
+use chrono::Utc;
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::Rng;
+use std::collections::HashMap;
+use super::openid::{CLAIMED_ID_PREFIX, OPENID_NS};
+
+pub const STUB_PATH: &str = "/steam-stub";
+const SIGNED_FIELDS: &str = "op_endpoint,claimed_id,identity,return_to,response_nonce";
+
+lazy_static::lazy_static! {
+    /// Key of the assertions, a new one at each start
+    static ref SIGNING_KEY: [u8; 32] = rand::thread_rng().gen();
+}
+
+/// Local stand-in for Steam, mounted in debug builds when `STEAM_STUB_STEAM_ID` is set: the OpenID sign-in answers
+/// with this Steam account right away and the Web API reports a VAC ban only with
+/// `STEAM_STUB_VAC_BANNED=true`. Point `STEAM_OPENID_ENDPOINT` to `<base url>/steam-stub/openid/login`
+/// and `STEAM_API_URL` to `<base url>/steam-stub`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    if std::env::var("STEAM_STUB_STEAM_ID").is_ok() {
+        routes(cfg);
+    }
+}
+
+pub(super) fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope(STUB_PATH)
+        .service(
+            web::resource("/openid/login")
+                .route(web::get().to(openid_login))
+                .route(web::post().to(openid_check_authentication)))
+        .service(
+            web::resource("/ISteamUser/GetPlayerBans/v1")
+                .route(web::get().to(player_bans))));
+}
+
+/// Signs in at once and sends the browser back with a signed assertion, `steam_id` picks another account.
+async fn openid_login(request: HttpRequest, params: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let return_to = match params.get("openid.return_to") {
+        Some(return_to) => return_to,
+        None => return HttpResponse::BadRequest().json("openid.return_to is missing")
+    };
+    let steam_id = params.get("steam_id").cloned()
+        .or_else(|| std::env::var("STEAM_STUB_STEAM_ID").ok())
+        .unwrap_or_default();
+    let connection = request.connection_info();
+    let claimed_id = format!("{}{}", CLAIMED_ID_PREFIX, steam_id);
+
+    let mut assertion: HashMap<String, String> = [
+        ("openid.ns", OPENID_NS.to_string()),
+        ("openid.mode", String::from("id_res")),
+        ("openid.op_endpoint", format!("{}://{}{}", connection.scheme(), connection.host(), request.path())),
+        ("openid.claimed_id", claimed_id.clone()),
+        ("openid.identity", claimed_id),
+        ("openid.return_to", return_to.clone()),
+        ("openid.response_nonce", format!("{}{:08x}", Utc::now().format("%Y-%m-%dT%H:%M:%SZ"), rand::thread_rng().gen::<u32>())),
+        ("openid.assoc_handle", String::from("stub")),
+        ("openid.signed", SIGNED_FIELDS.to_string()),
+    ].iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+    assertion.insert(String::from("openid.sig"), sign(&assertion));
+
+    let query = assertion.iter()
+        .map(|(key, value)| format!("{}={}", key, utf8_percent_encode(value, NON_ALPHANUMERIC)))
+        .collect::<Vec<String>>()
+        .join("&");
+    let separator = if return_to.contains('?') { '&' } else { '?' };
+
+    HttpResponse::TemporaryRedirect()
+        .status(StatusCode::SEE_OTHER)
+        .insert_header(("Location", format!("{}{}{}", return_to, separator, query)))
+        .finish()
+}
+
+/// Tells whether an assertion sent to the relying party was signed by the stub
+async fn openid_check_authentication(params: web::Form<HashMap<String, String>>) -> HttpResponse {
+    let sig = params.get("openid.sig").map(String::as_str).unwrap_or_default();
+    let expected_sig = sign(&params);
+    let is_valid = params.get("openid.mode").map(String::as_str) == Some("check_authentication")
+        && sig.len() == expected_sig.len()
+        && memcmp::eq(sig.as_bytes(), expected_sig.as_bytes());
+
+    HttpResponse::Ok().body(format!("ns:{}\nis_valid:{}\n", OPENID_NS, is_valid))
+}
+
+async fn player_bans(params: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let vac_banned = std::env::var("STEAM_STUB_VAC_BANNED").map(|banned| banned == "true").unwrap_or(false);
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "players": [{
+            "SteamId": params.get("steamids").cloned().unwrap_or_default(),
+            "CommunityBanned": false,
+            "VACBanned": vac_banned,
+            "NumberOfVACBans": if vac_banned { 1 } else { 0 },
+            "DaysSinceLastBan": 0,
+            "NumberOfGameBans": 0,
+            "EconomyBan": "none"
+        }]
+    }))
+}
+
+/// HMAC of the signed fields as `key:value` lines, in the order of `openid.signed`
+fn sign(params: &HashMap<String, String>) -> String {
+    let signed_fields = params.get("openid.signed").map(String::as_str).unwrap_or_default();
+    let message: String = signed_fields.split(',')
+        .map(|field| format!("{}:{}\n", field, params.get(&format!("openid.{}", field)).map(String::as_str).unwrap_or_default()))
+        .collect();
+
+    let key = PKey::hmac(&*SIGNING_KEY).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    signer.update(message.as_bytes()).unwrap();
+
+    base64::encode(signer.sign_to_vec().unwrap())
+}
@@ -0,0 +1,180 @@
+use chrono::{Duration, Utc};
+use diesel::{Connection, PgConnection};
+use diesel::result::Error as DBError;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::app_conf::SECRET_KEY;
+use crate::enums::BanScopes;
+use crate::errors::{AppError, AppResult};
+use crate::models::forms::refresh_token::RefreshTokenForm;
+use crate::models::refresh_token;
+use crate::models::user;
+use crate::services::{ban as ban_service, user as user_service};
+
+const TOKEN_SECRET_LENGTH: usize = 32;
+
+/// Claims of an access token, sent as `base64(claims).base64(HMAC-SHA256 signature)`
+#[derive(Serialize, Deserialize)]
+struct AccessClaims {
+    sub: i32,
+    exp: i64,
+    /// `token_version` of the user when issued
+    ver: i32,
+}
+
+#[derive(Serialize)]
+pub struct TokensDto {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub refresh_token: String,
+}
+
+fn access_token_ttl() -> Duration {
+    Duration::seconds(std::env::var("ACCESS_TOKEN_TTL_SECS").ok()
+        .map(|secs| secs.parse::<i64>().expect("ACCESS_TOKEN_TTL_SECS must be a number of seconds"))
+        .unwrap_or(15 * 60))
+}
+
+fn refresh_token_ttl() -> Duration {
+    Duration::seconds(std::env::var("REFRESH_TOKEN_TTL_SECS").ok()
+        .map(|secs| secs.parse::<i64>().expect("REFRESH_TOKEN_TTL_SECS must be a number of seconds"))
+        .unwrap_or(30 * 24 * 3600))
+}
+
+fn sign(payload: &str) -> AppResult<Vec<u8>> {
+    let to_error = |err: openssl::error::ErrorStack| AppError::InternalServerError(err.to_string());
+    let key = PKey::hmac(SECRET_KEY.as_bytes()).map_err(to_error)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).map_err(to_error)?;
+    signer.update(b"access-token.").map_err(to_error)?;
+    signer.update(payload.as_bytes()).map_err(to_error)?;
+
+    signer.sign_to_vec().map_err(to_error)
+}
+
+pub fn new_access_token(user_id: i32, conn: &PgConnection) -> AppResult<String> {
+    let claims = AccessClaims {
+        sub: user_id,
+        exp: (Utc::now() + access_token_ttl()).timestamp(),
+        ver: user::get_token_version(&user_id, conn)?,
+    };
+    let payload = base64::encode_config(serde_json::to_vec(&claims)?, base64::URL_SAFE_NO_PAD);
+    let signature = base64::encode_config(sign(&payload)?, base64::URL_SAFE_NO_PAD);
+
+    Ok(format!("{}.{}", payload, signature))
+}
+
+/// Returns the user id of a valid access token, not revoked since it was issued
+pub fn verify_access_token(token: &str, conn: &PgConnection) -> Option<i32> {
+    let (payload, signature) = token.split_once('.')?;
+    let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).ok()?;
+    let expected = sign(payload).ok()?;
+    if signature.len() != expected.len() || !openssl::memcmp::eq(&signature, &expected) {
+        return None
+    }
+
+    let claims = serde_json::from_slice::<AccessClaims>(
+        &base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?).ok()?;
+    if claims.exp < Utc::now().timestamp() 
+        || user::get_token_version(&claims.sub, conn).ok()? != claims.ver {
+        return None
+    }
+
+    Some(claims.sub)
+}
+
+fn hash_token_secret(secret: &str) -> String {
+    base64::encode(openssl::sha::sha256(secret.as_bytes()))
+}
+
+/// Creates a refresh token of the form `<token id>.<secret>`, a new family is started when
+/// `family_id` is None.
+fn new_refresh_token(user_id: i32, family_id: Option<Uuid>, conn: &PgConnection) -> AppResult<String> {
+    let token_id = Uuid::new_v4();
+    let secret = base64::encode_config(
+        rand::thread_rng().gen::<[u8; TOKEN_SECRET_LENGTH]>(), 
+        base64::URL_SAFE_NO_PAD);
+
+    refresh_token::create(RefreshTokenForm {
+        token_id: &token_id,
+        family_id: &family_id.unwrap_or_else(Uuid::new_v4),
+        user_id,
+        token_hash: &hash_token_secret(&secret),
+        expires_at: (Utc::now() + refresh_token_ttl()).naive_utc(),
+    }, conn)?;
+
+    Ok(format!("{}.{}", token_id.to_simple(), secret))
+}
+
+fn new_tokens(user_id: i32, family_id: Option<Uuid>, conn: &PgConnection) -> AppResult<TokensDto> {
+    Ok(TokensDto {
+        access_token: new_access_token(user_id, conn)?,
+        token_type: "Bearer",
+        expires_in: access_token_ttl().num_seconds(),
+        refresh_token: new_refresh_token(user_id, family_id, conn)?,
+    })
+}
+
+/// Tokens issued on login
+pub fn issue_tokens(user_id: i32, conn: &PgConnection) -> AppResult<TokensDto> {
+    new_tokens(user_id, None, conn)
+}
+
+/// Exchanges a refresh token for new tokens. A refresh token can be used once, using it
+/// again revokes every token derived from the same login. The account is checked as at login.
+pub fn refresh_tokens(token: &str, conn: &PgConnection) -> AppResult<TokensDto> {
+    let (token_id, secret) = match token.split_once('.') {
+        Some((token_id, secret)) => match Uuid::parse_str(token_id) {
+            Ok(token_id) => (token_id, secret),
+            Err(_) => return Err(AppError::Unauthorized)
+        },
+        None => return Err(AppError::Unauthorized)
+    };
+    let refresh_token = match refresh_token::get_by_token_id(&token_id, conn) {
+        Ok(refresh_token) => refresh_token,
+        Err(DBError::NotFound) => return Err(AppError::Unauthorized),
+        Err(err) => return Err(AppError::InternalServerError(err.to_string()))
+    };
+
+    let secret_hash = hash_token_secret(secret);
+    if secret_hash.len() != refresh_token.token_hash.len()
+        || !openssl::memcmp::eq(secret_hash.as_bytes(), refresh_token.token_hash.as_bytes())
+        || refresh_token.revoked_at.is_some()
+        || refresh_token.is_expired() {
+        return Err(AppError::Unauthorized)
+    }
+
+    let reused = conn.transaction::<bool, DBError, _>(|| {
+        if !refresh_token::mark_used(&refresh_token.id, conn)? {
+            refresh_token::revoke_family(&refresh_token.family_id, conn)?;
+            return Ok(true)
+        }
+        Ok(false)
+    })?;
+    if reused {
+        println!("Refresh token reuse detected for user {}, its family is revoked", refresh_token.user_id);
+        return Err(AppError::Unauthorized)
+    }
+
+    // the account must still be allowed to log in
+    let user = user::get(&refresh_token.user_id, conn)?;
+    if !user.can_login() {
+        return Err(AppError::Forbidden);
+    }
+    ban_service::check(user.id, BanScopes::Login, conn)?;
+    user_service::check_not_deleting(user.id, conn)?;
+
+    new_tokens(refresh_token.user_id, Some(refresh_token.family_id), conn)
+}
+
+/// Ends every access and refresh token of the user
+pub fn revoke_all(user_id: i32, conn: &PgConnection) -> AppResult<()> {
+    conn.transaction(|| {
+        user::increment_token_version(&user_id, conn)?;
+        refresh_token::revoke_all_by_user_id(&user_id, conn)
+    }).map_err(|err: DBError| AppError::InternalServerError(err.to_string()))
+}
@@ -0,0 +1,272 @@
+use chrono::Utc;
+use diesel::{Connection, PgConnection};
+use diesel::result::Error as DBError;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::Rng;
+use serde::Serialize;
+use crate::errors::{AppError, AppResult};
+use crate::models::forms::{recovery_code::RecoveryCodeForm, user_totp::UserTotpForm};
+use crate::models::user::User;
+use crate::models::{recovery_code, user_totp};
+use super::crypto;
+
+const ISSUER: &str = "Rigidity";
+const SECRET_LENGTH: usize = 20;
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Number of steps accepted before and after the current one, for clock drift
+const STEP_WINDOW: i64 = 1;
+/// Secrets are stored encrypted with a key of their own
+const SECRET_PURPOSE: &str = "totp-secret";
+const RECOVERY_CODES_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Secret to add to an authenticator app, once as text and once as an URI for a QR code
+#[derive(Serialize)]
+pub struct TotpSetupDto {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesDto {
+    pub recovery_codes: Vec<String>,
+}
+
+fn to_error(err: openssl::error::ErrorStack) -> AppError {
+    AppError::InternalServerError(err.to_string())
+}
+
+/// RFC 4648 base32 without padding, the encoding authenticator apps expect
+fn base32_encode(data: &[u8]) -> String {
+    let mut result = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    result
+}
+
+/// RFC 4226 HOTP value of a counter
+fn hotp(secret: &[u8], counter: u64) -> AppResult<u32> {
+    let key = PKey::hmac(secret).map_err(to_error)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key).map_err(to_error)?;
+    signer.update(&counter.to_be_bytes()).map_err(to_error)?;
+    let hash = signer.sign_to_vec().map_err(to_error)?;
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+
+    Ok(binary % 10u32.pow(DIGITS))
+}
+
+/// Returns the time step matching a code, if any
+fn find_step(secret: &[u8], code_text: &str) -> AppResult<Option<i64>> {
+    let code = match code_text.parse::<u32>() {
+        Ok(code) if is_totp_code(code_text) => code,
+        _ => return Ok(None)
+    };
+    let current_step = Utc::now().timestamp() / STEP_SECS;
+
+    for step in (current_step - STEP_WINDOW)..=(current_step + STEP_WINDOW) {
+        if hotp(secret, step as u64)? == code {
+            return Ok(Some(step))
+        }
+    }
+
+    Ok(None)
+}
+
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == DIGITS as usize && code.chars().all(|c| c.is_ascii_digit())
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    base64::encode(openssl::sha::sha256(code.as_bytes()))
+}
+
+fn get_totp(user_id: i32, conn: &PgConnection) -> AppResult<Option<user_totp::UserTotp>> {
+    match user_totp::get_by_user_id(&user_id, conn) {
+        Ok(totp) => Ok(Some(totp)),
+        Err(DBError::NotFound) => Ok(None),
+        Err(err) => Err(AppError::from(err))
+    }
+}
+
+/// Checks a code against the secret, a code is accepted once.
+fn verify_totp(totp: &user_totp::UserTotp, code: &str, conn: &PgConnection) -> AppResult<bool> {
+    match find_step(&crypto::decrypt(SECRET_PURPOSE, &totp.secret)?, code)? {
+        Some(step) => Ok(user_totp::use_step(&totp.id, step, conn)?),
+        None => Ok(false)
+    }
+}
+
+pub fn is_enabled(user_id: i32, conn: &PgConnection) -> AppResult<bool> {
+    Ok(get_totp(user_id, conn)?.is_some_and(|totp| totp.is_enabled()))
+}
+
+/// Starts an enrolment with a new secret, 2FA is only enabled once a code is verified.
+pub fn setup(user: &User, conn: &PgConnection) -> AppResult<TotpSetupDto> {
+    if is_enabled(user.id, conn)? {
+        return Err(AppError::BadRequest(String::from("Two-factor authentication is already enabled.")));
+    }
+
+    let raw_secret = rand::thread_rng().gen::<[u8; SECRET_LENGTH]>();
+    let secret = base32_encode(&raw_secret);
+    user_totp::create_pending(UserTotpForm {
+        user_id: user.id,
+        secret: &crypto::encrypt(SECRET_PURPOSE, &raw_secret)?,
+    }, conn)?;
+
+    let otpauth_uri = format!("otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        ISSUER,
+        utf8_percent_encode(&user.email, NON_ALPHANUMERIC),
+        secret,
+        ISSUER,
+        DIGITS,
+        STEP_SECS);
+
+    Ok(TotpSetupDto { secret, otpauth_uri })
+}
+
+/// Finishes an enrolment with a code of the authenticator app.
+/// Returns the recovery codes, they are only shown this time.
+pub fn enable(user_id: i32, code: &str, conn: &PgConnection) -> AppResult<Vec<String>> {
+    let totp = match get_totp(user_id, conn)? {
+        Some(totp) if !totp.is_enabled() => totp,
+        Some(_) => return Err(AppError::BadRequest(String::from("Two-factor authentication is already enabled."))),
+        None => return Err(AppError::BadRequest(String::from("Two-factor authentication setup was not started.")))
+    };
+    if !verify_totp(&totp, &normalize_code(code), conn)? {
+        return Err(AppError::BadRequest(String::from("Invalid code.")));
+    }
+
+    conn.transaction(|| {
+        user_totp::enable(&totp.id, conn)?;
+        new_recovery_codes(user_id, conn)
+    })
+}
+
+/// Turns 2FA off, a current code of the authenticator app is required.
+pub fn disable(user_id: i32, code: &str, conn: &PgConnection) -> AppResult<()> {
+    let totp = match get_totp(user_id, conn)? {
+        Some(totp) if totp.is_enabled() => totp,
+        _ => return Err(AppError::BadRequest(String::from("Two-factor authentication is not enabled.")))
+    };
+    if !verify_totp(&totp, &normalize_code(code), conn)? {
+        return Err(AppError::BadRequest(String::from("Invalid code.")));
+    }
+
+    conn.transaction(|| {
+        user_totp::delete(&user_id, conn)?;
+        recovery_code::delete_all(&user_id, conn)?;
+
+        Ok(())
+    })
+}
+
+/// Checks the second factor of a login, either a code of the authenticator app or a recovery code.
+pub fn verify_code(user_id: i32, code: &str, conn: &PgConnection) -> AppResult<bool> {
+    let totp = match get_totp(user_id, conn)? {
+        Some(totp) if totp.is_enabled() => totp,
+        _ => return Ok(false)
+    };
+
+    let code = normalize_code(code);
+    if is_totp_code(&code) {
+        return verify_totp(&totp, &code, conn);
+    }
+
+    Ok(recovery_code::use_code(&user_id, &hash_recovery_code(&code), conn)?)
+}
+
+fn new_recovery_codes(user_id: i32, conn: &PgConnection) -> AppResult<Vec<String>> {
+    let mut rng = rand::thread_rng();
+    let codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+        .map(|_| (0..RECOVERY_CODE_LENGTH)
+            .map(|_| BASE32_ALPHABET[rng.gen_range(0..BASE32_ALPHABET.len())] as char)
+            .collect())
+        .collect();
+
+    recovery_code::replace_all(
+        &user_id,
+        codes.iter()
+            .map(|code| RecoveryCodeForm {
+                user_id,
+                code_hash: hash_recovery_code(code),
+            })
+            .collect(),
+        conn)?;
+
+    // shown as two groups of five characters, the dash is ignored when checked
+    Ok(codes.iter()
+        .map(|code| format!("{}-{}", &code[..RECOVERY_CODE_LENGTH / 2], &code[RECOVERY_CODE_LENGTH / 2..]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn base32_matches_rfc_4648_without_padding() {
+        let vectors = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+        for (data, encoded) in vectors {
+            assert_eq!(base32_encode(data.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let codes = [755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489];
+        for (counter, code) in codes.iter().enumerate() {
+            assert_eq!(hotp(RFC_SECRET, counter as u64).unwrap(), *code);
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238_sha1_truncated_to_six_digits() {
+        let vectors: [(u64, u32); 6] = [
+            (59, 287082),
+            (1111111109, 81804),
+            (1111111111, 50471),
+            (1234567890, 5924),
+            (2000000000, 279037),
+            (20000000000, 353130),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(hotp(RFC_SECRET, time / STEP_SECS as u64).unwrap(), code);
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use actix::Addr;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{Connection, OptionalExtension, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+use crate::enums::{Languages, UserRoles, UserTokenPurposes};
+use crate::errors::{AppError, AppResult};
+use crate::handlers::user::{ChangeEmailData, ChangePasswordData, DeleteAccountData, UpdateProfileData};
+use crate::models::audit_event::{self, AuditEvent};
+use crate::models::ban::{self, Ban};
+use crate::models::custom_room::{self, CustomRoom, CustomRoomSlot};
+use crate::models::forms::user::UserProfileForm;
+use crate::models::recovery_code::{self, RecoveryCode};
+use crate::models::refresh_token::{self, RefreshToken};
+use crate::models::session::{self, Session};
+use crate::models::steam_ban_record::{self, SteamBanRecord};
+use crate::models::user::{self, User};
+use crate::models::user_token::{self, UserToken};
+use crate::models::user_totp::{self, UserTotp};
+use crate::services::rate_limit::{self as rate_limit_service, RateLimiter};
+use crate::services::token::TokensDto;
+use crate::services::websocket::WebsocketLobby;
+use crate::services::{auth as auth_service, custom_room as custom_room_service, session as session_service, token as token_service, totp as totp_service};
+
+pub mod purger;
+
+const DELETION_GRACE_DAYS: i64 = 30;
+
+#[derive(Serialize)]
+pub struct ProfileDto {
+    #[serde(flatten)]
+    pub user: User,
+    pub role: UserRoles,
+    pub email_confirmation_required: bool,
+    /// New address of an email change waiting for its confirmation
+    pub pending_email: Option<String>,
+    pub two_factor_enabled: bool,
+    pub deletion_scheduled_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct DeletionDto {
+    pub deletion_scheduled_at: NaiveDateTime,
+}
+
+/// Every field stored about a user, `User` hides some of them
+#[derive(Serialize)]
+pub struct AccountDto {
+    pub id: i32,
+    pub email: String,
+    pub nickname: String,
+    pub steam_id: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub birth_date: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub email_confirmation_required: bool,
+    pub pending_email: Option<String>,
+    pub deletion_requested_at: Option<NaiveDateTime>,
+    pub role: UserRoles,
+    pub language: Languages,
+}
+
+impl From<User> for AccountDto {
+    fn from(user: User) -> Self {
+        AccountDto {
+            id: user.id,
+            email: user.email,
+            nickname: user.nickname,
+            steam_id: user.steam_id,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            birth_date: user.birth_date,
+            created_at: user.created_at,
+            email_confirmation_required: user.email_confirmation_required,
+            pending_email: user.pending_email,
+            deletion_requested_at: user.deletion_requested_at,
+            role: user.role,
+            language: user.language,
+        }
+    }
+}
+
+/// Everything we hold about an account, secrets and hashes excepted
+#[derive(Serialize)]
+pub struct AccountExportDto {
+    pub exported_at: NaiveDateTime,
+    pub account: AccountDto,
+    pub sessions: Vec<Session>,
+    pub refresh_tokens: Vec<RefreshToken>,
+    pub user_tokens: Vec<UserToken>,
+    pub two_factor: Option<UserTotp>,
+    pub recovery_codes: Vec<RecoveryCode>,
+    pub custom_room: Option<CustomRoom>,
+    pub custom_room_slot: Option<CustomRoomSlot>,
+    pub bans: Vec<Ban>,
+    pub steam_ban_records: Vec<SteamBanRecord>,
+    pub activity: Vec<AuditEvent>,
+}
+
+/// Time during which a deleted account can be restored, `ACCOUNT_DELETION_GRACE_DAYS` (30 by default)
+pub fn deletion_grace_period() -> Duration {
+    Duration::days(std::env::var("ACCOUNT_DELETION_GRACE_DAYS").ok()
+        .map(|days| days.parse::<i64>().expect("ACCOUNT_DELETION_GRACE_DAYS must be a number of days"))
+        .unwrap_or(DELETION_GRACE_DAYS))
+}
+
+pub fn get_profile(user_id: i32, conn: &PgConnection) -> AppResult<ProfileDto> {
+    let user = user::get(&user_id, conn)?;
+
+    Ok(ProfileDto {
+        role: user.role,
+        email_confirmation_required: user.email_confirmation_required,
+        pending_email: user.pending_email.clone(),
+        two_factor_enabled: totp_service::is_enabled(user_id, conn)?,
+        deletion_scheduled_at: user.deletion_requested_at.map(|requested_at| requested_at + deletion_grace_period()),
+        user,
+    })
+}
+
+pub fn is_nickname_available(nickname: &str, conn: &PgConnection) -> AppResult<bool> {
+    Ok(!user::is_nickname_taken(nickname, conn)?)
+}
+
+pub fn update_profile(user_id: i32, data: &UpdateProfileData, conn: &PgConnection) -> AppResult<ProfileDto> {
+    user::update_profile(&user_id, UserProfileForm {
+        nickname: data.nickname.as_deref(),
+        first_name: data.first_name.as_deref().map(str::trim),
+        last_name: data.last_name.as_deref().map(str::trim),
+        language: data.language,
+    }, conn)?;
+
+    get_profile(user_id, conn)
+}
+
+/// Checks the current password of a sensitive change, failures count towards the account lockout.
+fn check_password(
+    user: &User,
+    password: &str,
+    action: &str,
+    request_ip: Option<&str>,
+    rate_limiter: &RateLimiter
+) -> AppResult<()> {
+    rate_limiter.check(action, request_ip, Some(&user.email))?;
+
+    if !auth_service::verify_password(&user.hash, &user.password_pepper_id, password)? {
+        rate_limiter.record_failure(&user.email)?;
+        return Err(AppError::BadRequest(String::from("Incorrect password.")));
+    }
+    rate_limiter.record_success(&user.email)
+}
+
+/// Checks the current password or, for the accounts with 2FA, a code of a sensitive change.
+/// Failures count towards the account lockout.
+pub fn check_password_or_code(
+    user_id: i32,
+    password: Option<&str>,
+    code: Option<&str>,
+    action: &str,
+    request_ip: Option<&str>,
+    rate_limiter: &RateLimiter,
+    conn: &PgConnection
+) -> AppResult<()> {
+    let user = user::get(&user_id, conn)?;
+    if let Some(password) = password {
+        return check_password(&user, password, action, request_ip, rate_limiter);
+    }
+
+    rate_limiter.check(action, request_ip, Some(&user.email))?;
+    if !totp_service::verify_code(user_id, code.unwrap_or_default(), conn)? {
+        rate_limiter.record_failure(&user.email)?;
+        return Err(AppError::BadRequest(String::from("Incorrect code.")));
+    }
+    rate_limiter.record_success(&user.email)
+}
+
+/// Changes the password, the other sessions and all the refresh tokens are revoked.
+/// Returns new tokens for the client making the change.
+pub fn change_password(
+    user_id: i32,
+    data: &ChangePasswordData,
+    current_session_id: Option<Uuid>,
+    request_ip: Option<&str>,
+    rate_limiter: &RateLimiter,
+    conn: &PgConnection
+) -> AppResult<TokensDto> {
+    let user = user::get(&user_id, conn)?;
+    check_password(&user, &data.current_password, "password-change", request_ip, rate_limiter)?;
+
+    let new_hash = auth_service::hash_password(&data.new_password)?;
+    conn.transaction(|| {
+        user::update_hash(&user_id, &new_hash, auth_service::current_pepper_id(), conn)?;
+        session_service::delete_others(user_id, current_session_id, conn)?;
+        token_service::revoke_all(user_id, conn)?;
+
+        token_service::issue_tokens(user_id, conn)
+    })
+}
+
+/// Changes the email, it has to be confirmed again.
+pub fn change_email(
+    user_id: i32,
+    data: &ChangeEmailData,
+    request_ip: Option<&str>,
+    rate_limiter: &RateLimiter,
+    conn: &PgConnection
+) -> AppResult<()> {
+    let user = user::get(&user_id, conn)?;
+    check_password(&user, &data.password, "email-change", request_ip, rate_limiter)?;
+
+    if user::get_by_email(&data.email, conn).optional()?.is_some() {
+        return Err(AppError::Conflict(String::from("email")));
+    }
+
+    // the current address stays in use until the new one is confirmed
+    conn.transaction(|| {
+        let user = user::set_pending_email(&user_id, &data.email, conn)?;
+        let (token, expire_timestamp) = auth_service::new_user_token(
+            user_id,
+            UserTokenPurposes::EmailConfirmation,
+            request_ip,
+            conn)?;
+
+        auth_service::queue_email_change_emails(&user, &data.email, &token, expire_timestamp, conn)
+    })
+}
+
+/// Links the Steam account of a verified ticket to the user
+pub fn link_steam(user_id: i32, steam_id: u64, conn: &PgConnection) -> AppResult<ProfileDto> {
+    let user = user::get(&user_id, conn)?;
+    let steam_id = steam_id.to_string();
+
+    match &user.steam_id {
+        Some(linked_steam_id) if *linked_steam_id == steam_id => return get_profile(user_id, conn),
+        Some(_) => return Err(AppError::BadRequest(String::from("A Steam account is already linked, unlink it first."))),
+        None => ()
+    }
+    match user::get_by_steam_id(&steam_id, conn).optional()? {
+        Some(_) => return Err(AppError::Conflict(String::from("steam_id"))),
+        None => user::set_steam_id(&user_id, Some(&steam_id), conn)?
+    };
+
+    get_profile(user_id, conn)
+}
+
+/// Unlinks the Steam account, only when the user can still log in with a password
+pub fn unlink_steam(user_id: i32, conn: &PgConnection) -> AppResult<ProfileDto> {
+    let user = user::get(&user_id, conn)?;
+    if user.steam_id.is_none() {
+        return Err(AppError::BadRequest(String::from("No Steam account is linked.")));
+    }
+    if !user.has_password() {
+        return Err(AppError::BadRequest(String::from("Set a password before unlinking Steam, it is the only way to log in.")));
+    }
+    user::set_steam_id(&user_id, None, conn)?;
+
+    get_profile(user_id, conn)
+}
+
+/// Schedules the deletion of the account after the grace period and signs the user out everywhere.
+/// The user leaves its custom room right away.
+pub fn request_deletion(
+    user_id: i32,
+    data: &DeleteAccountData,
+    request_ip: Option<&str>,
+    rate_limiter: &RateLimiter,
+    ws: Addr<WebsocketLobby>,
+    conn: &PgConnection
+) -> AppResult<DeletionDto> {
+    let user = user::get(&user_id, conn)?;
+    check_password(&user, &data.password, "account-deletion", request_ip, rate_limiter)?;
+
+    custom_room_service::handle_websocket_closing(&user_id, ws, conn);
+    let requested_at = Utc::now().naive_utc();
+    conn.transaction(|| {
+        user::set_deletion_requested_at(&user_id, Some(requested_at), conn)?;
+        session_service::delete_all(user_id, conn)?;
+        token_service::revoke_all(user_id, conn)?;
+
+        Ok(DeletionDto { deletion_scheduled_at: requested_at + deletion_grace_period() })
+    })
+}
+
+/// Refuses the accounts whose deletion is pending
+pub fn check_not_deleting(user_id: i32, conn: &PgConnection) -> AppResult<()> {
+    match user::get(&user_id, conn)?.deletion_requested_at {
+        Some(requested_at) => Err(AppError::DeletionPending(requested_at + deletion_grace_period())),
+        None => Ok(())
+    }
+}
+
+/// Restores an account whose deletion is pending
+pub fn cancel_deletion(user_id: i32, conn: &PgConnection) -> AppResult<ProfileDto> {
+    let user = user::get(&user_id, conn)?;
+    if user.deletion_requested_at.is_none() {
+        return Err(AppError::BadRequest(String::from("The account deletion was not requested.")));
+    }
+    user::set_deletion_requested_at(&user_id, None, conn)?;
+
+    get_profile(user_id, conn)
+}
+
+/// Deletes the accounts whose grace period is over, returns how many were deleted.
+/// An account that can't be deleted is logged and retried at the next purge.
+pub fn purge_deleted(conn: &PgConnection) -> AppResult<usize> {
+    let users = user::get_all_deletion_requested_before(
+        Utc::now().naive_utc() - deletion_grace_period(), 
+        conn)?;
+
+    let mut nb_purged = 0;
+    for user in &users {
+        let result = conn.transaction(|| {
+            rate_limit_service::forget_account(&user.email, conn)?;
+            audit_event::anonymize_by_user_id(&user.id, conn)?;
+            // the room of the account goes with it, as does its place in the room of another
+            custom_room::delete(&user.id, conn)?;
+            if let Some(slot) = custom_room::get_slot_by_user_id(&user.id, conn).optional()? {
+                custom_room::delete_slot_by_user_id(&slot.custom_room_id, &user.id, conn)?;
+            }
+            user::delete(&user.id, conn)?;
+
+            Ok::<_, AppError>(())
+        });
+
+        match result {
+            Ok(()) => nb_purged += 1,
+            Err(err) => println!("Purge of the account {} failed: {}", user.id, err)
+        }
+    }
+
+    Ok(nb_purged)
+}
+
+pub fn export(user_id: i32, conn: &PgConnection) -> AppResult<AccountExportDto> {
+    let to_error = |err: diesel::result::Error| AppError::InternalServerError(err.to_string());
+    let user = user::get(&user_id, conn)?;
+
+    Ok(AccountExportDto {
+        exported_at: Utc::now().naive_utc(),
+        sessions: session::get_all_by_user_id(&user_id, conn).map_err(to_error)?,
+        refresh_tokens: refresh_token::get_all_by_user_id(&user_id, conn).map_err(to_error)?,
+        user_tokens: user_token::get_all_by_user_id(&user_id, conn).map_err(to_error)?,
+        two_factor: user_totp::get_by_user_id(&user_id, conn).optional().map_err(to_error)?,
+        recovery_codes: recovery_code::get_all_by_user_id(&user_id, conn).map_err(to_error)?,
+        custom_room: custom_room::get_by_user_id(&user_id, conn).optional().map_err(to_error)?
+            .map(|(custom_room, _slots)| custom_room),
+        custom_room_slot: custom_room::get_slot_by_user_id(&user_id, conn).optional().map_err(to_error)?,
+        bans: ban::get_all_by_user_id(&user_id, conn).map_err(to_error)?,
+        steam_ban_records: steam_ban_record::get_all_by_user_id(&user_id, conn).map_err(to_error)?,
+        activity: audit_event::get_all_by_user_id(&user_id, conn).map_err(to_error)?,
+        account: AccountDto::from(user),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::custom_room::CustomRoomData;
+    use crate::models::forms::{custom_room::CustomRoomSlotForm, user::UserForm};
+
+    fn new_user(name: &str, conn: &PgConnection) -> User {
+        user::create(UserForm {
+            email: &format!("{}@purge.invalid", name),
+            nickname: name,
+            steam_id: None,
+            first_name: name,
+            last_name: name,
+            hash: user::NO_PASSWORD_HASH,
+            password_pepper_id: auth_service::current_pepper_id(),
+            birth_date: Utc::now().naive_utc() - Duration::days(20 * 365),
+            language: Languages::En,
+        }, conn).unwrap()
+    }
+
+    fn new_room(owner: &User, conn: &PgConnection) -> (CustomRoom, Vec<CustomRoomSlot>) {
+        custom_room::create(&owner.id, CustomRoomData {
+            label: owner.nickname.clone(),
+            nb_teams: 2,
+            max_players_per_team: 1,
+            game_mode: None,
+            map: None,
+        }, conn).unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs the database of DATABASE_URL"]
+    fn purge_deletes_the_accounts_still_in_a_room() {
+        let conn = PgConnection::establish(&std::env::var("DATABASE_URL").expect("DATABASE_URL must be set")).unwrap();
+        conn.begin_test_transaction().unwrap();
+
+        let owner = new_user("purged_owner", &conn);
+        let member = new_user("purged_member", &conn);
+        let other_owner = new_user("kept_owner", &conn);
+        new_room(&owner, &conn);
+        let other_room = new_room(&other_owner, &conn);
+        let slot = CustomRoomSlotForm::new_from_user_join(&other_room.0.id, &member.id, &other_room).unwrap();
+        custom_room::create_slot(&slot, &conn).unwrap();
+
+        let expired = Utc::now().naive_utc() - deletion_grace_period() - Duration::days(1);
+        user::set_deletion_requested_at(&owner.id, Some(expired), &conn).unwrap();
+        user::set_deletion_requested_at(&member.id, Some(expired), &conn).unwrap();
+
+        assert!(purge_deleted(&conn).unwrap() >= 2);
+        assert!(user::get(&owner.id, &conn).is_err());
+        assert!(user::get(&member.id, &conn).is_err());
+        assert!(custom_room::get_by_user_id(&owner.id, &conn).is_err());
+        let (_, slots) = custom_room::get(&other_room.0.id, &conn).unwrap();
+        assert_eq!(slots.iter().map(|slot| slot.user_id).collect::<Vec<i32>>(), vec![other_owner.id]);
+    }
+}
@@ -0,0 +1,57 @@
+use actix::prelude::{Actor, Context, AsyncContext, ActorFutureExt, WrapFuture};
+use actix_web::web;
+use std::time::Duration;
+use crate::Pool;
+use crate::errors::AppResult;
+use crate::services::{audit as audit_service, rate_limit as rate_limit_service};
+use super::purge_deleted;
+
+/// Deletes the accounts whose deletion grace period is over, then the audit events without account
+/// past their retention and the rate limit attempts past their window.
+pub struct AccountPurger {
+    interval: Duration,
+    pool: Pool,
+}
+
+impl AccountPurger {
+    pub fn new(interval: Duration, pool: Pool) -> Self {
+        AccountPurger {
+            interval,
+            pool,
+        }
+    }
+}
+
+impl Actor for AccountPurger {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.interval, |act, ctx| {
+            let pool = act.pool.clone();
+            // the queries run on the blocking thread pool, waiting for them keeps purges from overlapping
+            ctx.wait(web::block(move || -> AppResult<(usize, usize, usize)> {
+                let conn = &pool.get().unwrap();
+                Ok((
+                    purge_deleted(conn)?,
+                    audit_service::purge_unattributed(conn)?,
+                    rate_limit_service::purge_stale_attempts(conn)?))
+            })
+                .into_actor(act)
+                .map(|result, _, _| match result {
+                    Ok(Ok((nb_accounts, nb_events, nb_attempts))) => {
+                        if nb_accounts > 0 {
+                            println!("{} deleted accounts purged", nb_accounts);
+                        }
+                        if nb_events > 0 {
+                            println!("{} audit events without account purged", nb_events);
+                        }
+                        if nb_attempts > 0 {
+                            println!("{} rate limit attempts past their window purged", nb_attempts);
+                        }
+                    },
+                    Ok(Err(err)) => println!("Account purge failed: {}", err),
+                    Err(err) => println!("Account purge failed: {}", err)
+                }));
+        });
+    }
+}
@@ -103,6 +103,36 @@ impl BroadcastExceptMessage {
     }
 }
 
+/// Closes the socket of a user, e.g. when a ban is issued
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DisconnectUserMessage {
+    id: i32,
+    reason: String,
+}
+
+impl DisconnectUserMessage {
+    pub fn new(id: &i32, reason: String) -> Self {
+        DisconnectUserMessage {
+            id: *id,
+            reason
+        }
+    }
+
+    pub fn get_id(&self) -> &i32 {
+        &self.id
+    }
+
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/// Asks the lobby how many users have a live socket
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct LiveSessionCountMessage;
+
 pub fn new_connection(
     req: HttpRequest, 
     stream: Payload, 
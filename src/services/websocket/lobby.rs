@@ -1,8 +1,8 @@
-use super::messages::{Connect, Disconnect, WsMessage};
+use super::messages::{Connect, Disconnect, WsClose, WsMessage};
 use actix::prelude::{Actor, Context, Handler};
 use std::collections::HashMap;
 use actix::Addr;
-use super::{ws::WsConn, ForwardMessage, MultiForwardMessage, BroadcastExceptMessage};
+use super::{ws::WsConn, ForwardMessage, MultiForwardMessage, BroadcastExceptMessage, DisconnectUserMessage, LiveSessionCountMessage};
 use crate::{Pool};
 use crate::services::custom_room::handle_websocket_closing as on_custom_room_disconnect;
 
@@ -91,4 +91,23 @@ impl Handler<BroadcastExceptMessage> for Lobby {
     fn handle(&mut self, msg: BroadcastExceptMessage, _: &mut Context<Self>) -> Self::Result {
         self.send_message_to_all_except(msg.get_message(), msg.get_ids_to_except());
     }
-}
\ No newline at end of file
+}
+
+impl Handler<DisconnectUserMessage> for Lobby {
+    type Result = ();
+
+    // the socket sends Disconnect once stopped, the user leaves its custom room there
+    fn handle(&mut self, msg: DisconnectUserMessage, _: &mut Context<Self>) -> Self::Result {
+        if let Some(socket_recipient) = self.sessions.get(msg.get_id()) {
+            socket_recipient.do_send(WsClose(msg.get_reason().to_owned()));
+        }
+    }
+}
+
+impl Handler<LiveSessionCountMessage> for Lobby {
+    type Result = usize;
+
+    fn handle(&mut self, _: LiveSessionCountMessage, _: &mut Context<Self>) -> Self::Result {
+        self.sessions.len()
+    }
+}
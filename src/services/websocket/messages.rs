@@ -8,6 +8,11 @@ use super::lobby::Lobby;
 #[rtype(result = "()")]
 pub struct WsMessage(pub String);
 
+//Lobby sends this to WsConn to close the connexion with the client
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct WsClose(pub String);
+
 //WsConn sends this to the lobby to say "put me in please"
 #[derive(Message)]
 #[rtype(result = "()")]
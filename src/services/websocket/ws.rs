@@ -1,6 +1,6 @@
 use actix::prelude::*;
 use actix::{fut, ActorContext};
-use super::messages::{Disconnect, Connect, WsMessage}; //We'll be writing this later
+use super::messages::{Disconnect, Connect, WsClose, WsMessage}; //We'll be writing this later
 use super::lobby::Lobby; // as well as this
 use actix::{Actor, Addr, Running, StreamHandler, WrapFuture};
 use actix::{AsyncContext, Handler};
@@ -102,4 +102,17 @@ impl Handler<WsMessage> for WsConn {
     fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
         ctx.text(msg.0);
     }
-}
\ No newline at end of file
+}
+
+// server asked to close the connexion
+impl Handler<WsClose> for WsConn {
+    type Result = ();
+
+    fn handle(&mut self, msg: WsClose, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some(msg.0),
+        }));
+        ctx.stop();
+    }
+}
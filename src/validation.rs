@@ -0,0 +1,205 @@
+use chrono::{DateTime, Datelike, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use crate::errors::{AppError, AppResult};
+
+const EMAIL_MAX_LENGTH: usize = 100;
+const NICKNAME_MIN_LENGTH: usize = 3;
+const NICKNAME_MAX_LENGTH: usize = 32;
+const NAME_MAX_LENGTH: usize = 64;
+const PASSWORD_MIN_LENGTH: usize = 8;
+const PASSWORD_MAX_LENGTH: usize = 128;
+const MINIMUM_AGE: i32 = 13;
+const OLDEST_BIRTH_YEAR: i32 = 1900;
+
+/// Error messages of a payload by field, answered as `{"errors": {"<field>": ["<message>"]}}`
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors(BTreeMap<String, Vec<String>>);
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &str, message: &str) {
+        self.0.entry(field.to_string())
+            .or_default()
+            .push(message.to_string());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Checks a payload before it is used, implemented by the request data of the handlers.
+pub trait Validate {
+    fn check(&self, errors: &mut ValidationErrors);
+
+    fn validate(&self) -> AppResult<()> {
+        let mut errors = ValidationErrors::default();
+        self.check(&mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(errors))
+        }
+    }
+}
+
+pub fn required(errors: &mut ValidationErrors, field: &str, value: &str) {
+    if value.trim().is_empty() {
+        errors.add(field, "This field is required.");
+    }
+}
+
+pub fn length(errors: &mut ValidationErrors, field: &str, value: &str, min: usize, max: usize) {
+    let length = value.trim().chars().count();
+    if !(min..=max).contains(&length) {
+        errors.add(field, &format!("Must be {} to {} characters long.", min, max));
+    }
+}
+
+pub fn range(errors: &mut ValidationErrors, field: &str, value: i32, min: i32, max: i32) {
+    if !(min..=max).contains(&value) {
+        errors.add(field, &format!("Must be between {} and {}.", min, max));
+    }
+}
+
+pub fn email(errors: &mut ValidationErrors, field: &str, value: &str) {
+    let is_valid = match value.split_once('@') {
+        Some((local, domain)) => !local.is_empty()
+            && !domain.contains('@')
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !value.chars().any(char::is_whitespace),
+        None => false
+    };
+
+    if !is_valid {
+        errors.add(field, "Invalid email address.");
+    } else if value.len() > EMAIL_MAX_LENGTH {
+        errors.add(field, &format!("Must be at most {} characters long.", EMAIL_MAX_LENGTH));
+    }
+}
+
+pub fn nickname(errors: &mut ValidationErrors, field: &str, value: &str) {
+    length(errors, field, value, NICKNAME_MIN_LENGTH, NICKNAME_MAX_LENGTH);
+    if !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        errors.add(field, "Can only contain letters, digits, '_' and '-'.");
+    }
+}
+
+pub fn name(errors: &mut ValidationErrors, field: &str, value: &str) {
+    length(errors, field, value, 1, NAME_MAX_LENGTH);
+}
+
+pub fn password(errors: &mut ValidationErrors, field: &str, value: &str) {
+    let length = value.chars().count();
+    if !(PASSWORD_MIN_LENGTH..=PASSWORD_MAX_LENGTH).contains(&length) {
+        errors.add(field, &format!("Must be {} to {} characters long.", PASSWORD_MIN_LENGTH, PASSWORD_MAX_LENGTH));
+    }
+}
+
+pub fn birth_date(errors: &mut ValidationErrors, field: &str, value: &DateTime<Utc>) {
+    let today = Utc::now().date_naive();
+    let birth_date = value.date_naive();
+    let mut age = today.year() - birth_date.year();
+    if (today.month(), today.day()) < (birth_date.month(), birth_date.day()) {
+        age -= 1;
+    }
+
+    if birth_date > today || birth_date.year() < OLDEST_BIRTH_YEAR {
+        errors.add(field, "Invalid birth date.");
+    } else if age < MINIMUM_AGE {
+        errors.add(field, &format!("You must be at least {} years old.", MINIMUM_AGE));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    /// Messages of the field after the check
+    fn messages(check: impl Fn(&mut ValidationErrors)) -> Vec<String> {
+        let mut errors = ValidationErrors::default();
+        check(&mut errors);
+        errors.0.remove("field").unwrap_or_default()
+    }
+
+    #[test]
+    fn email_needs_a_local_part_and_a_domain() {
+        assert!(messages(|errors| email(errors, "field", "player@spikegames.eu")).is_empty());
+        for invalid in ["", "player", "@spikegames.eu", "player@spikegames", "player@.eu", "player@spikegames.",
+            "player@spike@games.eu", "pla yer@spikegames.eu"] {
+            assert_eq!(messages(|errors| email(errors, "field", invalid)), vec!["Invalid email address."], "{}", invalid);
+        }
+
+        let too_long = format!("{}@spikegames.eu", "a".repeat(EMAIL_MAX_LENGTH));
+        assert_eq!(messages(|errors| email(errors, "field", &too_long)), vec!["Must be at most 100 characters long."]);
+    }
+
+    #[test]
+    fn nickname_is_bounded_and_restricted_to_simple_characters() {
+        assert!(messages(|errors| nickname(errors, "field", "Spike_0-1")).is_empty());
+        assert_eq!(messages(|errors| nickname(errors, "field", "ab")), vec!["Must be 3 to 32 characters long."]);
+        assert_eq!(messages(|errors| nickname(errors, "field", &"a".repeat(33))), vec!["Must be 3 to 32 characters long."]);
+        assert_eq!(messages(|errors| nickname(errors, "field", "Spike 01")), vec!["Can only contain letters, digits, '_' and '-'."]);
+    }
+
+    #[test]
+    fn length_ignores_surrounding_whitespace_and_counts_characters() {
+        assert_eq!(messages(|errors| length(errors, "field", "   ", 1, 4)), vec!["Must be 1 to 4 characters long."]);
+        assert!(messages(|errors| length(errors, "field", "  room  ", 1, 4)).is_empty());
+        assert!(messages(|errors| length(errors, "field", "éèàç", 1, 4)).is_empty());
+        assert!(messages(|errors| required(errors, "field", " x ")).is_empty());
+        assert_eq!(messages(|errors| required(errors, "field", " ")), vec!["This field is required."]);
+    }
+
+    #[test]
+    fn password_and_range_bounds_are_inclusive() {
+        assert!(messages(|errors| password(errors, "field", &"a".repeat(PASSWORD_MIN_LENGTH))).is_empty());
+        assert!(messages(|errors| password(errors, "field", &"a".repeat(PASSWORD_MAX_LENGTH))).is_empty());
+        assert_eq!(messages(|errors| password(errors, "field", "short")), vec!["Must be 8 to 128 characters long."]);
+        // the whitespace of a password is kept
+        assert!(messages(|errors| password(errors, "field", &" ".repeat(PASSWORD_MIN_LENGTH))).is_empty());
+
+        assert!(messages(|errors| range(errors, "field", 1, 1, 4)).is_empty());
+        assert!(messages(|errors| range(errors, "field", 4, 1, 4)).is_empty());
+        assert_eq!(messages(|errors| range(errors, "field", 5, 1, 4)), vec!["Must be between 1 and 4."]);
+    }
+
+    #[test]
+    fn birth_date_checks_the_minimum_age() {
+        let now = Utc::now();
+        let years_ago = |years: i32| Utc.with_ymd_and_hms(now.year() - years, 1, 1, 0, 0, 0).unwrap();
+
+        assert!(messages(|errors| birth_date(errors, "field", &years_ago(MINIMUM_AGE + 1))).is_empty());
+        assert_eq!(messages(|errors| birth_date(errors, "field", &years_ago(MINIMUM_AGE - 1))),
+            vec!["You must be at least 13 years old."]);
+        assert_eq!(messages(|errors| birth_date(errors, "field", &(now + Duration::days(2)))), vec!["Invalid birth date."]);
+        assert_eq!(messages(|errors| birth_date(errors, "field", &Utc.with_ymd_and_hms(1899, 12, 31, 0, 0, 0).unwrap())),
+            vec!["Invalid birth date."]);
+    }
+
+    #[test]
+    fn validate_collects_every_field() {
+        struct Payload;
+        impl Validate for Payload {
+            fn check(&self, errors: &mut ValidationErrors) {
+                required(errors, "email", "");
+                nickname(errors, "nickname", "a ");
+            }
+        }
+
+        match Payload.validate() {
+            Err(AppError::Validation(errors)) => {
+                assert_eq!(serde_json::to_value(&errors).unwrap(), serde_json::json!({
+                    "email": ["This field is required."],
+                    "nickname": ["Must be 3 to 32 characters long.", "Can only contain letters, digits, '_' and '-'."],
+                }));
+            }
+            _ => panic!("the payload is invalid"),
+        }
+    }
+}